@@ -1,8 +1,7 @@
-use mssql_browser;
+use mssql_browser::{self, BrowserError, BrowserOptions};
 use std::error::Error;
 use std::net::{IpAddr, Ipv4Addr};
 use std::time::Duration;
-use tokio::time::timeout;
 
 const TIMEOUT: Duration = Duration::from_secs(1);
 
@@ -11,6 +10,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let mut args = std::env::args();
     args.next().unwrap();
     let mode = args.next().unwrap_or(String::from("broadcast"));
+    let options = BrowserOptions::new().timeout(Some(TIMEOUT));
 
     match &mode[..] {
         "broadcast" => {
@@ -20,20 +20,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 .unwrap_or(IpAddr::V4(Ipv4Addr::BROADCAST));
             println!("Listening...");
 
-            let mut iterator = mssql_browser::browse(multicast).await?;
+            let mut iterator = mssql_browser::browse_with_options(multicast, &options).await?;
 
-            loop {
-                match timeout(TIMEOUT, iterator.next()).await {
-                    // Found an instance
-                    Ok(res) => println!("Got response: {:#?}", res?),
-
-                    // Timeout expired
-                    Err(_) => {
-                        println!("Timeout expired");
-                        break;
-                    }
-                }
+            // Found an instance
+            while let Some(res) = iterator.next().await? {
+                println!("Got response: {:#?}", res);
             }
+
+            // Timeout expired
+            println!("Timeout expired");
         }
         "host" => {
             let remote_addr = args
@@ -42,18 +37,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 .unwrap_or(IpAddr::V4(Ipv4Addr::BROADCAST));
             println!("Listening...");
 
-            match timeout(TIMEOUT, mssql_browser::browse_host(remote_addr)).await {
+            match mssql_browser::browse_host_with_options(remote_addr, &options).await {
                 // Found one or more instances
-                Ok(iterator) => {
-                    let mut iterator = iterator?;
-
+                Ok(mut iterator) => {
                     while let Some(res) = iterator.next()? {
                         println!("Got response: {:#?}", res);
                     }
                 }
 
                 // Timeout expired
-                Err(_) => println!("Timeout expired"),
+                Err(BrowserError::Timeout(_)) => println!("Timeout expired"),
+
+                // Host is up, but the browser service isn't
+                Err(BrowserError::BrowserNotRunning(_)) => println!("Browser not running"),
+                Err(e) => return Err(e.into()),
             }
         }
         "instance" => {
@@ -65,17 +62,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
             println!("Listening...");
 
-            match timeout(
-                TIMEOUT,
-                mssql_browser::browse_instance(remote_addr, &instance_name),
-            )
-            .await
+            match mssql_browser::browse_instance_with_options(remote_addr, &instance_name, &options)
+                .await
             {
                 // Found an instance
-                Ok(res) => println!("Got response: {:#?}", res?),
+                Ok(res) => println!("Got response: {:#?}", res),
 
                 // Timeout expired
-                Err(_) => println!("Timeout expired"),
+                Err(BrowserError::Timeout(_)) => println!("Timeout expired"),
+
+                // Host is up, but the browser service isn't
+                Err(BrowserError::BrowserNotRunning(_)) => println!("Browser not running"),
+                Err(e) => return Err(e.into()),
             }
         }
         "instance_dac" => {
@@ -87,17 +85,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
             println!("Listening...");
 
-            match timeout(
-                TIMEOUT,
-                mssql_browser::browse_instance_dac(remote_addr, &instance_name),
+            match mssql_browser::browse_instance_dac_with_options(
+                remote_addr,
+                &instance_name,
+                &options,
             )
             .await
             {
                 // Found an instance
-                Ok(res) => println!("Got response: {:#?}", res?),
+                Ok(res) => println!("Got response: {:#?}", res),
 
                 // Timeout expired
-                Err(_) => println!("Timeout expired"),
+                Err(BrowserError::Timeout(_)) => println!("Timeout expired"),
+
+                // Host is up, but the browser service isn't
+                Err(BrowserError::BrowserNotRunning(_)) => println!("Browser not running"),
+                Err(e) => return Err(e.into()),
             }
         }
         m => println!("Invalid mode {:?}", m),
@@ -0,0 +1,76 @@
+use std::net::Ipv4Addr;
+
+/// Gets the IPv4 limited broadcast address `255.255.255.255`, which reaches
+/// every host on the local network segment but is never forwarded by routers.
+///
+/// # Examples
+/// ```
+/// assert_eq!(mssql_browser::limited_broadcast(), std::net::Ipv4Addr::BROADCAST);
+/// ```
+pub fn limited_broadcast() -> Ipv4Addr {
+    Ipv4Addr::BROADCAST
+}
+
+/// Computes the directed broadcast address of the given IPv4 subnet, which has all host bits set.
+///
+/// # Arguments
+/// * `network` - Any address in the subnet.
+/// * `prefix` - The length of the network prefix of the subnet.
+///
+/// # Panics
+/// Panics if `prefix` is larger than 32.
+///
+/// # Examples
+/// ```
+/// use std::net::Ipv4Addr;
+/// use mssql_browser::directed_broadcast;
+///
+/// assert_eq!(directed_broadcast(Ipv4Addr::new(10, 0, 5, 0), 24), Ipv4Addr::new(10, 0, 5, 255));
+/// assert_eq!(directed_broadcast(Ipv4Addr::new(172, 16, 3, 7), 20), Ipv4Addr::new(172, 16, 15, 255));
+/// ```
+pub fn directed_broadcast(network: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    assert!(prefix <= 32, "prefix length must be at most 32");
+
+    let host_mask = u32::MAX.checked_shr(u32::from(prefix)).unwrap_or(0);
+    Ipv4Addr::from(u32::from(network) | host_mask)
+}
+
+/// A local IPv4 interface address along with the length of the prefix of its subnet,
+/// from which `browse` can send its request, see `BrowserOptions::interfaces`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Interface {
+    /// The address of the interface.
+    pub addr: Ipv4Addr,
+
+    /// The length of the network prefix of the subnet the interface is on.
+    pub prefix: u8,
+}
+
+impl Ipv4Interface {
+    /// Gets the directed broadcast address of the subnet the interface is on.
+    ///
+    /// # Panics
+    /// Panics if `prefix` is larger than 32.
+    pub fn broadcast(&self) -> Ipv4Addr {
+        directed_broadcast(self.addr, self.prefix)
+    }
+}
+
+/// Enumerates the IPv4 addresses of the local network interfaces, skipping loopback interfaces.
+/// Pass the result to `BrowserOptions::interfaces` to browse all subnets of a multi-homed host.
+#[cfg(feature = "if-addrs")]
+pub fn local_ipv4_interfaces() -> std::io::Result<Vec<Ipv4Interface>> {
+    let interfaces = if_addrs::get_if_addrs()?
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .filter_map(|interface| match interface.addr {
+            if_addrs::IfAddr::V4(addr) => Some(Ipv4Interface {
+                addr: addr.ip,
+                prefix: addr.prefixlen,
+            }),
+            if_addrs::IfAddr::V6(_) => None,
+        })
+        .collect();
+
+    Ok(interfaces)
+}
@@ -1,21 +1,29 @@
 use super::error::*;
+use super::in_flight::InFlight;
 use super::info::*;
-use super::socket::{UdpSocket, UdpSocketFactory};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use super::observer::DatagramDirection;
+use super::options::BrowserOptions;
+use super::socket::{bind_socket_at, recv_from_deadline, UdpSocket, UdpSocketFactory};
+use super::stats::BrowseOperation;
+use futures::future;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::time::{Duration, Instant};
 
 /// The CLNT_BCAST_EX packet is a broadcast or multicast request that is generated by clients that are trying
 /// to identify the list of database instances on the network and their network protocol connection information.
 const CLNT_BCAST_EX: u8 = 0x02;
 
-/// The server responds to all client requests with an SVR_RESP.
-const SVR_RESP: u8 = 0x05;
+/// The link-local all-nodes multicast address, to which the IPv6 request is sent in dual-stack mode.
+const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
 
 /// Discovers any SQL Server instances running on hosts reached by
 /// the given multicast address.
 ///
 /// # Arguments
 /// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
-///                      This can be the Ipv4 BROADCAST address, or a Ipv6 multicast address.
+///                      This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///                      group, see `BrowserOptions::join_multicast_group`.
 #[cfg(any(feature = "tokio", feature = "async-std"))]
 pub async fn browse(
     multicast_addr: IpAddr,
@@ -30,12 +38,36 @@ pub async fn browse(
     browse_inner(multicast_addr, &mut factory).await
 }
 
+/// Discovers any SQL Server instances running on hosts reached by
+/// the given multicast address, using the given options.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///                      This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///                      group, see `BrowserOptions::join_multicast_group`.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_with_options(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+) -> Result<
+    AsyncInstanceIterator<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_with_options_inner(multicast_addr, options, &mut factory).await
+}
+
 /// Discovers any SQL Server instances running on hosts reached by
 /// the given multicast address.
 ///
 /// # Arguments
 /// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
-///                      This can be the Ipv4 BROADCAST address, or a Ipv6 multicast address.
+///                      This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///                      group, see `BrowserOptions::join_multicast_group`.
 pub async fn browse_inner<SF: UdpSocketFactory>(
     multicast_addr: IpAddr,
     socket_factory: &mut SF,
@@ -43,104 +75,536 @@ pub async fn browse_inner<SF: UdpSocketFactory>(
     AsyncInstanceIterator<SF::Socket>,
     BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
 > {
-    let local_addr = if multicast_addr.is_ipv4() {
-        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
-    } else {
-        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
-    };
+    browse_with_options_inner(multicast_addr, &BrowserOptions::default(), socket_factory).await
+}
+
+/// Discovers any SQL Server instances running on hosts reached by
+/// the given multicast address, using the given options.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///                      This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///                      group, see `BrowserOptions::join_multicast_group`.
+/// * `options` - The options to use for this operation.
+pub async fn browse_with_options_inner<SF: UdpSocketFactory>(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<
+    AsyncInstanceIterator<SF::Socket>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    let local_ip = options.bind_ip(&multicast_addr);
+    let mut socket = bind_browse_socket(multicast_addr, local_ip, options, socket_factory).await?;
+    let membership = join_group(&socket, multicast_addr, options)?;
+    let remote = browse_remote(multicast_addr, options);
+    let sent_at = send_request(&mut socket, &remote, options).await?;
+
+    let probes_remaining = options.probe_count - 1;
+
+    let mut extra_sockets = Vec::new();
+    if options.dual_stack {
+        let other_addr = match multicast_addr {
+            IpAddr::V4(_) => IpAddr::V6(IPV6_ALL_NODES),
+            IpAddr::V6(_) => IpAddr::V4(Ipv4Addr::BROADCAST),
+        };
 
-    let bind_to = SocketAddr::new(local_addr, 0);
-    let mut socket = socket_factory
-        .bind(&bind_to)
+        let local_ip = options.bind_ip(&other_addr);
+        extra_sockets.push(ExtraSocket::open(other_addr, local_ip, options, socket_factory).await?);
+    }
+
+    // Directed broadcasts of the subnets of the configured interfaces
+    if multicast_addr.is_ipv4() && !multicast_addr.is_multicast() {
+        for interface in &options.interfaces {
+            let addr = IpAddr::V4(interface.broadcast());
+            let local_ip = IpAddr::V4(interface.addr);
+            extra_sockets.push(ExtraSocket::open(addr, local_ip, options, socket_factory).await?);
+        }
+    }
+
+    Ok(AsyncInstanceIterator {
+        socket: socket,
+        membership,
+        in_flight: InFlight::default(),
+        remote,
+        extra_sockets,
+        discovery_method: DiscoveryMethod::of(multicast_addr, options.broadcast),
+        options: options.clone(),
+        buffer: vec![0u8; options.recv_buffer_size],
+        buffer_len: 0,
+        sent_at,
+        deadline: options.timeout.map(|timeout| sent_at + timeout),
+        probes_remaining,
+        next_probe_at: next_probe_at(sent_at, probes_remaining, options),
+        responders: HashSet::new(),
+        seen_instances: HashSet::new(),
+        last_new_responder_at: sent_at,
+        current_source: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
+        current_rtt: Duration::default(),
+        current_header: ResponseHeader {
+            kind: 0,
+            length: 0,
+            datagram_length: 0,
+        },
+        current_discovery_method: DiscoveryMethod::of(multicast_addr, options.broadcast),
+        current_offset: 0,
+    })
+}
+
+/// Binds a socket to `local_ip` for sending the CLNT_BCAST_EX request to the given address.
+/// The socket is never connected, as that would filter out the responses of all but one host.
+async fn bind_browse_socket<SF: UdpSocketFactory>(
+    multicast_addr: IpAddr,
+    local_ip: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<SF::Socket, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    // IPv6 has no broadcast, only multicast. Other IPv4 addresses might be directed broadcasts,
+    // which can't be told apart from unicast addresses without knowing the subnet.
+    let broadcast = options.broadcast && multicast_addr.is_ipv4() && !multicast_addr.is_multicast();
+
+    let mut socket = bind_socket_at(socket_factory, local_ip, options, broadcast)
         .await
         .map_err(BrowserError::BindFailed)?;
 
-    socket
-        .enable_broadcast()
-        .await
-        .map_err(BrowserError::SetBroadcastFailed)?;
+    if broadcast {
+        socket
+            .enable_broadcast()
+            .await
+            .map_err(BrowserError::SetBroadcastFailed)?;
+    }
 
+    Ok(socket)
+}
+
+/// A multicast group joined by the socket of an `AsyncInstanceIterator`.
+#[derive(Clone, Copy)]
+enum Membership {
+    V4(Ipv4Addr, Ipv4Addr),
+    V6(Ipv6Addr, u32),
+}
+
+impl Membership {
+    /// Leaves the group again. Failures are ignored, as closing the socket leaves it as well.
+    fn leave<S: UdpSocket>(self, socket: &S) {
+        let _ = match self {
+            Membership::V4(group, interface) => socket.leave_multicast_v4(&group, &interface),
+            Membership::V6(group, interface) => socket.leave_multicast_v6(&group, interface),
+        };
+    }
+}
+
+/// Joins the group `multicast_addr` if enabled via `BrowserOptions::join_multicast_group`.
+fn join_group<SFError: std::error::Error, S: UdpSocket>(
+    socket: &S,
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+) -> Result<Option<Membership>, BrowserError<SFError, S::Error>> {
+    if !options.join_multicast_group || !multicast_addr.is_multicast() {
+        return Ok(None);
+    }
+
+    let membership = match multicast_addr {
+        IpAddr::V4(group) => {
+            let interface = match options.bind_ip(&multicast_addr) {
+                IpAddr::V4(ip) => ip,
+                IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+            };
+            socket
+                .join_multicast_v4(&group, &interface)
+                .map(|_| Membership::V4(group, interface))
+        }
+        IpAddr::V6(group) => {
+            let interface = options.multicast_interface_v6.unwrap_or(0);
+            socket
+                .join_multicast_v6(&group, interface)
+                .map(|_| Membership::V6(group, interface))
+        }
+    };
+
+    membership
+        .map(Some)
+        .map_err(|e| BrowserError::JoinMulticastFailed(multicast_addr, e))
+}
+
+/// Gets the address to send the CLNT_BCAST_EX request for `addr` to. IPv6 addresses are scoped
+/// to the interface configured via `BrowserOptions::multicast_interface_v6`, if any.
+fn browse_remote(addr: IpAddr, options: &BrowserOptions) -> SocketAddr {
+    match (addr, options.multicast_interface_v6) {
+        (IpAddr::V6(addr), Some(index)) => SocketAddr::V6(SocketAddrV6::new(addr, 1434, 0, index)),
+        (addr, _) => SocketAddr::new(addr, 1434),
+    }
+}
+
+/// Sends the CLNT_BCAST_EX request to the given address, returning the point in time it was sent at.
+async fn send_request<SFError: std::error::Error, S: UdpSocket>(
+    socket: &mut S,
+    remote: &SocketAddr,
+    options: &BrowserOptions,
+) -> Result<Instant, BrowserError<SFError, S::Error>> {
     let buffer = [CLNT_BCAST_EX];
-    let remote = SocketAddr::new(multicast_addr, 1434);
+    let sent_at = options.now();
     socket
-        .send_to(&buffer, &remote)
+        .send_to(&buffer, remote)
         .await
-        .map_err(|e| BrowserError::SendFailed(remote, e))?;
+        .map_err(|e| BrowserError::SendFailed(*remote, e))?;
+    options.notify(DatagramDirection::Sent, remote, &buffer);
+    options.record(BrowseOperation::Broadcast, |s| s.record_request_sent());
 
-    Ok(AsyncInstanceIterator {
-        socket: socket,
-        buffer: Vec::new(),
-        current_remote_addr: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-        current_offset: 0,
-    })
+    Ok(sent_at)
+}
+
+/// Gets the point in time at which to send the next probe, if any remain.
+fn next_probe_at(
+    sent_at: Instant,
+    probes_remaining: usize,
+    options: &BrowserOptions,
+) -> Option<Instant> {
+    if probes_remaining > 0 {
+        Some(sent_at + options.probe_delay())
+    } else {
+        None
+    }
+}
+
+/// A socket the request is sent over in addition to the one `browse` was called for, either for
+/// the other IP version in dual-stack mode or for one of the configured interfaces.
+/// Each has its own buffer, as all sockets are received from at the same time.
+struct ExtraSocket<S: UdpSocket> {
+    socket: S,
+    remote: SocketAddr,
+    discovery_method: DiscoveryMethod,
+    buffer: Vec<u8>,
+}
+
+impl<S: UdpSocket> ExtraSocket<S> {
+    /// Binds a socket to `local_ip` and sends the request to `addr` over it.
+    async fn open<SF: UdpSocketFactory<Socket = S>>(
+        addr: IpAddr,
+        local_ip: IpAddr,
+        options: &BrowserOptions,
+        socket_factory: &mut SF,
+    ) -> Result<ExtraSocket<S>, BrowserError<SF::Error, S::Error>> {
+        let mut socket = bind_browse_socket(addr, local_ip, options, socket_factory).await?;
+        let remote = browse_remote(addr, options);
+        send_request(&mut socket, &remote, options).await?;
+
+        Ok(ExtraSocket {
+            socket,
+            remote,
+            discovery_method: DiscoveryMethod::of(addr, options.broadcast),
+            buffer: Vec::new(),
+        })
+    }
 }
 
 /// Iterates over the instances returned by `browse`
 pub struct AsyncInstanceIterator<S: UdpSocket> {
     socket: S,
+    membership: Option<Membership>,
+    in_flight: InFlight,
+    remote: SocketAddr,
+    extra_sockets: Vec<ExtraSocket<S>>,
+    discovery_method: DiscoveryMethod,
+    options: BrowserOptions,
     buffer: Vec<u8>,
+    buffer_len: usize,
+    sent_at: Instant,
+    deadline: Option<Instant>,
+    probes_remaining: usize,
+    next_probe_at: Option<Instant>,
+    responders: HashSet<SocketAddr>,
+    seen_instances: HashSet<(SocketAddr, String)>,
+    last_new_responder_at: Instant,
 
-    current_remote_addr: IpAddr,
+    current_source: SocketAddr,
+    current_rtt: Duration,
+    current_header: ResponseHeader,
+    current_discovery_method: DiscoveryMethod,
     current_offset: usize,
 }
 
 impl<S: UdpSocket> AsyncInstanceIterator<S> {
     /// Gets the next received instance information. You can call this method multiple
-    /// times to receive information about multiple instances until it returns Ok(None).
+    /// times to receive information about multiple instances until it returns Ok(None),
+    /// which happens once the timeout or quiet period configured in the options expired,
+    /// or all expected responders have answered. Each instance is returned once per
+    /// responding address, even if the host answers more than one request.
+    ///
+    /// A host may spread its instances over several datagrams, each of which is a complete
+    /// SVR_RESP and is parsed on its own, so the instances of all of them are returned.
+    /// As a host counts as having responded after its first datagram, waiting for a number of
+    /// expected responders might stop before later datagrams of the last host arrive.
+    ///
+    /// A response that can't be parsed is reported as a `BrowserError::ProtocolError`,
+    /// after which the iterator remains usable and continues with the next response.
+    /// The address of the responder is available through `last_source`.
+    ///
+    /// This method is cancellation safe: by the contract of `UdpSocket`, receiving a datagram
+    /// either completes or leaves it queued on the socket, so dropping the returned future, e.g. in a `select!`, and calling
+    /// `next` again neither loses nor repeats any instance.
     pub async fn next(
         &mut self,
-    ) -> Result<InstanceInfo, BrowserError<std::convert::Infallible, S::Error>> {
+    ) -> Result<Option<InstanceInfo>, BrowserError<std::convert::Infallible, S::Error>> {
         loop {
-            if self.current_offset >= self.buffer.len() {
-                // Need to receive a new packet
+            if self.current_offset >= self.buffer_len {
+                if self.all_expected_responded() {
+                    return Ok(None);
+                }
+
+                // Need to receive a new packet. Mark the buffer as consumed first, so if the
+                // future is dropped while receiving, the next call doesn't parse the stale buffer.
+                // The buffer is reused as is, only its first `buffer_len` bytes are ever read.
                 // TODO: Find a way to determine buffer size based on FIONREAD
                 // once/if ever tokio supports it
-                self.buffer.resize_with(65535 + 3, Default::default);
+                self.current_offset = std::usize::MAX;
 
-                let (bytes_received, remote_addr) = self
-                    .socket
-                    .recv_from(&mut self.buffer)
-                    .await
-                    .map_err(BrowserError::ReceiveFailed)?;
+                let deadline = self.current_deadline();
+                let probe_due = match (self.next_probe_at, deadline) {
+                    (Some(probe_at), Some(deadline)) => probe_at < deadline,
+                    (probe_at, _) => probe_at.is_some(),
+                };
 
-                self.current_remote_addr = remote_addr.ip();
+                // Wait for the next probe instead if it's due before the deadline
+                self.in_flight.start(&self.remote);
+                let received = if probe_due {
+                    self.recv_from_any(self.next_probe_at).await
+                } else {
+                    self.recv_from_any(deadline).await
+                };
+                self.in_flight.finish();
 
-                if bytes_received < 3 || self.buffer[0] != SVR_RESP {
-                    self.current_offset = std::usize::MAX;
-                    continue;
-                }
+                let (bytes_received, remote_addr) =
+                    match received.map_err(BrowserError::ReceiveFailed)? {
+                        Some(x) => x,
+                        None if probe_due => {
+                            self.send_probe().await?;
+                            continue;
+                        }
+                        None => return Ok(None),
+                    };
 
-                let resp_data_len = u16::from_le_bytes([self.buffer[1], self.buffer[2]]);
-                if resp_data_len as usize != bytes_received - 3 {
-                    self.current_offset = std::usize::MAX;
-                    continue;
-                }
+                self.options.notify(
+                    DatagramDirection::Received,
+                    &remote_addr,
+                    &self.buffer[..bytes_received],
+                );
+                self.options
+                    .record(BrowseOperation::Broadcast, |s| s.record_response_received());
+                self.current_source = remote_addr;
+                self.current_rtt = self.options.now() - self.sent_at;
 
-                // Validate that the buffer is valid utf-8
-                // TODO: Decode mbcs string
-                if std::str::from_utf8(&self.buffer[3..]).is_err() {
-                    self.current_offset = std::usize::MAX;
-                    continue;
+                self.buffer_len = bytes_received;
+                self.current_header = match self.validate_datagram() {
+                    Ok(header) => header,
+                    Err(e) => return Err(self.skip_invalid_datagram(e)),
+                };
+
+                if self.responders.insert(remote_addr) {
+                    self.last_new_responder_at = self.options.now();
                 }
 
-                self.buffer.truncate(bytes_received);
+                let rtt = self.current_rtt;
+                self.options
+                    .record(BrowseOperation::Broadcast, |s| s.record_rtt(rtt));
                 self.current_offset = 3;
+
+                // The response might not describe any instances
+                continue;
             }
 
             // UNSAFE: Buffer is already validated to be valid utf-8 when the iterator was created
-            let as_str =
-                unsafe { std::str::from_utf8_unchecked(&self.buffer[self.current_offset..]) };
+            let as_str = unsafe {
+                std::str::from_utf8_unchecked(&self.buffer[self.current_offset..self.buffer_len])
+            };
 
-            let (instance, consumed) = match parse_instance_info(self.current_remote_addr, as_str) {
+            let (mut instance, consumed) = match parse_instance_info(
+                self.current_source.ip(),
+                as_str,
+                self.options.strict_parsing,
+            ) {
                 Ok(x) => x,
-                Err(_) => {
-                    self.current_offset = std::usize::MAX;
-                    continue;
-                }
+                Err(e) => return Err(self.skip_invalid_datagram(e)),
             };
 
             self.current_offset += consumed;
-            return Ok(instance);
+
+            // Hosts answer each probe that reaches them
+            let key = (
+                self.current_source,
+                instance.instance_name.to_ascii_uppercase(),
+            );
+            if !self.seen_instances.insert(key) {
+                continue;
+            }
+
+            instance.rtt = Some(self.current_rtt);
+            instance.header = Some(self.current_header);
+            instance.source = Some(self.current_source);
+            instance.discovery_method = self.current_discovery_method;
+            return Ok(Some(instance));
+        }
+    }
+
+    /// Sends the request again over the same socket, e.g. periodically in a long running
+    /// discovery process, so instances that came online since are discovered too.
+    /// The timeout, quiet period and expected number of responders apply anew from now on,
+    /// as does the configured number of probes, while instances that were returned before
+    /// aren't returned again.
+    pub async fn rebroadcast(
+        &mut self,
+    ) -> Result<(), BrowserError<std::convert::Infallible, S::Error>> {
+        let sent_at = self.send_to_all().await?;
+
+        self.sent_at = sent_at;
+        self.deadline = self.options.timeout.map(|timeout| sent_at + timeout);
+        self.probes_remaining = self.options.probe_count - 1;
+        self.next_probe_at = next_probe_at(sent_at, self.probes_remaining, &self.options);
+        self.last_new_responder_at = sent_at;
+        self.responders.clear();
+        Ok(())
+    }
+
+    /// Forgets the instances returned so far, so they are returned again when hosts answer
+    /// the next request, e.g. to take a full snapshot of the network on every `rebroadcast`.
+    pub fn clear_seen(&mut self) {
+        self.seen_instances.clear();
+    }
+
+    /// Sends one of the additional probes configured via `BrowserOptions::probe_count`.
+    /// Round trip times and the timeout remain relative to the first request.
+    async fn send_probe(&mut self) -> Result<(), BrowserError<std::convert::Infallible, S::Error>> {
+        let sent_at = self.send_to_all().await?;
+
+        self.probes_remaining -= 1;
+        self.next_probe_at = next_probe_at(sent_at, self.probes_remaining, &self.options);
+        Ok(())
+    }
+
+    /// Sends the request over every socket, returning the point in time it was sent at.
+    async fn send_to_all(
+        &mut self,
+    ) -> Result<Instant, BrowserError<std::convert::Infallible, S::Error>> {
+        let sent_at = send_request(&mut self.socket, &self.remote, &self.options).await?;
+        for extra in &mut self.extra_sockets {
+            send_request(&mut extra.socket, &extra.remote, &self.options).await?;
+        }
+
+        Ok(sent_at)
+    }
+
+    /// Receives the next datagram into `buffer` from whichever socket receives one first.
+    async fn recv_from_any(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(usize, SocketAddr)>, S::Error> {
+        if self.extra_sockets.is_empty() {
+            return recv_from_deadline(
+                &mut self.socket,
+                &mut self.buffer,
+                deadline,
+                &*self.options.clock,
+            )
+            .await;
+        }
+
+        let buffer_size = self.buffer.len();
+        let mut receives = vec![Box::pin(recv_from_deadline(
+            &mut self.socket,
+            &mut self.buffer,
+            deadline,
+            &*self.options.clock,
+        ))];
+        for extra in &mut self.extra_sockets {
+            extra.buffer.resize_with(buffer_size, Default::default);
+            receives.push(Box::pin(recv_from_deadline(
+                &mut extra.socket,
+                &mut extra.buffer,
+                deadline,
+                &*self.options.clock,
+            )));
+        }
+
+        // The receives that didn't complete are dropped, leaving their datagrams queued
+        let (received, index, _) = future::select_all(receives).await;
+
+        match (&received, index.checked_sub(1)) {
+            (Ok(Some((bytes_received, _))), Some(extra)) => {
+                let extra = &self.extra_sockets[extra];
+                self.buffer[..*bytes_received].copy_from_slice(&extra.buffer[..*bytes_received]);
+                self.current_discovery_method = extra.discovery_method;
+            }
+            _ => self.current_discovery_method = self.discovery_method,
+        }
+
+        received
+    }
+
+    /// Gets the address of the host that sent the most recently received response.
+    pub fn last_source(&self) -> SocketAddr {
+        self.current_source
+    }
+
+    /// Gets the local address the discovery socket is bound to, if the socket can tell.
+    /// In dual-stack mode or with interfaces configured, this is the address of the socket
+    /// for the address `browse` was called with.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Determines whether the number of expected responders configured in the options has been reached.
+    fn all_expected_responded(&self) -> bool {
+        match self.options.expected_responders {
+            Some(count) => self.responders.len() >= count,
+            None => false,
+        }
+    }
+
+    /// Validates the size, header and encoding of the datagram that was just received.
+    fn validate_datagram(&self) -> Result<ResponseHeader, BrowserProtocolError> {
+        let datagram = &self.buffer[..self.buffer_len];
+        self.options.check_response_size(datagram)?;
+
+        // Validate that the buffer is valid utf-8
+        // TODO: Decode mbcs string
+        let (header, body) = parse_response_header(datagram, self.options.lenient_length)?;
+        std::str::from_utf8(body).map_err(BrowserProtocolError::InvalidUtf8)?;
+        Ok(header)
+    }
+
+    /// Skips the rest of the current datagram because it couldn't be parsed,
+    /// returning the error to report for it, with the datagram attached.
+    /// The next call to `next` continues with the next datagram.
+    fn skip_invalid_datagram(
+        &mut self,
+        error: BrowserProtocolError,
+    ) -> BrowserError<std::convert::Infallible, S::Error> {
+        self.options
+            .record(BrowseOperation::Broadcast, |s| s.record_parse_error());
+        self.current_offset = std::usize::MAX;
+        BrowserError::ProtocolError(error.with_response(&self.buffer[..self.buffer_len]))
+    }
+
+    /// Gets the point in time at which to stop waiting for further responses,
+    /// taking both the timeout and the quiet period into account.
+    fn current_deadline(&self) -> Option<Instant> {
+        let quiet_deadline = self
+            .options
+            .quiet_period
+            .map(|period| self.last_new_responder_at + period);
+
+        match (self.deadline, quiet_deadline) {
+            (Some(deadline), Some(quiet_deadline)) => Some(deadline.min(quiet_deadline)),
+            (deadline, quiet_deadline) => deadline.or(quiet_deadline),
+        }
+    }
+}
+
+impl<S: UdpSocket> Drop for AsyncInstanceIterator<S> {
+    fn drop(&mut self) {
+        self.in_flight.report_drop("AsyncInstanceIterator");
+        if let Some(membership) = self.membership {
+            membership.leave(&self.socket);
         }
     }
 }
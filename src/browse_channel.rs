@@ -0,0 +1,108 @@
+use super::browse::browse_with_options_inner;
+use super::error::*;
+use super::info::*;
+use super::options::{BrowserOptions, DEFAULT_PROBE_INTERVAL};
+use super::socket::DefaultSocketFactory;
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::future::{self, Either};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// The handle of a task spawned on the async runtime selected via the crate features.
+#[cfg(all(feature = "tokio", not(feature = "prefer-async-std")))]
+pub type JoinHandle<T> = tokio::task::JoinHandle<T>;
+
+/// The handle of a task spawned on the async runtime selected via the crate features.
+#[cfg(all(
+    feature = "async-std",
+    any(not(feature = "tokio"), feature = "prefer-async-std")
+))]
+pub type JoinHandle<T> = async_std::task::JoinHandle<T>;
+
+/// Discovers any SQL Server instances running on hosts reached by the given multicast address
+/// in a background task, which sends each instance or error on the returned channel.
+/// The channel is closed once browsing finishes, as configured in the options.
+///
+/// Invalid responses are sent as `BrowserError::ProtocolError` without ending the task.
+/// The task stops early once the receiver has been dropped, which is noticed within
+/// `DEFAULT_PROBE_INTERVAL`.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///   This can be the Ipv4 BROADCAST address, or a Ipv6 multicast address.
+/// * `options` - The options to use for this operation.
+pub fn browse_to_channel(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+) -> (
+    JoinHandle<()>,
+    UnboundedReceiver<Result<InstanceInfo, BrowserError>>,
+) {
+    let (sender, receiver) = mpsc::unbounded();
+    let options = options.clone();
+
+    let task = async move {
+        let mut factory = DefaultSocketFactory::new();
+        let mut iterator =
+            match browse_with_options_inner(multicast_addr, &options, &mut factory).await {
+                Ok(iterator) => iterator,
+                Err(e) => {
+                    let _ = sender.unbounded_send(Err(e));
+                    return;
+                }
+            };
+
+        loop {
+            // Browsing can go on without any response until the timeout, so whether the
+            // receiver was dropped is checked at the default probe interval while waiting
+            let next = iterator.next();
+            futures::pin_mut!(next);
+            let next = loop {
+                match future::select(next.as_mut(), Box::pin(sleep(DEFAULT_PROBE_INTERVAL))).await {
+                    Either::Left((next, _)) => break next,
+                    Either::Right(_) if sender.is_closed() => return,
+                    Either::Right(_) => continue,
+                }
+            };
+
+            let (result, done) = match next {
+                Ok(Some(instance)) => (Ok(instance), false),
+                Ok(None) => return,
+                Err(e @ BrowserError::ProtocolError(_)) => (Err(e.with_factory_error()), false),
+                Err(e) => (Err(e.with_factory_error()), true),
+            };
+
+            if sender.unbounded_send(result).is_err() || done {
+                return;
+            }
+        }
+    };
+
+    (spawn(task), receiver)
+}
+
+#[cfg(all(feature = "tokio", not(feature = "prefer-async-std")))]
+fn spawn(task: impl std::future::Future<Output = ()> + Send + 'static) -> JoinHandle<()> {
+    tokio::spawn(task)
+}
+
+#[cfg(all(
+    feature = "async-std",
+    any(not(feature = "tokio"), feature = "prefer-async-std")
+))]
+fn spawn(task: impl std::future::Future<Output = ()> + Send + 'static) -> JoinHandle<()> {
+    async_std::task::spawn(task)
+}
+
+#[cfg(all(feature = "tokio", not(feature = "prefer-async-std")))]
+async fn sleep(duration: Duration) {
+    tokio::time::delay_for(duration).await
+}
+
+#[cfg(all(
+    feature = "async-std",
+    any(not(feature = "tokio"), feature = "prefer-async-std")
+))]
+async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await
+}
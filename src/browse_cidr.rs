@@ -0,0 +1,72 @@
+use super::browse_host::browse_host_with_options_inner;
+use super::error::*;
+use super::info::*;
+use super::options::BrowserOptions;
+use super::socket::{UdpSocket, UdpSocketFactory};
+use futures::stream::{self, Stream, StreamExt};
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// The result of querying a single host while scanning a network,
+/// consisting of the address of the host and the instances found on it.
+pub type HostScanResult<SFError, SError> = (
+    IpAddr,
+    Result<Vec<InstanceInfo>, BrowserError<SFError, SError>>,
+);
+
+/// Discovers any SQL Server instances running on the hosts of the given network,
+/// querying up to `BrowserOptions::concurrency` hosts at the same time. Results are
+/// streamed per host as soon as they are available, in no particular order.
+/// For IPv4 networks the network and broadcast addresses are skipped.
+///
+/// # Arguments
+/// * `network` - The network of which to query each host.
+/// * `options` - The options to use for each host.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub fn browse_cidr(
+    network: IpNet,
+    options: &BrowserOptions,
+) -> impl Stream<
+    Item = HostScanResult<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    browse_cidr_inner(network, options, super::socket::DefaultSocketFactory::new())
+}
+
+/// Discovers any SQL Server instances running on the hosts of the given network,
+/// querying up to `BrowserOptions::concurrency` hosts at the same time. Results are
+/// streamed per host as soon as they are available, in no particular order.
+/// For IPv4 networks the network and broadcast addresses are skipped.
+///
+/// # Arguments
+/// * `network` - The network of which to query each host.
+/// * `options` - The options to use for each host.
+/// * `socket_factory` - The factory used to bind a socket, cloned for each host.
+pub fn browse_cidr_inner<SF: UdpSocketFactory + Clone>(
+    network: IpNet,
+    options: &BrowserOptions,
+    socket_factory: SF,
+) -> impl Stream<Item = HostScanResult<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let concurrency = options.concurrency;
+    let options = options.clone();
+
+    stream::iter(network.hosts())
+        .map(move |remote_addr| {
+            let options = options.clone();
+            let mut socket_factory = socket_factory.clone();
+
+            async move {
+                let result =
+                    browse_host_with_options_inner(remote_addr, &options, &mut socket_factory)
+                        .await
+                        .and_then(|iterator| {
+                            iterator.into_vec().map_err(BrowserError::ProtocolError)
+                        });
+
+                (remote_addr, result)
+            }
+        })
+        .buffer_unordered(concurrency)
+}
@@ -0,0 +1,74 @@
+use super::browse::browse_with_options_inner;
+use super::error::*;
+use super::info::*;
+use super::options::{BrowserOptions, DEFAULT_TIMEOUT};
+use super::socket::{UdpSocket, UdpSocketFactory};
+use std::net::IpAddr;
+
+/// Discovers SQL Server instances running on hosts reached by the given multicast address
+/// until the returned future is dropped, calling `f` for each instance, e.g. in a passive
+/// discovery service. Each instance is passed to `f` once, when it is first discovered.
+///
+/// The request is sent again whenever a round of browsing finishes as configured in the
+/// options, so the timeout sets the interval at which hosts are asked again. Without a
+/// timeout, `DEFAULT_TIMEOUT` is used. Note that a quiet period or expected number of
+/// responders ends a round early. Invalid responses are skipped, so this only returns
+/// if sending or receiving fails. To stop browsing, drop the future, e.g. by selecting
+/// it together with a shutdown signal.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///   This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///   group, see `BrowserOptions::join_multicast_group`.
+/// * `options` - The options to use for this operation.
+/// * `f` - The function to call for each discovered instance.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_for_each<F: FnMut(InstanceInfo)>(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+    f: F,
+) -> Result<
+    (),
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_for_each_inner(multicast_addr, options, f, &mut factory).await
+}
+
+/// Discovers SQL Server instances running on hosts reached by the given multicast address
+/// until the returned future is dropped, calling `f` for each instance, see `browse_for_each`.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///   This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///   group, see `BrowserOptions::join_multicast_group`.
+/// * `options` - The options to use for this operation.
+/// * `f` - The function to call for each discovered instance.
+pub async fn browse_for_each_inner<SF: UdpSocketFactory, F: FnMut(InstanceInfo)>(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+    mut f: F,
+    socket_factory: &mut SF,
+) -> Result<(), BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let options = match options.timeout {
+        Some(_) => options.clone(),
+        None => options.clone().timeout(Some(DEFAULT_TIMEOUT)),
+    };
+
+    let mut iterator = browse_with_options_inner(multicast_addr, &options, socket_factory).await?;
+
+    loop {
+        match iterator.next().await {
+            Ok(Some(instance)) => f(instance),
+            Ok(None) => iterator
+                .rebroadcast()
+                .await
+                .map_err(BrowserError::with_factory_error)?,
+            Err(BrowserError::ProtocolError(_)) => {}
+            Err(e) => return Err(e.with_factory_error()),
+        }
+    }
+}
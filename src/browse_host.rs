@@ -1,14 +1,18 @@
 use super::error::*;
 use super::info::*;
-use super::socket::{UdpSocket, UdpSocketFactory};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use super::options::BrowserOptions;
+use super::socket::{
+    bind_socket, connect_socket, exchange, try_next_port, UdpSocket, UdpSocketFactory,
+};
+use super::stats::BrowseOperation;
+use futures::lock::Mutex;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 
 /// The CLNT_UCAST_EX packet is a unicast request that is generated by clients that are trying to identify
 /// the list of database instances and their network protocol connection information installed on a single machine.
-const CLNT_UCAST_EX: u8 = 0x03;
-
-/// The server responds to all client requests with an SVR_RESP.
-const SVR_RESP: u8 = 0x05;
+pub(crate) const CLNT_UCAST_EX: u8 = 0x03;
 
 /// Discovers any SQL Server instances running on the given host
 ///
@@ -29,6 +33,27 @@ pub async fn browse_host(
     browse_host_inner(remote_addr, &mut factory).await
 }
 
+/// Discovers any SQL Server instances running on the given host, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///                   about the instances running on it.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_with_options(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+) -> Result<
+    InstanceIterator,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_with_options_inner(remote_addr, options, &mut factory).await
+}
+
 /// Discovers any SQL Server instances running on the given host
 ///
 /// # Arguments
@@ -38,95 +63,129 @@ pub async fn browse_host_inner<SF: UdpSocketFactory>(
     remote_addr: IpAddr,
     socket_factory: &mut SF,
 ) -> Result<InstanceIterator, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
-    let local_addr = if remote_addr.is_ipv4() {
-        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
-    } else {
-        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
-    };
-
-    let bind_to = SocketAddr::new(local_addr, 0);
-    let mut socket = socket_factory
-        .bind(&bind_to)
-        .await
-        .map_err(BrowserError::BindFailed)?;
-
-    let remote = SocketAddr::new(remote_addr, 1434);
-    socket
-        .connect(&remote)
-        .await
-        .map_err(|e| BrowserError::ConnectFailed(remote, e))?;
-
-    let buffer = [CLNT_UCAST_EX];
-    socket
-        .send_to(&buffer, &remote)
-        .await
-        .map_err(|e| BrowserError::SendFailed(remote, e))?;
-
-    let mut buffer = Vec::with_capacity(65535 + 3);
-
-    buffer.resize_with(buffer.capacity(), Default::default);
-
-    let bytes_received = socket
-        .recv(&mut buffer)
-        .await
-        .map_err(BrowserError::ReceiveFailed)?;
-
-    if bytes_received < 1 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
-    }
+    browse_host_with_options_inner(remote_addr, &BrowserOptions::default(), socket_factory).await
+}
 
-    if buffer[0] != SVR_RESP {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
-                found: BrowserProtocolToken::MessageIdentifier(buffer[0]),
-            },
-        ));
-    }
+/// Discovers any SQL Server instances running on the given host, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///                   about the instances running on it.
+/// * `options` - The options to use for this operation.
+pub async fn browse_host_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<InstanceIterator, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    browse_host_shared(remote_addr, options, &Mutex::new(socket_factory)).await
+}
 
-    if bytes_received < 3 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageLength,
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
-    }
+/// Discovers any SQL Server instances running on the given host, trying each browser port in turn.
+/// The socket factory is only locked while binding, so that several hosts can be browsed
+/// concurrently with the same factory.
+pub(crate) async fn browse_host_shared<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &Mutex<&mut SF>,
+) -> Result<InstanceIterator, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let mut ports = options.browser_ports.iter().peekable();
+    loop {
+        // There is at least one port, and the loop returns after the last one
+        let remote = SocketAddr::new(remote_addr, *ports.next().unwrap());
+        let socket = {
+            let mut socket_factory = socket_factory.lock().await;
+            bind_socket(&mut **socket_factory, &remote.ip(), options, false)
+                .await
+                .map_err(BrowserError::BindFailed)?
+        };
 
-    let resp_data_len = u16::from_le_bytes([buffer[1], buffer[2]]);
-    if resp_data_len as usize != bytes_received - 3 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::LengthMismatch {
-                datagram: bytes_received,
-                header: (resp_data_len + 3) as usize,
-            },
-        ));
+        match browse_host_at(socket, remote, options).await {
+            Err(e) if try_next_port(&e) && ports.peek().is_some() => continue,
+            result => return result,
+        }
     }
+}
+
+/// Discovers the instances running on a host by sending the request to the given browser port.
+async fn browse_host_at<SFError: std::error::Error, S: UdpSocket>(
+    mut socket: S,
+    remote: SocketAddr,
+    options: &BrowserOptions,
+) -> Result<InstanceIterator, BrowserError<SFError, S::Error>> {
+    connect_socket(&mut socket, &remote, options).await?;
+
+    let mut buffer = vec![0u8; 65535 + 3];
+
+    let (bytes_received, rtt) = exchange(
+        &mut socket,
+        &[CLNT_UCAST_EX],
+        &mut buffer,
+        &remote,
+        options,
+        BrowseOperation::Host,
+    )
+    .await?;
 
     buffer.truncate(bytes_received);
 
-    // Validate that the buffer is valid utf-8
-    // TODO: Decode mbcs string
-    std::str::from_utf8(&buffer[3..])
-        .map_err(|e| BrowserError::ProtocolError(BrowserProtocolError::InvalidUtf8(e)))?;
+    let iterator = parse_host_response(remote, buffer, rtt, options).map_err(|e| {
+        options.record(BrowseOperation::Host, |s| s.record_parse_error());
+        BrowserError::ProtocolError(e)
+    })?;
+
+    options.record(BrowseOperation::Host, |s| s.record_rtt(rtt));
+    Ok(iterator)
+}
+
+/// Validates the SVR_RESP datagram sent in response to a CLNT_UCAST_EX request,
+/// returning an iterator over the instances it describes.
+/// Errors have the datagram attached.
+pub(crate) fn parse_host_response(
+    remote: SocketAddr,
+    buffer: Vec<u8>,
+    rtt: Duration,
+    options: &BrowserOptions,
+) -> Result<InstanceIterator, BrowserProtocolError> {
+    let header = validate_host_response(&buffer, options).map_err(|e| e.with_response(&buffer))?;
 
     Ok(InstanceIterator {
-        remote_addr,
+        remote,
         buffer,
         offset: 3,
+        header,
+        rtt,
+        strict: options.strict_parsing,
+        reject_duplicates: options.reject_duplicate_instances,
+        seen: HashSet::new(),
     })
 }
 
-/// Iterates over the instances returned by `browse_host`
+/// Validates the size, header and encoding of a host response, returning its header.
+fn validate_host_response(
+    buffer: &[u8],
+    options: &BrowserOptions,
+) -> Result<ResponseHeader, BrowserProtocolError> {
+    options.check_response_size(buffer)?;
+
+    // Validate that the buffer is valid utf-8
+    // TODO: Decode mbcs string
+    let (header, body) = parse_response_header(buffer, options.lenient_length)?;
+    std::str::from_utf8(body).map_err(BrowserProtocolError::InvalidUtf8)?;
+    Ok(header)
+}
+
+/// Iterates over the instances returned by `browse_host`.
+/// A single response can describe any number of instances, each of
+/// which is parsed on demand by `next`.
 pub struct InstanceIterator {
-    remote_addr: IpAddr,
+    remote: SocketAddr,
     buffer: Vec<u8>,
     offset: usize,
+    header: ResponseHeader,
+    rtt: Duration,
+    strict: bool,
+    reject_duplicates: bool,
+    seen: HashSet<String>,
 }
 
 impl InstanceIterator {
@@ -138,16 +197,128 @@ impl InstanceIterator {
         Option<InstanceInfo>,
         BrowserError<std::convert::Infallible, std::convert::Infallible>,
     > {
-        if self.offset == self.buffer.len() {
-            return Ok(None);
+        self.parse_next().map_err(BrowserError::ProtocolError)
+    }
+
+    pub(crate) fn parse_next(&mut self) -> Result<Option<InstanceInfo>, BrowserProtocolError> {
+        self.parse_instance()
+            .map_err(|e| e.with_response(&self.buffer))
+    }
+
+    /// Parses the next instance, without attaching the datagram to errors.
+    fn parse_instance(&mut self) -> Result<Option<InstanceInfo>, BrowserProtocolError> {
+        loop {
+            if self.offset >= self.buffer.len() {
+                return Ok(None);
+            }
+
+            // UNSAFE: Buffer is already validated to be valid utf-8 when the iterator was created
+            let as_str = unsafe { std::str::from_utf8_unchecked(&self.buffer[self.offset..]) };
+            let (mut instance, consumed) =
+                parse_instance_info(self.remote.ip(), as_str, self.strict)?;
+            self.offset += consumed;
+
+            // Instance names are case insensitive
+            if !self
+                .seen
+                .insert(instance.instance_name.to_ascii_uppercase())
+            {
+                if self.reject_duplicates {
+                    return Err(BrowserProtocolError::DuplicateInstance {
+                        name: instance.instance_name,
+                    });
+                }
+
+                continue;
+            }
+
+            instance.rtt = Some(self.rtt);
+            instance.header = Some(self.header);
+            instance.source = Some(self.remote);
+            return Ok(Some(instance));
         }
+    }
 
-        // UNSAFE: Buffer is already validated to be valid utf-8 when the iterator was created
-        let as_str = unsafe { std::str::from_utf8_unchecked(&self.buffer[self.offset..]) };
-        let (instance, consumed) = parse_instance_info(self.remote_addr, as_str)
-            .map_err(|e| BrowserError::ProtocolError(e))?;
+    /// Gets the received datagram the instances are parsed from.
+    pub(crate) fn response(&self) -> &[u8] {
+        &self.buffer
+    }
 
-        self.offset += consumed;
-        Ok(Some(instance))
+    /// Parses all remaining instances into a `Vec`, failing on the first invalid one.
+    pub fn into_vec(self) -> Result<Vec<InstanceInfo>, BrowserProtocolError> {
+        self.into_iter().collect()
+    }
+
+    /// Parses all remaining instances on a best-effort basis, skipping the ones that can't be
+    /// parsed instead of failing. Returns the parsed instances along with a
+    /// `BrowserProtocolError::InvalidInstance` for each skipped one, which tells where in the
+    /// datagram the instance was described and how. After an invalid instance, parsing resumes
+    /// at the start of the next one, after the `;;` terminating the invalid one.
+    pub fn into_partial(mut self) -> (Vec<InstanceInfo>, Vec<BrowserProtocolError>) {
+        let mut instances = Vec::new();
+        let mut errors = Vec::new();
+
+        loop {
+            let offset = self.offset;
+            match self.parse_instance() {
+                Ok(Some(instance)) => instances.push(instance),
+                Ok(None) => break,
+                Err(error) => {
+                    // Duplicates are rejected after being consumed, anything else isn't
+                    if self.offset == offset {
+                        self.offset = next_record(&self.buffer, offset);
+                    }
+
+                    errors.push(BrowserProtocolError::InvalidInstance {
+                        error: Box::new(error),
+                        offset,
+                        record: String::from_utf8_lossy(&self.buffer[offset..self.offset])
+                            .into_owned(),
+                    });
+                }
+            }
+        }
+
+        (instances, errors)
+    }
+}
+
+/// Finds the start of the description of the instance following the one at `offset`,
+/// which starts after the `;;` terminating the record, or the end of the datagram if there is none.
+fn next_record(buffer: &[u8], offset: usize) -> usize {
+    const NEXT: &[u8] = b";;";
+
+    buffer[offset..]
+        .windows(NEXT.len())
+        .position(|window| window == NEXT)
+        .map_or(buffer.len(), |position| offset + position + 2)
+}
+
+impl IntoIterator for InstanceIterator {
+    type Item = Result<InstanceInfo, BrowserProtocolError>;
+    type IntoIter = InstanceIntoIter;
+
+    fn into_iter(self) -> InstanceIntoIter {
+        InstanceIntoIter { inner: self }
+    }
+}
+
+/// A standard `Iterator` over the instances of an `InstanceIterator`.
+/// Stops after yielding the first error.
+pub struct InstanceIntoIter {
+    inner: InstanceIterator,
+}
+
+impl Iterator for InstanceIntoIter {
+    type Item = Result<InstanceInfo, BrowserProtocolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.parse_next() {
+            Ok(x) => x.map(Ok),
+            Err(e) => {
+                self.inner.offset = self.inner.buffer.len();
+                Some(Err(e))
+            }
+        }
     }
 }
@@ -0,0 +1,115 @@
+use super::browse_host::{browse_host_shared, InstanceIterator};
+use super::error::*;
+use super::options::{AddressPreference, BrowserOptions};
+use super::socket::{try_next_port, UdpSocket, UdpSocketFactory};
+use futures::future;
+use futures::lock::Mutex;
+
+/// Discovers any SQL Server instances running on the host with the given name
+///
+/// # Arguments
+/// * `host` - The name of the remote host of which to retrieve information
+///   about the instances running on it.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_by_name(
+    host: &str,
+) -> Result<
+    InstanceIterator,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_by_name_inner(host, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on the host with the given name, using the given options.
+/// The addresses the name resolves to are selected by `BrowserOptions::address_preference`
+/// and probed concurrently, or in turn for the `Prefer*` preferences, until one of them responds.
+///
+/// # Arguments
+/// * `host` - The name of the remote host of which to retrieve information
+///   about the instances running on it.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_by_name_with_options(
+    host: &str,
+    options: &BrowserOptions,
+) -> Result<
+    InstanceIterator,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_by_name_with_options_inner(host, options, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on the host with the given name
+///
+/// # Arguments
+/// * `host` - The name of the remote host of which to retrieve information
+///   about the instances running on it.
+pub async fn browse_host_by_name_inner<SF: UdpSocketFactory>(
+    host: &str,
+    socket_factory: &mut SF,
+) -> Result<InstanceIterator, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    browse_host_by_name_with_options_inner(host, &BrowserOptions::default(), socket_factory).await
+}
+
+/// Discovers any SQL Server instances running on the host with the given name, using the given options.
+/// The name is resolved by `UdpSocketFactory::resolve`, and the addresses it resolves to are
+/// selected by `BrowserOptions::address_preference`. They are probed concurrently and the first
+/// response is returned, or in turn for the `Prefer*` preferences until one of them responds.
+///
+/// # Arguments
+/// * `host` - The name of the remote host of which to retrieve information
+///   about the instances running on it.
+/// * `options` - The options to use for this operation.
+pub async fn browse_host_by_name_with_options_inner<SF: UdpSocketFactory>(
+    host: &str,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<InstanceIterator, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let resolved = socket_factory
+        .resolve(host)
+        .await
+        .map_err(|e| BrowserError::ResolveFailed(host.to_owned(), e))?;
+
+    let addrs = options.select_addresses(&resolved);
+    if addrs.is_empty() {
+        return Err(BrowserError::ResolveFailed(
+            host.to_owned(),
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "the host name resolved to no acceptable address",
+            ),
+        ));
+    }
+
+    let socket_factory = Mutex::new(socket_factory);
+    match options.address_preference {
+        Some(AddressPreference::PreferV4) | Some(AddressPreference::PreferV6) => {
+            let mut addrs = addrs.into_iter().peekable();
+            loop {
+                // There is at least one address, and the loop returns after the last one
+                let remote_addr = addrs.next().unwrap();
+                match browse_host_shared(remote_addr, options, &socket_factory).await {
+                    Err(e) if try_next_port(&e) && addrs.peek().is_some() => continue,
+                    result => return result,
+                }
+            }
+        }
+        _ => {
+            // All addresses are probed at once, the first to respond wins
+            let probes = addrs.into_iter().map(|remote_addr| {
+                Box::pin(browse_host_shared(remote_addr, options, &socket_factory))
+            });
+            future::select_ok(probes)
+                .await
+                .map(|(iterator, _)| iterator)
+        }
+    }
+}
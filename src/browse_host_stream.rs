@@ -0,0 +1,243 @@
+use super::browse_host::{parse_host_response, InstanceIterator, CLNT_UCAST_EX};
+use super::error::*;
+use super::in_flight::InFlight;
+use super::info::*;
+use super::observer::DatagramDirection;
+use super::options::BrowserOptions;
+use super::socket::{
+    bind_socket, connect_socket, receive_error, recv_peer_deadline, UdpSocket, UdpSocketFactory,
+};
+use super::stats::BrowseOperation;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+
+/// Discovers any SQL Server instances running on the given host, returning them as the
+/// responses arrive. Unlike `browse_host`, which only parses the first response, this keeps
+/// receiving until the timeout expires, for hosts that spread their instances over several datagrams.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_stream(
+    remote_addr: IpAddr,
+) -> Result<
+    HostInstanceStream<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_stream_inner(remote_addr, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on the given host, returning them as the
+/// responses arrive, using the given options. Responses are received until the timeout
+/// in the options expires, or indefinitely if it's disabled.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_stream_with_options(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+) -> Result<
+    HostInstanceStream<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_stream_with_options_inner(remote_addr, options, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on the given host, returning them as the
+/// responses arrive. Unlike `browse_host`, which only parses the first response, this keeps
+/// receiving until the timeout expires, for hosts that spread their instances over several datagrams.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+pub async fn browse_host_stream_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    socket_factory: &mut SF,
+) -> Result<HostInstanceStream<SF::Socket>, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>>
+{
+    browse_host_stream_with_options_inner(remote_addr, &BrowserOptions::default(), socket_factory)
+        .await
+}
+
+/// Discovers any SQL Server instances running on the given host, returning them as the
+/// responses arrive, using the given options. Responses are received until the timeout
+/// in the options expires, or indefinitely if it's disabled.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+/// * `options` - The options to use for this operation.
+pub async fn browse_host_stream_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<HostInstanceStream<SF::Socket>, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>>
+{
+    let mut socket = bind_socket(socket_factory, &remote_addr, options, false)
+        .await
+        .map_err(BrowserError::BindFailed)?;
+
+    let remote = SocketAddr::new(remote_addr, 1434);
+    connect_socket(&mut socket, &remote, options).await?;
+
+    let buffer = [CLNT_UCAST_EX];
+    let sent_at = options.now();
+    socket
+        .send_to(&buffer, &remote)
+        .await
+        .map_err(|e| BrowserError::SendFailed(remote, e))?;
+    options.notify(DatagramDirection::Sent, &remote, &buffer);
+    options.record(BrowseOperation::Host, |s| s.record_request_sent());
+
+    Ok(HostInstanceStream {
+        socket,
+        in_flight: InFlight::default(),
+        remote,
+        options: options.clone(),
+        sent_at,
+        deadline: options.timeout.map(|timeout| sent_at + timeout),
+        received_any: false,
+        current: None,
+        seen: HashSet::new(),
+    })
+}
+
+/// Iterates over the instances returned by `browse_host_stream`,
+/// receiving further responses of the host as needed.
+pub struct HostInstanceStream<S: UdpSocket> {
+    socket: S,
+    in_flight: InFlight,
+    remote: SocketAddr,
+    options: BrowserOptions,
+    sent_at: Instant,
+    deadline: Option<Instant>,
+    received_any: bool,
+    current: Option<InstanceIterator>,
+    seen: HashSet<String>,
+}
+
+impl<S: UdpSocket> HostInstanceStream<S> {
+    /// Gets the next received instance information. You can call this method multiple
+    /// times to receive information about multiple instances until it returns Ok(None),
+    /// which happens once the timeout expired after at least one response was received.
+    /// If the host didn't respond at all, `BrowserError::Timeout` is returned instead.
+    ///
+    /// A response that can't be parsed is reported as a `BrowserError::ProtocolError`,
+    /// after which the stream remains usable and continues with the next response.
+    ///
+    /// This method is cancellation safe as long as the socket keeps the contract of `UdpSocket`,
+    /// so the returned future can be dropped, e.g. in a `select!`, without losing a response.
+    pub async fn next(
+        &mut self,
+    ) -> Result<Option<InstanceInfo>, BrowserError<std::convert::Infallible, S::Error>> {
+        loop {
+            if let Some(current) = &mut self.current {
+                match current.parse_next() {
+                    Ok(Some(instance)) => {
+                        // Instance names are case insensitive
+                        if self
+                            .seen
+                            .insert(instance.instance_name.to_ascii_uppercase())
+                        {
+                            return Ok(Some(instance));
+                        }
+
+                        if self.options.reject_duplicate_instances {
+                            let error = BrowserProtocolError::DuplicateInstance {
+                                name: instance.instance_name,
+                            }
+                            .with_response(current.response());
+                            self.current = None;
+                            return Err(BrowserError::ProtocolError(error));
+                        }
+
+                        continue;
+                    }
+                    Ok(None) => self.current = None,
+                    Err(e) => {
+                        self.current = None;
+                        return Err(self.parse_error(e));
+                    }
+                }
+            }
+
+            let mut buffer = vec![0u8; 65535 + 3];
+            self.in_flight.start(&self.remote);
+            let received = recv_peer_deadline(
+                &mut self.socket,
+                &mut buffer,
+                &self.remote,
+                self.deadline,
+                &self.options,
+            )
+            .await;
+            self.in_flight.finish();
+            let received = received
+                .map_err(|e| receive_error::<std::convert::Infallible, S>(&self.remote, e))?;
+
+            let bytes_received = match received {
+                Some(bytes_received) => bytes_received,
+                None if self.received_any => return Ok(None),
+                None => {
+                    self.options
+                        .record(BrowseOperation::Host, |s| s.record_timeout());
+                    return Err(BrowserError::Timeout(self.remote));
+                }
+            };
+
+            let rtt = self.options.now() - self.sent_at;
+            self.received_any = true;
+            self.options.notify(
+                DatagramDirection::Received,
+                &self.remote,
+                &buffer[..bytes_received],
+            );
+            self.options
+                .record(BrowseOperation::Host, |s| s.record_response_received());
+
+            buffer.truncate(bytes_received);
+            match parse_host_response(self.remote, buffer, rtt, &self.options) {
+                Ok(iterator) => {
+                    self.options
+                        .record(BrowseOperation::Host, |s| s.record_rtt(rtt));
+                    self.current = Some(iterator);
+                }
+                Err(e) => return Err(self.parse_error(e)),
+            }
+        }
+    }
+
+    /// Gets the local address the socket is bound to, if the socket can tell.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Records a response that couldn't be parsed.
+    fn parse_error(
+        &self,
+        error: BrowserProtocolError,
+    ) -> BrowserError<std::convert::Infallible, S::Error> {
+        self.options
+            .record(BrowseOperation::Host, |s| s.record_parse_error());
+        BrowserError::ProtocolError(error)
+    }
+}
+
+impl<S: UdpSocket> Drop for HostInstanceStream<S> {
+    fn drop(&mut self) {
+        self.in_flight.report_drop("HostInstanceStream");
+    }
+}
@@ -0,0 +1,110 @@
+use super::browse_host::browse_host_shared;
+use super::browse_instance_dac::browse_instance_dac_shared;
+use super::error::*;
+use super::info::*;
+use super::options::BrowserOptions;
+use super::socket::{try_next_port, UdpSocket, UdpSocketFactory};
+use futures::future;
+use futures::lock::Mutex;
+use std::net::IpAddr;
+
+/// Discovers any SQL Server instances running on the given host, along with the
+/// DAC endpoint of each instance. Instances that didn't answer the DAC request
+/// are returned with `dac_info` set to `None`.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_with_dac(
+    remote_addr: IpAddr,
+) -> Result<
+    Vec<InstanceInfo>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_with_dac_inner(remote_addr, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on the given host, along with the
+/// DAC endpoint of each instance, using the given options. Instances that didn't
+/// answer the DAC request are returned with `dac_info` set to `None`.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+/// * `options` - The options to use for this operation and each DAC request.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_host_with_dac_with_options(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+) -> Result<
+    Vec<InstanceInfo>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_host_with_dac_with_options_inner(remote_addr, options, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on the given host, along with the
+/// DAC endpoint of each instance. Instances that didn't answer the DAC request
+/// are returned with `dac_info` set to `None`.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+pub async fn browse_host_with_dac_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    socket_factory: &mut SF,
+) -> Result<Vec<InstanceInfo>, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    browse_host_with_dac_with_options_inner(remote_addr, &BrowserOptions::default(), socket_factory)
+        .await
+}
+
+/// Discovers any SQL Server instances running on the given host, along with the
+/// DAC endpoint of each instance, using the given options. The DAC requests are sent
+/// concurrently. Instances that didn't answer the DAC request, whether it timed out, was
+/// refused or was answered with an invalid response, are returned with `dac_info` set to `None`.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host of which to retrieve information
+///   about the instances running on it.
+/// * `options` - The options to use for this operation and each DAC request.
+pub async fn browse_host_with_dac_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<Vec<InstanceInfo>, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let socket_factory = Mutex::new(socket_factory);
+    let mut instances = browse_host_shared(remote_addr, options, &socket_factory)
+        .await?
+        .into_vec()?;
+
+    let requests = instances.iter().map(|instance| {
+        browse_instance_dac_shared(
+            remote_addr,
+            &instance.instance_name,
+            options,
+            &socket_factory,
+        )
+    });
+    let responses = future::join_all(requests).await;
+
+    // Only the instances that didn't answer are kept without DAC information,
+    // any other failure, e.g. of the socket, fails the whole operation
+    for (instance, response) in instances.iter_mut().zip(responses) {
+        instance.dac_info = match response {
+            Ok(info) => Some(info),
+            Err(e) if try_next_port(&e) => None,
+            Err(e) => return Err(e),
+        };
+    }
+
+    Ok(instances)
+}
@@ -1,14 +1,15 @@
 use super::error::*;
 use super::info::*;
-use super::socket::{UdpSocket, UdpSocketFactory};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use super::options::BrowserOptions;
+use super::socket::{
+    bind_socket, connect_socket, exchange, try_next_port, UdpSocket, UdpSocketFactory,
+};
+use super::stats::BrowseOperation;
+use std::net::{IpAddr, SocketAddr};
 
 /// The CLNT_UCAST_INST packet is a request for information related to a specific instance.
 const CLNT_UCAST_INST: u8 = 0x04;
 
-/// The server responds to all client requests with an SVR_RESP.
-const SVR_RESP: u8 = 0x05;
-
 /// Gets information about the given instance.
 ///
 /// # Arguments
@@ -29,6 +30,28 @@ pub async fn browse_instance(
     browse_instance_inner(remote_addr, instance_name, &mut factory).await
 }
 
+/// Gets information about the given instance, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_instance_with_options(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+) -> Result<
+    InstanceInfo,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_instance_with_options_inner(remote_addr, instance_name, options, &mut factory).await
+}
+
 /// Gets information about the given instance.
 ///
 /// # Arguments
@@ -39,91 +62,145 @@ pub async fn browse_instance_inner<SF: UdpSocketFactory>(
     instance_name: &str,
     socket_factory: &mut SF,
 ) -> Result<InstanceInfo, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
-    if instance_name.len() > super::MAX_INSTANCE_NAME_LEN {
-        return Err(BrowserError::InstanceNameTooLong);
-    }
+    browse_instance_with_options_inner(
+        remote_addr,
+        instance_name,
+        &BrowserOptions::default(),
+        socket_factory,
+    )
+    .await
+}
 
-    let local_addr = if remote_addr.is_ipv4() {
-        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
-    } else {
-        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
-    };
+/// Gets information about the given instance, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+pub async fn browse_instance_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<InstanceInfo, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    check_instance_name(instance_name)?;
+
+    let mut ports = options.browser_ports.iter().peekable();
+    loop {
+        // There is at least one port, and the loop returns after the last one
+        let remote = SocketAddr::new(remote_addr, *ports.next().unwrap());
+        match browse_instance_at(remote, instance_name, options, socket_factory).await {
+            Err(e) if try_next_port(&e) && ports.peek().is_some() => continue,
+            result => return result,
+        }
+    }
+}
 
-    let bind_to = SocketAddr::new(local_addr, 0);
-    let mut socket = socket_factory
-        .bind(&bind_to)
+/// Gets information about the given instance by sending the request to the given browser port.
+async fn browse_instance_at<SF: UdpSocketFactory>(
+    remote: SocketAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<InstanceInfo, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let mut socket = bind_socket(socket_factory, &remote.ip(), options, false)
         .await
         .map_err(BrowserError::BindFailed)?;
 
-    let remote = SocketAddr::new(remote_addr, 1434);
-    socket
-        .connect(&remote)
-        .await
-        .map_err(|e| BrowserError::ConnectFailed(remote, e))?;
-
-    let mut buffer = [0u8; 1 + super::MAX_INSTANCE_NAME_LEN + 1];
-    buffer[0] = CLNT_UCAST_INST;
-    buffer[1..(1 + instance_name.len())].copy_from_slice(instance_name.as_bytes()); // TODO: Encode as mbcs string
-    let buffer_len = 2 + instance_name.len();
-    socket
-        .send_to(&buffer[0..buffer_len], &remote)
-        .await
-        .map_err(|e| BrowserError::SendFailed(remote, e))?;
+    connect_socket(&mut socket, &remote, options).await?;
+
+    let request = instance_request(instance_name);
+    let mut buffer = vec![0u8; options.recv_buffer_size];
+
+    let (bytes_received, rtt) = exchange(
+        &mut socket,
+        &request,
+        &mut buffer,
+        &remote,
+        options,
+        BrowseOperation::Instance,
+    )
+    .await?;
+
+    let mut instance = parse_instance_response(remote.ip(), &buffer[..bytes_received], options)
+        .map_err(|e| {
+            options.record(BrowseOperation::Instance, |s| s.record_parse_error());
+            BrowserError::ProtocolError(e)
+        })?;
+
+    options.record(BrowseOperation::Instance, |s| s.record_rtt(rtt));
+    instance.rtt = Some(rtt);
+    instance.source = Some(remote);
+    Ok(instance)
+}
 
-    let mut buffer = [0u8; 3 + 1024];
+/// Builds a CLNT_UCAST_INST request for the given instance.
+pub(crate) fn instance_request(instance_name: &str) -> Vec<u8> {
+    // TODO: Encode as mbcs string
+    let name = instance_name.as_bytes();
+    let mut buffer = Vec::with_capacity(1 + name.len() + 1);
+    buffer.push(CLNT_UCAST_INST);
+    buffer.extend_from_slice(name);
+    buffer.push(0);
+    buffer
+}
 
-    let bytes_received = socket
-        .recv(&mut buffer)
-        .await
-        .map_err(BrowserError::ReceiveFailed)?;
-
-    if bytes_received < 1 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
-    }
+/// Parses the SVR_RESP datagram sent in response to a CLNT_UCAST_INST request.
+/// Errors have the datagram attached.
+pub(crate) fn parse_instance_response(
+    remote_addr: IpAddr,
+    buffer: &[u8],
+    options: &BrowserOptions,
+) -> Result<InstanceInfo, BrowserProtocolError> {
+    parse_instance_datagram(remote_addr, buffer, options).map_err(|e| e.with_response(buffer))
+}
 
-    if buffer[0] != SVR_RESP {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
-                found: BrowserProtocolToken::MessageIdentifier(buffer[0]),
-            },
-        ));
+fn parse_instance_datagram(
+    remote_addr: IpAddr,
+    buffer: &[u8],
+    options: &BrowserOptions,
+) -> Result<InstanceInfo, BrowserProtocolError> {
+    options.check_response_size(buffer)?;
+    let (header, body) = parse_response_header(buffer, options.lenient_length)?;
+    if body.is_empty() {
+        return Err(BrowserProtocolError::EmptyResponse);
     }
 
-    if bytes_received < 3 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageLength,
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
+    // TODO: Decode mbcs string
+    let as_str = std::str::from_utf8(body).map_err(BrowserProtocolError::InvalidUtf8)?;
+    let (mut instance, consumed) =
+        parse_instance_info(remote_addr, as_str, options.strict_parsing)?;
+
+    if consumed != as_str.len() {
+        return Err(BrowserProtocolError::ExtraneousData(Vec::from(
+            &body[consumed..],
+        )));
     }
 
-    let resp_data_len = u16::from_le_bytes([buffer[1], buffer[2]]);
-    if resp_data_len as usize != bytes_received - 3 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::LengthMismatch {
-                datagram: bytes_received,
-                header: (resp_data_len + 3) as usize,
-            },
-        ));
+    instance.header = Some(header);
+    Ok(instance)
+}
+
+/// Checks that the given instance name can be sent in a request.
+pub(crate) fn check_instance_name<SFError: std::error::Error, SError: std::error::Error>(
+    instance_name: &str,
+) -> Result<(), BrowserError<SFError, SError>> {
+    if instance_name.is_empty() {
+        return Err(BrowserError::InstanceNameEmpty);
     }
 
-    // TODO: Decode mbcs string
-    let as_str = std::str::from_utf8(&buffer[3..bytes_received]).unwrap();
-    let (instance, consumed) =
-        parse_instance_info(remote_addr, &as_str).map_err(|e| BrowserError::ProtocolError(e))?;
+    if instance_name.len() > super::MAX_INSTANCE_NAME_LEN {
+        return Err(BrowserError::InstanceNameTooLong);
+    }
 
-    if consumed != as_str.len() {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::ExtraneousData(Vec::from(&buffer[(3 + consumed)..])),
-        ));
+    // Backslashes separate the server and instance name, semicolons separate the fields
+    // of the response and a NUL byte would end the request early.
+    if let Some(c) = instance_name
+        .chars()
+        .find(|&c| c == '\\' || c == ';' || c.is_control())
+    {
+        return Err(BrowserError::InvalidInstanceName(c));
     }
 
-    Ok(instance)
+    Ok(())
 }
@@ -1,12 +1,21 @@
+use super::browse_instance::check_instance_name;
 use super::error::*;
 use super::info::*;
-use super::socket::{UdpSocket, UdpSocketFactory};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use super::options::BrowserOptions;
+use super::socket::{bind_socket, connect_socket, exchange, UdpSocket, UdpSocketFactory};
+use super::stats::BrowseOperation;
+use futures::lock::Mutex;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 
 /// The CLNT_UCAST_DAC packet request is used to determine the TCP [RFC793] port on which the
 /// Microsoft SQL Server dedicated administrator connection (DAC) endpoint is listening.
 const CLNT_UCAST_DAC: u8 = 0x0F;
 
+/// The version of the CLNT_UCAST_DAC request and its response,
+/// unless overridden via `BrowserOptions::dac_version`.
+pub const DEFAULT_DAC_VERSION: u8 = 0x01;
+
 /// The server responds to all client requests with an SVR_RESP.
 const SVR_RESP: u8 = 0x05;
 
@@ -30,6 +39,28 @@ pub async fn browse_instance_dac(
     browse_instance_dac_inner(remote_addr, instance_name, &mut factory).await
 }
 
+/// Gets DAC information about the given instance, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_instance_dac_with_options(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+) -> Result<
+    DacInfo,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_instance_dac_with_options_inner(remote_addr, instance_name, options, &mut factory).await
+}
+
 /// Gets DAC information about the given instance
 ///
 /// # Arguments
@@ -40,111 +71,195 @@ pub async fn browse_instance_dac_inner<SF: UdpSocketFactory>(
     instance_name: &str,
     socket_factory: &mut SF,
 ) -> Result<DacInfo, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
-    const VERSION: u8 = 0x01;
+    browse_instance_dac_with_options_inner(
+        remote_addr,
+        instance_name,
+        &BrowserOptions::default(),
+        socket_factory,
+    )
+    .await
+}
 
-    if instance_name.len() > super::MAX_INSTANCE_NAME_LEN {
-        return Err(BrowserError::InstanceNameTooLong);
-    }
+/// Gets DAC information about the given instance, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+pub async fn browse_instance_dac_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<DacInfo, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    browse_instance_dac_shared(
+        remote_addr,
+        instance_name,
+        options,
+        &Mutex::new(socket_factory),
+    )
+    .await
+}
 
-    let local_addr = if remote_addr.is_ipv4() {
-        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
-    } else {
-        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
-    };
+/// Gets DAC information about the given instance, locking the socket factory only while
+/// binding, so that several instances can be queried concurrently with the same factory.
+pub(crate) async fn browse_instance_dac_shared<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+    socket_factory: &Mutex<&mut SF>,
+) -> Result<DacInfo, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let mut buffer = [0u8; 6];
+    let (bytes_received, rtt) = dac_exchange(
+        remote_addr,
+        instance_name,
+        options,
+        socket_factory,
+        &mut buffer,
+    )
+    .await?;
 
-    let bind_to = SocketAddr::new(local_addr, 0);
-    let mut socket = socket_factory
-        .bind(&bind_to)
-        .await
-        .map_err(BrowserError::BindFailed)?;
+    let info = parse_dac_response_version(&buffer[..bytes_received], options.dac_version).map_err(
+        |e| {
+            options.record(BrowseOperation::Dac, |s| s.record_parse_error());
+            BrowserError::ProtocolError(e.with_response(&buffer[..bytes_received]))
+        },
+    )?;
+
+    options.record(BrowseOperation::Dac, |s| s.record_rtt(rtt));
+    Ok(info)
+}
+
+/// Sends a CLNT_UCAST_DAC request for the given instance, receiving the response into `buffer`.
+/// Returns the number of bytes received and the round trip time.
+pub(crate) async fn dac_exchange<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+    socket_factory: &Mutex<&mut SF>,
+    buffer: &mut [u8],
+) -> Result<(usize, Duration), BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    check_instance_name(instance_name)?;
+
+    let mut socket = {
+        let mut socket_factory = socket_factory.lock().await;
+        bind_socket(&mut **socket_factory, &remote_addr, options, false)
+            .await
+            .map_err(BrowserError::BindFailed)?
+    };
 
     let remote = SocketAddr::new(remote_addr, 1434);
-    socket
-        .connect(&remote)
-        .await
-        .map_err(|e| BrowserError::ConnectFailed(remote, e))?;
-
-    let mut buffer = [0u8; 2 + super::MAX_INSTANCE_NAME_LEN + 1];
-    buffer[0] = CLNT_UCAST_DAC;
-    buffer[1] = VERSION;
-    buffer[2..(2 + instance_name.len())].copy_from_slice(instance_name.as_bytes()); // TODO: Encode as mbcs string
-    let buffer_len = 3 + instance_name.len();
-    socket
-        .send(&buffer[0..buffer_len])
-        .await
-        .map_err(|e| BrowserError::SendFailed(remote, e))?;
+    connect_socket(&mut socket, &remote, options).await?;
 
-    let mut buffer = [0u8; 6];
+    let request = dac_request(instance_name, options.dac_version);
+    exchange(
+        &mut socket,
+        &request,
+        buffer,
+        &remote,
+        options,
+        BrowseOperation::Dac,
+    )
+    .await
+}
+
+/// Builds a CLNT_UCAST_DAC request of the given version for the given instance.
+pub(crate) fn dac_request(instance_name: &str, version: u8) -> Vec<u8> {
+    // TODO: Encode as mbcs string
+    let name = instance_name.as_bytes();
+    let mut buffer = Vec::with_capacity(2 + name.len() + 1);
+    buffer.push(CLNT_UCAST_DAC);
+    buffer.push(version);
+    buffer.extend_from_slice(name);
+    buffer.push(0);
+    buffer
+}
 
-    let bytes_received = socket
-        .recv(&mut buffer)
-        .await
-        .map_err(BrowserError::ReceiveFailed)?;
-
-    if bytes_received < 1 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
+/// Parses the SVR_RESP datagram sent in response to a CLNT_UCAST_DAC request,
+/// e.g. one that was captured off the network.
+///
+/// # Examples
+/// ```
+/// let info = mssql_browser::parse_dac_response(&[0x05, 0x06, 0x00, 0x01, 0x2D, 0x05]).unwrap();
+/// assert_eq!(info.port, 1325);
+/// ```
+pub fn parse_dac_response(buffer: &[u8]) -> Result<DacInfo, BrowserProtocolError> {
+    parse_dac_response_version(buffer, DEFAULT_DAC_VERSION)
+}
+
+/// Parses the SVR_RESP datagram sent in response to a CLNT_UCAST_DAC request of the given
+/// version, which the response must report as well.
+pub(crate) fn parse_dac_response_version(
+    buffer: &[u8],
+    expected_version: u8,
+) -> Result<DacInfo, BrowserProtocolError> {
+    let version = parse_dac_version(buffer, expected_version)?;
+    if version != expected_version {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::DacVersion(expected_version),
+            found: BrowserProtocolToken::DacVersion(version),
+        });
     }
 
-    if buffer[0] != SVR_RESP {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
-                found: BrowserProtocolToken::MessageIdentifier(buffer[0]),
-            },
-        ));
+    if buffer.len() < 6 {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::DacPort,
+            found: BrowserProtocolToken::EndOfMessage,
+        });
     }
 
-    if bytes_received < 3 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::MessageLength,
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
+    let port = u16::from_le_bytes([buffer[4], buffer[5]]);
+    Ok(DacInfo { port })
+}
+
+/// Validates the header of the SVR_RESP datagram sent in response to a CLNT_UCAST_DAC request,
+/// returning the protocol version it reports, whichever version that is.
+/// `requested_version` is only used to report a datagram too short to hold a version.
+pub(crate) fn parse_dac_version(
+    buffer: &[u8],
+    requested_version: u8,
+) -> Result<u8, BrowserProtocolError> {
+    if buffer.is_empty() {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
+            found: BrowserProtocolToken::EndOfMessage,
+        });
     }
 
-    let packet_size = u16::from_le_bytes([buffer[1], buffer[2]]) as usize;
-    if packet_size != buffer.len() {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::LengthMismatch {
-                datagram: bytes_received,
-                header: packet_size,
-            },
-        ));
+    if buffer[0] != SVR_RESP {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
+            found: BrowserProtocolToken::MessageIdentifier(buffer[0]),
+        });
     }
 
-    if bytes_received < 4 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::DacVersion(VERSION),
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
+    if buffer.len() < 3 {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::MessageLength,
+            found: BrowserProtocolToken::EndOfMessage,
+        });
     }
 
-    if buffer[3] != VERSION {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::DacVersion(VERSION),
-                found: BrowserProtocolToken::DacVersion(buffer[3]),
-            },
-        ));
+    let packet_size = u16::from_le_bytes([buffer[1], buffer[2]]) as usize;
+    if buffer.len() < packet_size {
+        return Err(BrowserProtocolError::Truncated {
+            datagram: buffer.len(),
+            header: packet_size,
+        });
+    }
+    if packet_size != buffer.len() {
+        return Err(BrowserProtocolError::LengthMismatch {
+            datagram: buffer.len(),
+            header: packet_size,
+        });
     }
 
-    if bytes_received < 6 {
-        return Err(BrowserError::ProtocolError(
-            BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::DacPort,
-                found: BrowserProtocolToken::EndOfMessage,
-            },
-        ));
+    if buffer.len() < 4 {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::DacVersion(requested_version),
+            found: BrowserProtocolToken::EndOfMessage,
+        });
     }
 
-    let port = u16::from_le_bytes([buffer[4], buffer[5]]);
-    return Ok(DacInfo { port });
+    Ok(buffer[3])
 }
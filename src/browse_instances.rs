@@ -0,0 +1,239 @@
+use super::browse_instance::{check_instance_name, instance_request, parse_instance_response};
+use super::error::*;
+use super::info::*;
+use super::observer::DatagramDirection;
+use super::options::BrowserOptions;
+use super::socket::{
+    bind_socket, connect_socket, receive_error, recv_peer_deadline, UdpSocket, UdpSocketFactory,
+};
+use super::stats::BrowseOperation;
+use std::net::{IpAddr, SocketAddr};
+
+/// Gets information about several instances on the same host, reusing a single socket.
+/// All requests are sent at once, and the responses are matched to them by instance name.
+/// Instances that didn't respond within the timeout are returned as `Ok(None)`, those that
+/// sent a malformed response as the error parsing it.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instances are running.
+/// * `instance_names` - The names of the instances, each must be less than `MAX_INSTANCE_NAME_LEN` characters.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_instances(
+    remote_addr: IpAddr,
+    instance_names: &[&str],
+) -> Result<
+    Vec<(String, Result<Option<InstanceInfo>, BrowserProtocolError>)>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_instances_inner(remote_addr, instance_names, &mut factory).await
+}
+
+/// Gets information about several instances on the same host, reusing a single socket,
+/// using the given options. All requests are sent at once, and the responses are matched to
+/// them by instance name. Unanswered requests are retransmitted according to
+/// `BrowserOptions::retry`. Instances that didn't respond within the timeout are returned as
+/// `Ok(None)`, those that sent a malformed response as the error parsing it.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instances are running.
+/// * `instance_names` - The names of the instances, each must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_instances_with_options(
+    remote_addr: IpAddr,
+    instance_names: &[&str],
+    options: &BrowserOptions,
+) -> Result<
+    Vec<(String, Result<Option<InstanceInfo>, BrowserProtocolError>)>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_instances_with_options_inner(remote_addr, instance_names, options, &mut factory).await
+}
+
+/// Gets information about several instances on the same host, reusing a single socket.
+/// All requests are sent at once, and the responses are matched to them by instance name.
+/// Instances that didn't respond within the timeout are returned as `Ok(None)`, those that
+/// sent a malformed response as the error parsing it.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instances are running.
+/// * `instance_names` - The names of the instances, each must be less than `MAX_INSTANCE_NAME_LEN` characters.
+pub async fn browse_instances_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_names: &[&str],
+    socket_factory: &mut SF,
+) -> Result<
+    Vec<(String, Result<Option<InstanceInfo>, BrowserProtocolError>)>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    browse_instances_with_options_inner(
+        remote_addr,
+        instance_names,
+        &BrowserOptions::default(),
+        socket_factory,
+    )
+    .await
+}
+
+/// Gets information about several instances on the same host, reusing a single socket,
+/// using the given options. All requests are sent at once, and the responses are matched to
+/// them by instance name. Unanswered requests are retransmitted according to
+/// `BrowserOptions::retry`. Instances that didn't respond within the timeout are returned as
+/// `Ok(None)`, those that sent a malformed response as the error parsing it.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instances are running.
+/// * `instance_names` - The names of the instances, each must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+pub async fn browse_instances_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_names: &[&str],
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<
+    Vec<(String, Result<Option<InstanceInfo>, BrowserProtocolError>)>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    for instance_name in instance_names {
+        check_instance_name(instance_name)?;
+    }
+
+    let mut socket = bind_socket(socket_factory, &remote_addr, options, false)
+        .await
+        .map_err(BrowserError::BindFailed)?;
+
+    let remote = SocketAddr::new(remote_addr, 1434);
+    connect_socket(&mut socket, &remote, options).await?;
+
+    let requests = instance_names
+        .iter()
+        .map(|instance_name| instance_request(instance_name))
+        .collect::<Vec<_>>();
+    let mut results = instance_names
+        .iter()
+        .map(|_| Ok(None))
+        .collect::<Vec<Result<Option<InstanceInfo>, BrowserProtocolError>>>();
+    let mut sent_at = vec![options.now(); instance_names.len()];
+    let mut buffer = vec![0u8; options.recv_buffer_size];
+
+    let deadline = options.timeout.map(|timeout| options.now() + timeout);
+    let mut attempt = 0;
+
+    loop {
+        // Sends all requests that are still unanswered before waiting for any response
+        let round_started = options.now();
+        for (index, request) in requests.iter().enumerate() {
+            if !is_pending(&results[index]) {
+                continue;
+            }
+
+            sent_at[index] = options.now();
+            socket
+                .send_to(request, &remote)
+                .await
+                .map_err(|e| BrowserError::SendFailed(remote, e))?;
+            options.notify(DatagramDirection::Sent, &remote, request);
+            options.record(BrowseOperation::Instance, |s| s.record_request_sent());
+        }
+        attempt += 1;
+
+        let retry_at = options
+            .retry
+            .as_ref()
+            .and_then(|policy| policy.delay_after(attempt))
+            .map(|delay| round_started + delay);
+        let retry_due = match (retry_at, deadline) {
+            (Some(retry_at), Some(deadline)) => retry_at < deadline,
+            (retry_at, _) => retry_at.is_some(),
+        };
+        let wait_until = if retry_due { retry_at } else { deadline };
+
+        while results.iter().any(is_pending) {
+            let bytes_received =
+                match recv_peer_deadline(&mut socket, &mut buffer, &remote, wait_until, options)
+                    .await
+                    .map_err(|e| receive_error::<SF::Error, SF::Socket>(&remote, e))?
+                {
+                    Some(x) => x,
+                    None => break,
+                };
+            options.notify(
+                DatagramDirection::Received,
+                &remote,
+                &buffer[..bytes_received],
+            );
+            options.record(BrowseOperation::Instance, |s| s.record_response_received());
+
+            let response = &buffer[..bytes_received];
+            let parsed = parse_instance_response(remote_addr, response, options);
+            if parsed.is_err() {
+                options.record(BrowseOperation::Instance, |s| s.record_parse_error());
+            }
+
+            // Responses naming an instance that was already answered, or wasn't asked for, are
+            // late responses to an earlier transmission. Malformed responses without a readable
+            // instance name can't be attributed to any request and are skipped as well.
+            let name = match &parsed {
+                Ok(instance) => Some(instance.instance_name.as_str()),
+                Err(_) => response_instance_name(response),
+            };
+            let index = match name.and_then(|name| {
+                (0..instance_names.len()).find(|&index| {
+                    is_pending(&results[index]) && instance_names[index].eq_ignore_ascii_case(name)
+                })
+            }) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            results[index] = parsed.map(|mut instance| {
+                let rtt = options.now() - sent_at[index];
+                options.record(BrowseOperation::Instance, |s| s.record_rtt(rtt));
+                instance.rtt = Some(rtt);
+                instance.source = Some(remote);
+                Some(instance)
+            });
+        }
+
+        if !retry_due || !results.iter().any(is_pending) {
+            break;
+        }
+    }
+
+    for _ in results.iter().filter(|result| is_pending(result)) {
+        options.record(BrowseOperation::Instance, |s| s.record_timeout());
+    }
+
+    Ok(instance_names
+        .iter()
+        .map(|instance_name| instance_name.to_string())
+        .zip(results)
+        .collect())
+}
+
+/// Determines whether no response was received yet for the request of an instance.
+fn is_pending(result: &Result<Option<InstanceInfo>, BrowserProtocolError>) -> bool {
+    matches!(result, Ok(None))
+}
+
+/// Finds the value of the InstanceName field in a response, even if the response
+/// is malformed otherwise, to attribute the parse error to the right request.
+fn response_instance_name(response: &[u8]) -> Option<&str> {
+    let body = std::str::from_utf8(response.get(3..)?).ok()?;
+    let mut parts = body.split(';');
+    while let Some(part) = parts.next() {
+        if part.eq_ignore_ascii_case("InstanceName") {
+            return parts.next();
+        }
+    }
+
+    None
+}
@@ -0,0 +1,110 @@
+use super::browse::{browse_with_options_inner, AsyncInstanceIterator};
+use super::error::*;
+use super::options::BrowserOptions;
+use super::socket::{UdpSocket, UdpSocketFactory};
+use std::net::{IpAddr, Ipv6Addr};
+
+/// Discovers any SQL Server instances running on hosts that are members of the given IPv6
+/// multicast group, e.g. `ff02::1` for all hosts on the link, sending the request from the
+/// interface with the given index. Link-local groups exist once per interface, so this is the
+/// IPv6 counterpart of browsing a subnet via its directed broadcast address. Joining the group
+/// isn't necessary, as hosts answer with unicast datagrams.
+///
+/// # Arguments
+/// * `group` - The multicast group to send the request to.
+/// * `interface_index` - The index of the interface to send the request from.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_multicast_v6(
+    group: Ipv6Addr,
+    interface_index: u32,
+) -> Result<
+    AsyncInstanceIterator<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_multicast_v6_inner(group, interface_index, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on hosts that are members of the given IPv6
+/// multicast group, sending the request from the interface with the given index and using
+/// the given options. The interface overrides `BrowserOptions::multicast_interface_v6`.
+///
+/// # Arguments
+/// * `group` - The multicast group to send the request to.
+/// * `interface_index` - The index of the interface to send the request from.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_multicast_v6_with_options(
+    group: Ipv6Addr,
+    interface_index: u32,
+    options: &BrowserOptions,
+) -> Result<
+    AsyncInstanceIterator<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_multicast_v6_with_options_inner(group, interface_index, options, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on hosts that are members of the given IPv6
+/// multicast group, e.g. `ff02::1` for all hosts on the link, sending the request from the
+/// interface with the given index. Link-local groups exist once per interface, so this is the
+/// IPv6 counterpart of browsing a subnet via its directed broadcast address. Joining the group
+/// isn't necessary, as hosts answer with unicast datagrams.
+///
+/// # Arguments
+/// * `group` - The multicast group to send the request to.
+/// * `interface_index` - The index of the interface to send the request from.
+pub async fn browse_multicast_v6_inner<SF: UdpSocketFactory>(
+    group: Ipv6Addr,
+    interface_index: u32,
+    socket_factory: &mut SF,
+) -> Result<
+    AsyncInstanceIterator<SF::Socket>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    browse_multicast_v6_with_options_inner(
+        group,
+        interface_index,
+        &BrowserOptions::default(),
+        socket_factory,
+    )
+    .await
+}
+
+/// Discovers any SQL Server instances running on hosts that are members of the given IPv6
+/// multicast group, sending the request from the interface with the given index and using
+/// the given options. The interface overrides `BrowserOptions::multicast_interface_v6`.
+///
+/// # Arguments
+/// * `group` - The multicast group to send the request to.
+/// * `interface_index` - The index of the interface to send the request from.
+/// * `options` - The options to use for this operation.
+pub async fn browse_multicast_v6_with_options_inner<SF: UdpSocketFactory>(
+    group: Ipv6Addr,
+    interface_index: u32,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<
+    AsyncInstanceIterator<SF::Socket>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    if !group.is_multicast() {
+        return Err(BrowserError::NotMulticast(IpAddr::V6(group)));
+    }
+
+    browse_with_options_inner(
+        IpAddr::V6(group),
+        &options
+            .clone()
+            .multicast_interface_v6(Some(interface_index)),
+        socket_factory,
+    )
+    .await
+}
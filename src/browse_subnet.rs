@@ -0,0 +1,104 @@
+use super::broadcast::directed_broadcast;
+use super::browse::{browse_with_options_inner, AsyncInstanceIterator};
+use super::error::*;
+use super::options::BrowserOptions;
+use super::socket::{UdpSocket, UdpSocketFactory};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Discovers any SQL Server instances running on hosts in the given remote IPv4 subnet,
+/// by sending the request to its directed broadcast address, e.g. `10.0.5.255` for `10.0.5.0/24`.
+/// Unlike the limited broadcast address this reaches subnets other than the local one,
+/// as long as the routers in between forward directed broadcasts.
+///
+/// # Arguments
+/// * `network` - Any address in the subnet to browse.
+/// * `prefix` - The length of the network prefix of the subnet, at most 32.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_subnet(
+    network: Ipv4Addr,
+    prefix: u8,
+) -> Result<
+    AsyncInstanceIterator<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_subnet_inner(network, prefix, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on hosts in the given remote IPv4 subnet,
+/// by sending the request to its directed broadcast address, using the given options.
+/// The broadcast option is always enabled on the socket.
+///
+/// # Arguments
+/// * `network` - Any address in the subnet to browse.
+/// * `prefix` - The length of the network prefix of the subnet, at most 32.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_subnet_with_options(
+    network: Ipv4Addr,
+    prefix: u8,
+    options: &BrowserOptions,
+) -> Result<
+    AsyncInstanceIterator<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_subnet_with_options_inner(network, prefix, options, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on hosts in the given remote IPv4 subnet,
+/// by sending the request to its directed broadcast address, e.g. `10.0.5.255` for `10.0.5.0/24`.
+/// Unlike the limited broadcast address this reaches subnets other than the local one,
+/// as long as the routers in between forward directed broadcasts.
+///
+/// # Arguments
+/// * `network` - Any address in the subnet to browse.
+/// * `prefix` - The length of the network prefix of the subnet, at most 32.
+pub async fn browse_subnet_inner<SF: UdpSocketFactory>(
+    network: Ipv4Addr,
+    prefix: u8,
+    socket_factory: &mut SF,
+) -> Result<
+    AsyncInstanceIterator<SF::Socket>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    browse_subnet_with_options_inner(network, prefix, &BrowserOptions::default(), socket_factory)
+        .await
+}
+
+/// Discovers any SQL Server instances running on hosts in the given remote IPv4 subnet,
+/// by sending the request to its directed broadcast address, using the given options.
+/// The broadcast option is always enabled on the socket.
+///
+/// # Arguments
+/// * `network` - Any address in the subnet to browse.
+/// * `prefix` - The length of the network prefix of the subnet, at most 32.
+/// * `options` - The options to use for this operation.
+pub async fn browse_subnet_with_options_inner<SF: UdpSocketFactory>(
+    network: Ipv4Addr,
+    prefix: u8,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<
+    AsyncInstanceIterator<SF::Socket>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    if prefix > 32 {
+        return Err(BrowserError::InvalidPrefixLength(prefix));
+    }
+
+    let broadcast_addr = directed_broadcast(network, prefix);
+
+    browse_with_options_inner(
+        IpAddr::V4(broadcast_addr),
+        &options.clone().broadcast(true),
+        socket_factory,
+    )
+    .await
+}
@@ -0,0 +1,78 @@
+use super::browse::browse_with_options_inner;
+use super::error::*;
+use super::options::BrowserOptions;
+use super::socket::{UdpSocket, UdpSocketFactory};
+use futures::stream::{self, Stream};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+/// Discovers any SQL Server instances running on hosts reached by the given multicast address,
+/// yielding only the TCP endpoint of each, which is what connecting to them takes. Instances
+/// without a TCP endpoint are skipped, as are invalid responses. The stream ends once browsing
+/// finishes as configured in the options, or after yielding an error if receiving fails.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///   This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///   group, see `BrowserOptions::join_multicast_group`.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browse_tcp_endpoints(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+) -> Result<
+    impl Stream<
+        Item = Result<
+            SocketAddr,
+            BrowserError<
+                Infallible,
+                <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+            >,
+        >,
+    >,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+>{
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browse_tcp_endpoints_inner(multicast_addr, options, &mut factory).await
+}
+
+/// Discovers any SQL Server instances running on hosts reached by the given multicast address,
+/// yielding only the TCP endpoint of each, see `browse_tcp_endpoints`.
+///
+/// # Arguments
+/// * `multicast_addr` - A multicast address to which to broadcast the browse datagram.
+///   This can be the Ipv4 BROADCAST address, or a Ipv4 or Ipv6 multicast
+///   group, see `BrowserOptions::join_multicast_group`.
+/// * `options` - The options to use for this operation.
+pub async fn browse_tcp_endpoints_inner<SF: UdpSocketFactory>(
+    multicast_addr: IpAddr,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<
+    impl Stream<Item = Result<SocketAddr, BrowserError<Infallible, <SF::Socket as UdpSocket>::Error>>>,
+    BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>,
+> {
+    let iterator = browse_with_options_inner(multicast_addr, options, socket_factory).await?;
+
+    // The iterator is dropped after an error, which ends the stream
+    Ok(stream::unfold(Some(iterator), |iterator| async move {
+        let mut iterator = iterator?;
+
+        loop {
+            match iterator.next().await {
+                Ok(Some(instance)) => {
+                    if let Some(tcp_info) = &instance.tcp_info {
+                        let endpoint = SocketAddr::new(instance.addr, tcp_info.port);
+                        return Some((Ok(endpoint), Some(iterator)));
+                    }
+                }
+                Ok(None) => return None,
+                Err(BrowserError::ProtocolError(_)) => {}
+                Err(e) => return Some((Err(e), None)),
+            }
+        }
+    }))
+}
@@ -0,0 +1,109 @@
+use super::browse_instance_dac::{dac_exchange, parse_dac_version};
+use super::error::*;
+use super::options::BrowserOptions;
+use super::socket::{UdpSocket, UdpSocketFactory};
+use super::stats::BrowseOperation;
+use futures::lock::Mutex;
+use std::net::IpAddr;
+
+/// Performs the DAC handshake with the given instance, returning the protocol version byte of
+/// the response instead of the DAC endpoint. SSRP has no capability exchange, but the version
+/// reveals which revision of the DAC request the browser service supports. Unlike
+/// `browse_instance_dac`, responses of any version are accepted.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browser_dac_version(
+    remote_addr: IpAddr,
+    instance_name: &str,
+) -> Result<
+    u8,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browser_dac_version_inner(remote_addr, instance_name, &mut factory).await
+}
+
+/// Performs the DAC handshake with the given instance, returning the protocol version byte of
+/// the response, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browser_dac_version_with_options(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+) -> Result<
+    u8,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browser_dac_version_with_options_inner(remote_addr, instance_name, options, &mut factory).await
+}
+
+/// Performs the DAC handshake with the given instance, returning the protocol version byte of
+/// the response instead of the DAC endpoint. SSRP has no capability exchange, but the version
+/// reveals which revision of the DAC request the browser service supports. Unlike
+/// `browse_instance_dac`, responses of any version are accepted.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+pub async fn browser_dac_version_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    socket_factory: &mut SF,
+) -> Result<u8, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    browser_dac_version_with_options_inner(
+        remote_addr,
+        instance_name,
+        &BrowserOptions::default(),
+        socket_factory,
+    )
+    .await
+}
+
+/// Performs the DAC handshake with the given instance, returning the protocol version byte of
+/// the response, using the given options.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host on which the instance is running.
+/// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+/// * `options` - The options to use for this operation.
+pub async fn browser_dac_version_with_options_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    instance_name: &str,
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<u8, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    // Later versions might send a larger response than the 6 bytes of version 1
+    let mut buffer = vec![0u8; options.recv_buffer_size];
+    let (bytes_received, rtt) = dac_exchange(
+        remote_addr,
+        instance_name,
+        options,
+        &Mutex::new(socket_factory),
+        &mut buffer,
+    )
+    .await?;
+
+    let version =
+        parse_dac_version(&buffer[..bytes_received], options.dac_version).map_err(|e| {
+            options.record(BrowseOperation::Dac, |s| s.record_parse_error());
+            BrowserError::ProtocolError(e.with_response(&buffer[..bytes_received]))
+        })?;
+
+    options.record(BrowseOperation::Dac, |s| s.record_rtt(rtt));
+    Ok(version)
+}
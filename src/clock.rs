@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The source of the current time the browser operations compute their deadlines, retransmissions
+/// and round-trip times from, set via `BrowserOptions::clock`. The waiting itself is left to the
+/// socket, which is handed the time remaining until the next deadline as its receive timeout.
+/// Combined with a `ManualClock`, a custom socket can let that time pass instantly by advancing
+/// the clock instead of sleeping, which makes timeouts testable without real delays.
+pub trait Clock: Send + Sync {
+    /// Gets the current point in time.
+    fn now(&self) -> Instant;
+}
+
+/// The clock used by default, which reads the monotonic system clock via `Instant::now`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when advanced explicitly, for testing time dependent behaviour.
+/// Clones share the same time, so a clone can be kept to advance the clock installed in the options.
+///
+/// # Examples
+/// ```
+/// use mssql_browser::{BrowserOptions, Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let options = BrowserOptions::new().clock(clock.clone());
+///
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(clock.now() - start, Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a clock that stands still at the current point in time.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
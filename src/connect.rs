@@ -0,0 +1,28 @@
+use super::info::InstanceInfo;
+use std::net::SocketAddr;
+
+/// The TCP stream type of the async runtime selected via the crate features.
+#[cfg(all(feature = "tokio", not(feature = "prefer-async-std")))]
+pub type TcpStream = tokio::net::TcpStream;
+
+/// The TCP stream type of the async runtime selected via the crate features.
+#[cfg(all(
+    feature = "async-std",
+    any(not(feature = "tokio"), feature = "prefer-async-std")
+))]
+pub type TcpStream = async_std::net::TcpStream;
+
+impl InstanceInfo {
+    /// Opens a TCP connection to the TCP endpoint advertised by this instance.
+    /// Fails with `std::io::ErrorKind::NotFound` if the instance doesn't advertise one.
+    pub async fn connect_tcp(&self) -> std::io::Result<TcpStream> {
+        let tcp_info = self.tcp_info.as_ref().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "instance does not advertise a TCP endpoint",
+            )
+        })?;
+
+        TcpStream::connect(SocketAddr::new(self.addr, tcp_info.port)).await
+    }
+}
@@ -1,7 +1,8 @@
 use std::error::Error;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 /// An error that can be returned from the different browser operations
+#[non_exhaustive]
 pub enum BrowserError<
     #[cfg(any(feature = "tokio", feature = "async-std"))]
     SFError: Error = <super::socket::DefaultSocketFactory as super::socket::UdpSocketFactory>::Error,
@@ -18,18 +19,48 @@ pub enum BrowserError<
     /// Enabling the broadcast option on the `tokio::net::UdpSocket` failed.
     SetBroadcastFailed(SError),
 
+    /// Joining the given multicast group failed, see `BrowserOptions::join_multicast_group`.
+    JoinMulticastFailed(IpAddr, SError),
+
     /// Sending the request datagram failed.
     SendFailed(SocketAddr, SError),
 
     /// Locking the `tokio::net::UdpSocket` to a specific endpoint via `tokio::net::UdpSocket::connect` failed.
     ConnectFailed(SocketAddr, SError),
 
+    /// Connecting the socket to the given address didn't complete within the configured timeout.
+    ConnectTimeout(SocketAddr),
+
     /// Receiving a datagram failed.
     ReceiveFailed(SError),
 
+    /// No response was received from the given address within the configured timeout.
+    Timeout(SocketAddr),
+
+    /// The given host actively refused the request, which means it is reachable
+    /// but no browser service is listening on it.
+    BrowserNotRunning(SocketAddr),
+
     /// The given instance name is too long.
     InstanceNameTooLong,
 
+    /// The given instance name is empty.
+    InstanceNameEmpty,
+
+    /// The given instance name contains a character that isn't permitted in instance names,
+    /// or that would break the framing of the request.
+    InvalidInstanceName(char),
+
+    /// The given network prefix length is larger than the number of bits in the address.
+    InvalidPrefixLength(u8),
+
+    /// The given address isn't a multicast address.
+    NotMulticast(IpAddr),
+
+    /// Resolving the given host name failed, or it resolved to no address
+    /// accepted by `BrowserOptions::address_preference`.
+    ResolveFailed(String, std::io::Error),
+
     /// The server send back an invalid response.
     ProtocolError(BrowserProtocolError),
 }
@@ -44,10 +75,19 @@ impl<SFError: std::error::Error, SError: Error> std::fmt::Debug
         match self {
             BindFailed(e) => write!(f, "BindFailed({:?})", e),
             SetBroadcastFailed(e) => write!(f, "SetBroadcastFailed({:?})", e),
+            JoinMulticastFailed(addr, e) => write!(f, "JoinMulticastFailed({:?}, {:?})", addr, e),
             SendFailed(addr, e) => write!(f, "SendFailed({:?}, {:?})", addr, e),
             ConnectFailed(addr, e) => write!(f, "ConnectFailed({:?}, {:?})", addr, e),
+            ConnectTimeout(addr) => write!(f, "ConnectTimeout({:?})", addr),
             ReceiveFailed(e) => write!(f, "ReceiveFailed({:?})", e),
+            Timeout(addr) => write!(f, "Timeout({:?})", addr),
+            BrowserNotRunning(addr) => write!(f, "BrowserNotRunning({:?})", addr),
             InstanceNameTooLong => write!(f, "InstanceNameTooLong"),
+            InstanceNameEmpty => write!(f, "InstanceNameEmpty"),
+            InvalidInstanceName(c) => write!(f, "InvalidInstanceName({:?})", c),
+            InvalidPrefixLength(prefix) => write!(f, "InvalidPrefixLength({})", prefix),
+            NotMulticast(addr) => write!(f, "NotMulticast({:?})", addr),
+            ResolveFailed(host, e) => write!(f, "ResolveFailed({:?}, {:?})", host, e),
             ProtocolError(e) => write!(f, "ProtocolError({:?})", e),
         }
     }
@@ -62,37 +102,116 @@ impl<SFError: std::error::Error, SError: Error> std::fmt::Display
         match self {
             BindFailed(err) => write!(f, "bind failed: {}", err),
             SetBroadcastFailed(err) => write!(f, "enabling broadcast option failed: {}", err),
+            JoinMulticastFailed(addr, err) => {
+                write!(f, "joining multicast group '{}' failed: {}", addr, err)
+            }
             SendFailed(addr, err) => write!(f, "sending of datagram to '{}' failed: {}", addr, err),
             ConnectFailed(addr, err) => write!(f, "connect to '{}' failed: {}", addr, err),
+            ConnectTimeout(addr) => write!(f, "timed out connecting to '{}'", addr),
             ReceiveFailed(err) => write!(f, "receiving of datagram failed: {}", err),
+            Timeout(addr) => write!(f, "timed out waiting for a response from '{}'", addr),
+            BrowserNotRunning(addr) => write!(f, "no browser service is running on '{}'", addr),
             InstanceNameTooLong => write!(
                 f,
                 "specified instance name is longer than {} bytes",
                 super::MAX_INSTANCE_NAME_LEN
             ),
+            InstanceNameEmpty => write!(f, "specified instance name is empty"),
+            InvalidInstanceName(c) => {
+                write!(f, "specified instance name contains the invalid character {:?}", c)
+            }
+            InvalidPrefixLength(prefix) => write!(f, "invalid network prefix length /{}", prefix),
+            NotMulticast(addr) => write!(f, "'{}' is not a multicast address", addr),
+            ResolveFailed(host, err) => write!(f, "resolving '{}' failed: {}", host, err),
             ProtocolError(e) => write!(f, "protocol error: {}", e),
         }
     }
 }
 
-impl<SFError: Error, SError: Error> Error for BrowserError<SFError, SError> {
-    fn cause(&self) -> Option<&dyn Error> {
+impl<SFError: Error + 'static, SError: Error + 'static> Error for BrowserError<SFError, SError> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         use BrowserError::*;
 
         match self {
             BindFailed(err) => Some(err),
             SetBroadcastFailed(err) => Some(err),
+            JoinMulticastFailed(_, err) => Some(err),
             SendFailed(_, err) => Some(err),
             ConnectFailed(_, err) => Some(err),
+            ConnectTimeout(_) => None,
             ReceiveFailed(err) => Some(err),
+            Timeout(_) => None,
+            BrowserNotRunning(_) => None,
             InstanceNameTooLong => None,
+            InstanceNameEmpty => None,
+            InvalidInstanceName(_) => None,
+            InvalidPrefixLength(_) => None,
+            NotMulticast(_) => None,
+            ResolveFailed(_, err) => Some(err),
             ProtocolError(err) => Some(err),
         }
     }
 }
 
+impl<SFError: Error, SError: Error> From<BrowserProtocolError> for BrowserError<SFError, SError> {
+    fn from(err: BrowserProtocolError) -> BrowserError<SFError, SError> {
+        BrowserError::ProtocolError(err)
+    }
+}
+
+impl<SFError: Error, SError: Error> BrowserError<SFError, SError> {
+    /// Converts the error into one with different socket factory and socket error types,
+    /// mapping the underlying error, if any, with the matching function. Useful to unify the
+    /// errors of operations run on different `UdpSocketFactory` implementations.
+    pub fn map_socket_err<F1: Error, F2: Error>(
+        self,
+        map_factory_err: impl FnOnce(SFError) -> F1,
+        map_socket_err: impl FnOnce(SError) -> F2,
+    ) -> BrowserError<F1, F2> {
+        use BrowserError::*;
+
+        match self {
+            BindFailed(err) => BindFailed(map_factory_err(err)),
+            SetBroadcastFailed(err) => SetBroadcastFailed(map_socket_err(err)),
+            JoinMulticastFailed(addr, err) => JoinMulticastFailed(addr, map_socket_err(err)),
+            SendFailed(addr, err) => SendFailed(addr, map_socket_err(err)),
+            ConnectFailed(addr, err) => ConnectFailed(addr, map_socket_err(err)),
+            ConnectTimeout(addr) => ConnectTimeout(addr),
+            ReceiveFailed(err) => ReceiveFailed(map_socket_err(err)),
+            Timeout(addr) => Timeout(addr),
+            BrowserNotRunning(addr) => BrowserNotRunning(addr),
+            InstanceNameTooLong => InstanceNameTooLong,
+            InstanceNameEmpty => InstanceNameEmpty,
+            InvalidInstanceName(c) => InvalidInstanceName(c),
+            InvalidPrefixLength(prefix) => InvalidPrefixLength(prefix),
+            NotMulticast(addr) => NotMulticast(addr),
+            ResolveFailed(host, err) => ResolveFailed(host, err),
+            ProtocolError(err) => ProtocolError(err),
+        }
+    }
+
+    /// Converts the error into one with different socket factory and socket error types,
+    /// into which the underlying error, if any, converts via `From`.
+    pub fn convert_socket_err<F1, F2>(self) -> BrowserError<F1, F2>
+    where
+        F1: Error + From<SFError>,
+        F2: Error + From<SError>,
+    {
+        self.map_socket_err(F1::from, F2::from)
+    }
+}
+
+impl<SError: Error> BrowserError<std::convert::Infallible, SError> {
+    /// Converts an error of an operation on an already bound socket into the error type
+    /// of the operation that bound it.
+    pub(crate) fn with_factory_error<SFError: Error>(self) -> BrowserError<SFError, SError> {
+        self.map_socket_err(|err| match err {}, |err| err)
+    }
+}
+
 /// Received an unexpected response from the server
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum BrowserProtocolError {
     /// An unexpected token was received from the server
     UnexpectedToken {
@@ -113,11 +232,98 @@ pub enum BrowserProtocolError {
         header: usize,
     },
 
+    /// The datagram is shorter than the length specified in the packet header. This usually
+    /// means a large response exceeded the path MTU and was cut short or lost fragments on the
+    /// way, or that it didn't fit into the buffer set via `BrowserOptions::recv_buffer_size`.
+    Truncated {
+        /// The size, in bytes, of the datagram
+        datagram: usize,
+
+        /// The size, in bytes, specified in the packet header
+        header: usize,
+    },
+
+    /// The response to an instance request consists of the SVR_RESP header only, without
+    /// describing the instance. Hosts that run the browser service but have no matching
+    /// instance to report can respond like this.
+    EmptyResponse,
+
     /// Unexpected MBCS string encoding found in the received message
     InvalidUtf8(std::str::Utf8Error),
 
     /// There was extraneous data after the parsed message
     ExtraneousData(Vec<u8>),
+
+    /// The response is larger than the limit configured via `BrowserOptions::max_response_size`.
+    ResponseTooLarge {
+        /// The maximum size, in bytes
+        limit: usize,
+
+        /// The size, in bytes, of the datagram
+        actual: usize,
+    },
+
+    /// A host response lists the same instance more than once,
+    /// see `BrowserOptions::reject_duplicate_instances`.
+    DuplicateInstance {
+        /// The name of the instance that was listed again
+        name: String,
+    },
+
+    /// A received response could not be parsed. Returned by the browse operations,
+    /// which attach the datagram to the error so it can be inspected when diagnosing
+    /// a misbehaving server, see `reason` for the error itself.
+    InvalidResponse {
+        /// The reason the response could not be parsed
+        error: Box<BrowserProtocolError>,
+
+        /// The received datagram
+        response: Vec<u8>,
+    },
+
+    /// One of the instances described in a response could not be parsed. Returned by
+    /// `InstanceIterator::into_partial`, which skips such instances and continues with the
+    /// next one, see `reason` for the error itself.
+    InvalidInstance {
+        /// The reason the instance could not be parsed
+        error: Box<BrowserProtocolError>,
+
+        /// The offset of the description of the instance within the datagram
+        offset: usize,
+
+        /// The description of the instance that was skipped
+        record: String,
+    },
+}
+
+impl BrowserProtocolError {
+    /// Gets the received datagram the error was found in, if it is attached.
+    pub fn response(&self) -> Option<&[u8]> {
+        match self {
+            BrowserProtocolError::InvalidResponse { response, .. } => Some(response),
+            _ => None,
+        }
+    }
+
+    /// Gets the reason the response could not be parsed, without the attached datagram.
+    pub fn reason(&self) -> &BrowserProtocolError {
+        match self {
+            BrowserProtocolError::InvalidResponse { error, .. } => error.reason(),
+            BrowserProtocolError::InvalidInstance { error, .. } => error.reason(),
+            _ => self,
+        }
+    }
+
+    /// Attaches the datagram the error was found in, unless one is attached already.
+    pub(crate) fn with_response(self, response: &[u8]) -> BrowserProtocolError {
+        match self {
+            BrowserProtocolError::InvalidResponse { .. } => self,
+            _ => BrowserProtocolError::InvalidResponse {
+                error: Box::new(self),
+                response: response.to_vec(),
+            },
+        }
+    }
 }
 
 impl std::fmt::Display for BrowserProtocolError {
@@ -133,16 +339,50 @@ impl std::fmt::Display for BrowserProtocolError {
                 "mismatch between datagram size {} bytes and size specified in header {} bytes",
                 datagram, header
             ),
+            Truncated { datagram, header } => write!(
+                f,
+                "datagram of {} bytes is truncated, the header specifies {} bytes",
+                datagram, header
+            ),
+            EmptyResponse => write!(f, "response does not describe any instance"),
             InvalidUtf8(err) => err.fmt(f),
             ExtraneousData(data) => write!(f, "{} unexpected trailing bytes", data.len()),
+            ResponseTooLarge { limit, actual } => write!(
+                f,
+                "response of {} bytes exceeds the limit of {} bytes",
+                actual, limit
+            ),
+            DuplicateInstance { name } => write!(f, "instance '{}' is listed more than once", name),
+            InvalidResponse { error, .. } => error.fmt(f),
+            InvalidInstance { error, offset, .. } => {
+                write!(f, "instance at offset {} skipped: {}", offset, error)
+            }
         }
     }
 }
 
-impl Error for BrowserProtocolError {}
+impl Error for BrowserProtocolError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use BrowserProtocolError::*;
+
+        match self {
+            InvalidUtf8(err) => Some(err),
+            UnexpectedToken { .. } => None,
+            LengthMismatch { .. } => None,
+            Truncated { .. } => None,
+            EmptyResponse => None,
+            ExtraneousData(_) => None,
+            ResponseTooLarge { .. } => None,
+            DuplicateInstance { .. } => None,
+            InvalidResponse { error, .. } => Some(&**error),
+            InvalidInstance { error, .. } => Some(&**error),
+        }
+    }
+}
 
 /// The value that was expected.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum BrowserProtocolToken {
     /// End of the datagram
     EndOfMessage,
@@ -161,7 +401,6 @@ pub enum BrowserProtocolToken {
     Identifier(BrowserProtocolField),
     ValueOf(BrowserProtocolField),
     TcpPort,
-    ViaParameters,
     EndpointIdentifierOrSemicolon,
 }
 
@@ -176,17 +415,21 @@ impl std::fmt::Display for BrowserProtocolToken {
             MessageLength => write!(f, "message length"),
             DacVersion(v) => write!(f, "dac version {}", v),
             DacPort => write!(f, "dac port"),
-            Identifier(field) => write!(f, "identifier for field {:?}", field),
-            ValueOf(field) => write!(f, "value for field {:?}", field),
+            Identifier(field) => write!(f, "identifier for field {}", field),
+            ValueOf(field) => write!(f, "value for field {}", field),
             TcpPort => write!(f, "tcp port"),
-            ViaParameters => write!(f, "via parameters"),
-            EndpointIdentifierOrSemicolon => write!(f, "endpoint identifier or semicolon"),
+            EndpointIdentifierOrSemicolon => write!(
+                f,
+                "endpoint identifier (one of {}) or semicolon",
+                super::info::ENDPOINT_IDENTIFIERS.join(", ")
+            ),
         }
     }
 }
 
 /// Different fields found in a browser response
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
 pub enum BrowserProtocolField {
     ServerName,
     InstanceName,
@@ -196,6 +439,8 @@ pub enum BrowserProtocolField {
     NamedPipeName,
     TcpPort,
     ViaMachineName,
+    ViaNic,
+    ViaPort,
     RpcComputerName,
     SpxServiceName,
     AppleTalkObjectName,
@@ -203,3 +448,27 @@ pub enum BrowserProtocolField {
     BvGroupName,
     BvOrgName,
 }
+
+impl std::fmt::Display for BrowserProtocolField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use BrowserProtocolField::*;
+
+        match self {
+            ServerName => write!(f, "server name"),
+            InstanceName => write!(f, "instance name"),
+            IsClustered => write!(f, "is clustered"),
+            Version => write!(f, "version"),
+            NamedPipeName => write!(f, "named pipe name"),
+            TcpPort => write!(f, "TCP port"),
+            ViaMachineName => write!(f, "VIA machine name"),
+            ViaNic => write!(f, "VIA NIC"),
+            ViaPort => write!(f, "VIA port"),
+            RpcComputerName => write!(f, "RPC computer name"),
+            SpxServiceName => write!(f, "SPX service name"),
+            AppleTalkObjectName => write!(f, "AppleTalk object name"),
+            BvItemName => write!(f, "Banyan VINES item name"),
+            BvGroupName => write!(f, "Banyan VINES group name"),
+            BvOrgName => write!(f, "Banyan VINES organization name"),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::net::SocketAddr;
+
+/// Tracks whether a receive is in flight, so dropping a socket in the middle of an
+/// operation, e.g. because the future awaiting it was cancelled, can be traced.
+/// Zero sized and free unless the `tracing` feature is enabled.
+#[derive(Default)]
+pub(crate) struct InFlight {
+    #[cfg(feature = "tracing")]
+    receiving_from: Option<SocketAddr>,
+}
+
+impl InFlight {
+    /// Marks a receive of a response from `remote` as started.
+    #[inline]
+    pub(crate) fn start(&mut self, _remote: &SocketAddr) {
+        #[cfg(feature = "tracing")]
+        {
+            self.receiving_from = Some(*_remote);
+        }
+    }
+
+    /// Marks the receive as completed, successfully or not.
+    #[inline]
+    pub(crate) fn finish(&mut self) {
+        #[cfg(feature = "tracing")]
+        {
+            self.receiving_from = None;
+        }
+    }
+
+    /// Emits a trace event if a receive is still in flight, called when `owner` is dropped.
+    #[inline]
+    pub(crate) fn report_drop(&self, _owner: &str) {
+        #[cfg(feature = "tracing")]
+        {
+            if let Some(remote) = self.receiving_from {
+                tracing::debug!(
+                    owner = _owner,
+                    remote = %remote,
+                    "socket dropped while receiving, the operation was cancelled"
+                );
+            }
+        }
+    }
+}
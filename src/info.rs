@@ -1,5 +1,10 @@
 use super::error::{BrowserProtocolError, BrowserProtocolField, BrowserProtocolToken};
-use std::net::IpAddr;
+use std::convert::TryFrom;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// The identifiers of the endpoints a response can describe, in the order they're defined in by the protocol.
+pub const ENDPOINT_IDENTIFIERS: [&str; 7] = ["np", "tcp", "via", "rpc", "spx", "adsp", "bv"];
 
 /// Information send in a browser protocol response
 /// See [SVR_RESP](https://docs.microsoft.com/en-us/openspecs/windows_protocols/mc-sqlr/2e1560c9-5097-4023-9f5e-72b9ff1ec3b1)
@@ -9,6 +14,8 @@ pub struct InstanceInfo {
     pub addr: IpAddr,
 
     /// The name of the server. The SERVERNAME MUST be no greater than 255 bytes.
+    /// This is the NetBIOS name of the machine the instance runs on, which can differ from
+    /// the name or address that was queried, e.g. when reached through an alias or a cluster name.
     pub server_name: String,
 
     /// A text string that represents the name of the server instance being described.
@@ -28,6 +35,370 @@ pub struct InstanceInfo {
     pub spx_info: Option<SpxInfo>,
     pub adsp_info: Option<AdspInfo>,
     pub bv_info: Option<BvInfo>,
+
+    /// Any `key;value` pairs following the endpoint information that aren't part of the
+    /// documented protocol, in the order they were sent. Always empty when parsing strictly.
+    pub unknown: Vec<(String, String)>,
+
+    /// The DAC endpoint of the instance. Only filled in by `browse_host_with_dac`,
+    /// and only if the instance answered the DAC request.
+    pub dac_info: Option<DacInfo>,
+
+    /// The time between sending the request and receiving the response this
+    /// instance was described in. For broadcasts this is the time since the probe was sent.
+    /// `None` if the information wasn't received over the network.
+    pub rtt: Option<Duration>,
+
+    /// The UDP address the response describing this instance was received from.
+    /// On multi-homed servers this can differ from the addresses advertised in the response.
+    /// `None` if the information wasn't received over the network.
+    pub source: Option<SocketAddr>,
+
+    /// How the request that this instance was described in response to was sent.
+    pub discovery_method: DiscoveryMethod,
+
+    /// The header of the response this instance was described in.
+    /// `None` if the information wasn't parsed from an SVR_RESP datagram.
+    pub header: Option<ResponseHeader>,
+}
+
+impl InstanceInfo {
+    /// Gets a key for ordering instances deterministically, by address and then instance name.
+    ///
+    /// ```
+    /// # fn sort(instances: &mut Vec<mssql_browser::InstanceInfo>) {
+    /// instances.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    /// # }
+    /// ```
+    pub fn sort_key(&self) -> (IpAddr, &str) {
+        (self.addr, &self.instance_name)
+    }
+
+    /// Gets the NetBIOS name of the machine the instance runs on, as sent in the SERVERNAME field.
+    pub fn netbios_name(&self) -> &str {
+        &self.server_name
+    }
+
+    /// Gets a canonical identity of the machine the instance runs on, suitable for
+    /// keying an inventory on hosts rather than addresses. This is the NetBIOS name in
+    /// upper case, or if the server sent an empty one, the computer name of the RPC endpoint
+    /// or the machine name of the VIA endpoint. A fully qualified name is reduced to its host part.
+    ///
+    /// Returns `None` if no name is available, or if the names sent by the server disagree.
+    pub fn host_identity(&self) -> Option<String> {
+        let names = std::iter::once(self.server_name.as_str())
+            .chain(self.rpc_info.as_ref().map(|x| x.computer_name.as_str()))
+            .chain(self.via_info.as_ref().map(|x| x.machine_name.as_str()))
+            .map(|name| name.split('.').next().unwrap_or_default())
+            .filter(|name| !name.is_empty());
+
+        let mut identity: Option<&str> = None;
+        for name in names {
+            match identity {
+                Some(identity) if !identity.eq_ignore_ascii_case(name) => return None,
+                Some(_) => {}
+                None => identity = Some(name),
+            }
+        }
+
+        identity.map(str::to_ascii_uppercase)
+    }
+
+    /// Gets the identifiers of the protocols the instance has an endpoint for, e.g. `"tcp"` or
+    /// `"np"`, in the order they're defined in by the protocol. Unknown endpoints and the DAC
+    /// endpoint aren't included.
+    pub fn protocols(&self) -> Vec<&'static str> {
+        let present = [
+            self.np_info.is_some(),
+            self.tcp_info.is_some(),
+            self.via_info.is_some(),
+            self.rpc_info.is_some(),
+            self.spx_info.is_some(),
+            self.adsp_info.is_some(),
+            self.bv_info.is_some(),
+        ];
+
+        ENDPOINT_IDENTIFIERS
+            .iter()
+            .zip(present.iter())
+            .filter(|(_, present)| **present)
+            .map(|(protocol, _)| *protocol)
+            .collect()
+    }
+
+    /// Gets the number of protocols the instance has an endpoint for, see `protocols`.
+    pub fn endpoint_count(&self) -> usize {
+        self.protocols().len()
+    }
+
+    /// Gets the size, in bytes, of the whole response datagram this instance was described in,
+    /// as received and including the header. Compare it with the length sent in the header to
+    /// tell truncation apart from a bad length in a packet capture, see `header`.
+    /// `None` if the information wasn't parsed from an SVR_RESP datagram.
+    pub fn bytes_received(&self) -> Option<usize> {
+        self.header.map(|header| header.datagram_length)
+    }
+
+    /// Gets a compact single line description of the instance for logging, listing at most
+    /// a few endpoints and truncating long values.
+    ///
+    /// ```
+    /// use std::net::{IpAddr, Ipv4Addr};
+    ///
+    /// let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    /// let body = b"ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;np;\\\\SRV1\\pipe\\sql\\query;;";
+    /// let mut datagram = vec![0x05, body.len() as u8, 0x00];
+    /// datagram.extend_from_slice(body);
+    ///
+    /// let instance = mssql_browser::parse_responses(vec![(addr, &datagram[..])])
+    ///     .next()
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     instance.summary().to_string(),
+    ///     r"SRV1\SQLEXPRESS at 10.0.0.1 version 15.0.2000.5: tcp 1433, np \\SRV1\pipe\sql\query"
+    /// );
+    /// assert_eq!(
+    ///     instance.summary().max_endpoints(1).to_string(),
+    ///     r"SRV1\SQLEXPRESS at 10.0.0.1 version 15.0.2000.5: tcp 1433 (+1 more)"
+    /// );
+    /// ```
+    pub fn summary(&self) -> InstanceSummary<'_> {
+        InstanceSummary {
+            info: self,
+            max_endpoints: DEFAULT_SUMMARY_ENDPOINTS,
+        }
+    }
+
+    /// Gets the endpoint to connect to the instance with, preferring TCP over named pipes
+    /// as it works across networks and doesn't require Windows authentication to reach the
+    /// pipe. Returns `None` if the instance offers neither.
+    pub fn preferred_endpoint(&self) -> Option<Endpoint> {
+        self.endpoint(EndpointPreference::Tcp)
+    }
+
+    /// Gets the endpoint to connect to the instance with, using the transport given by
+    /// `preference` if the instance offers both. Returns `None` if the instance offers neither.
+    pub fn endpoint(&self, preference: EndpointPreference) -> Option<Endpoint> {
+        let tcp = self
+            .tcp_info
+            .as_ref()
+            .map(|tcp| Endpoint::Tcp(SocketAddr::new(self.addr, tcp.port)));
+        let np = self
+            .np_info
+            .as_ref()
+            .map(|np| Endpoint::NamedPipe(np.name.clone()));
+
+        match preference {
+            EndpointPreference::Tcp => tcp.or(np),
+            EndpointPreference::NamedPipe => np.or(tcp),
+        }
+    }
+
+    /// Serializes the instance into an SVR_RESP datagram describing it, the inverse of parsing
+    /// a response. Useful for implementing a fake browser service. Only the endpoints that are
+    /// present are written, followed by any `unknown` pairs, which are only parsed back when not
+    /// parsing strictly. Fields that aren't sent over the wire, such as `rtt`, are ignored.
+    ///
+    /// # Panics
+    /// Panics if the description is longer than the 65535 bytes that fit in the length header.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::net::{IpAddr, Ipv4Addr};
+    ///
+    /// let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    /// let body = b"ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;";
+    /// let mut datagram = vec![0x05, body.len() as u8, 0x00];
+    /// datagram.extend_from_slice(body);
+    ///
+    /// let instance = mssql_browser::parse_responses(vec![(addr, &datagram[..])])
+    ///     .next()
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(instance.to_ssrp_bytes(), datagram);
+    /// ```
+    pub fn to_ssrp_bytes(&self) -> Vec<u8> {
+        let mut body = format!(
+            "ServerName;{};InstanceName;{};IsClustered;{};Version;{};",
+            self.server_name,
+            self.instance_name,
+            if self.is_clustered { "Yes" } else { "No" },
+            self.version
+        );
+
+        if let Some(np) = &self.np_info {
+            body += &format!("np;{};", np.name);
+        }
+        if let Some(tcp) = &self.tcp_info {
+            body += &format!("tcp;{};", tcp.port);
+        }
+        if let Some(via) = &self.via_info {
+            body += &format!("via;{}", via.machine_name);
+            for address in &via.addresses {
+                body += &format!(",{}:{}", address.nic, address.port);
+            }
+            body += ";";
+        }
+        if let Some(rpc) = &self.rpc_info {
+            body += &format!("rpc;{};", rpc.computer_name);
+        }
+        if let Some(spx) = &self.spx_info {
+            body += &format!("spx;{};", spx.service_name);
+        }
+        if let Some(adsp) = &self.adsp_info {
+            body += &format!("adsp;{};", adsp.object_name);
+        }
+        if let Some(bv) = &self.bv_info {
+            body += &format!("bv;{};{};{};", bv.item_name, bv.group_name, bv.org_name);
+        }
+        for (key, value) in &self.unknown {
+            body += &format!("{};{};", key, value);
+        }
+        body += ";";
+
+        let length = u16::try_from(body.len()).expect("instance description exceeds 65535 bytes");
+        let mut datagram = Vec::with_capacity(3 + body.len());
+        datagram.push(SVR_RESP);
+        datagram.extend_from_slice(&length.to_le_bytes());
+        datagram.extend_from_slice(body.as_bytes());
+        datagram
+    }
+}
+
+/// The number of endpoints listed by an `InstanceSummary` unless overridden via `max_endpoints`.
+const DEFAULT_SUMMARY_ENDPOINTS: usize = 4;
+
+/// The number of characters of a value shown by an `InstanceSummary`, longer ones are truncated.
+const MAX_SUMMARY_VALUE_LEN: usize = 64;
+
+/// A compact single line description of an `InstanceInfo` of bounded length,
+/// for logging where the output of `Debug` is too large. Created via `InstanceInfo::summary`.
+pub struct InstanceSummary<'a> {
+    info: &'a InstanceInfo,
+    max_endpoints: usize,
+}
+
+impl<'a> InstanceSummary<'a> {
+    /// Sets the maximum number of endpoints listed, further ones are only counted.
+    /// Defaults to 4.
+    pub fn max_endpoints(mut self, max: usize) -> InstanceSummary<'a> {
+        self.max_endpoints = max;
+        self
+    }
+
+    /// Gets the identifier and value of each endpoint of the instance, including unknown ones.
+    fn endpoints(&self) -> Vec<(&'a str, String)> {
+        let info = self.info;
+        let mut endpoints = Vec::new();
+
+        if let Some(tcp) = &info.tcp_info {
+            endpoints.push(("tcp", tcp.port.to_string()));
+        }
+        if let Some(np) = &info.np_info {
+            endpoints.push(("np", np.name.clone()));
+        }
+        if let Some(via) = &info.via_info {
+            for address in via.addresses.iter() {
+                endpoints.push(("via", format!("{}:{}", address.nic, address.port)));
+            }
+        }
+        if let Some(rpc) = &info.rpc_info {
+            endpoints.push(("rpc", rpc.computer_name.clone()));
+        }
+        if let Some(spx) = &info.spx_info {
+            endpoints.push(("spx", spx.service_name.clone()));
+        }
+        if let Some(adsp) = &info.adsp_info {
+            endpoints.push(("adsp", adsp.object_name.clone()));
+        }
+        if let Some(bv) = &info.bv_info {
+            endpoints.push(("bv", bv.item_name.clone()));
+        }
+        for (key, value) in info.unknown.iter() {
+            endpoints.push((key.as_str(), value.clone()));
+        }
+
+        endpoints
+    }
+}
+
+/// Writes `value`, truncated to `MAX_SUMMARY_VALUE_LEN` characters.
+fn write_truncated(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    match value.char_indices().nth(MAX_SUMMARY_VALUE_LEN) {
+        Some((end, _)) => write!(f, "{}...", &value[..end]),
+        None => write!(f, "{}", value),
+    }
+}
+
+impl<'a> std::fmt::Display for InstanceSummary<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_truncated(f, &self.info.server_name)?;
+        write!(f, "\\")?;
+        write_truncated(f, &self.info.instance_name)?;
+        write!(f, " at {} version ", self.info.addr)?;
+        write_truncated(f, &self.info.version)?;
+
+        let endpoints = self.endpoints();
+        for (i, (key, value)) in endpoints.iter().take(self.max_endpoints).enumerate() {
+            write!(f, "{}", if i == 0 { ": " } else { ", " })?;
+            write_truncated(f, key)?;
+            write!(f, " ")?;
+            write_truncated(f, value)?;
+        }
+
+        if endpoints.len() > self.max_endpoints {
+            write!(f, " (+{} more)", endpoints.len() - self.max_endpoints)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A transport over which an instance can be connected to, see `InstanceInfo::endpoint`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// The TCP address the instance listens on.
+    Tcp(SocketAddr),
+
+    /// The name of the pipe the instance listens on, e.g. `\\SRV1\pipe\sql\query`.
+    NamedPipe(String),
+}
+
+/// The transport `InstanceInfo::endpoint` picks when an instance offers more than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointPreference {
+    /// Prefer TCP, the policy of `InstanceInfo::preferred_endpoint`.
+    Tcp,
+
+    /// Prefer named pipes.
+    NamedPipe,
+}
+
+/// The way in which the request that led to an `InstanceInfo` was addressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiscoveryMethod {
+    /// Sent to an IPv4 broadcast address by `browse` or `browse_subnet`.
+    Broadcast,
+
+    /// Sent to a multicast address by `browse`.
+    Multicast,
+
+    /// Sent to a single host, e.g. by `browse_host` or `browse_instance`.
+    Unicast,
+}
+
+impl DiscoveryMethod {
+    /// Determines how a browse request sent to `addr` is addressed.
+    pub(crate) fn of(addr: IpAddr, broadcast: bool) -> DiscoveryMethod {
+        if addr.is_multicast() {
+            DiscoveryMethod::Multicast
+        } else if broadcast && addr.is_ipv4() {
+            DiscoveryMethod::Broadcast
+        } else {
+            DiscoveryMethod::Unicast
+        }
+    }
 }
 
 /// Information about the named pipe endpoint
@@ -55,6 +426,16 @@ pub struct ViaInfo {
     pub addresses: Vec<ViaAddress>,
 }
 
+impl ViaInfo {
+    /// Iterates over the NIC identifier and port of each address.
+    /// Addresses of which the port isn't a valid decimal number are skipped.
+    pub fn endpoints(&self) -> impl Iterator<Item = (&str, u16)> + '_ {
+        self.addresses
+            .iter()
+            .filter_map(|address| Some((address.nic.as_str(), address.port.parse().ok()?)))
+    }
+}
+
 /// A combination of NIC name and port.
 #[derive(Debug)]
 pub struct ViaAddress {
@@ -108,21 +489,237 @@ pub struct DacInfo {
     pub port: u16,
 }
 
+/// The header of an SVR_RESP datagram, mostly useful for diagnosing malformed responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseHeader {
+    /// The message identifier, always SVR_RESP (0x05) for a valid response.
+    pub kind: u8,
+
+    /// The length of the data following the header, as sent by the server.
+    pub length: u16,
+
+    /// The size, in bytes, of the whole datagram including the header, as received.
+    pub datagram_length: usize,
+}
+
+impl ResponseHeader {
+    /// Determines whether the length sent by the server matches the size of the datagram.
+    /// Responses where it doesn't are only accepted with `BrowserOptions::lenient_length`.
+    pub fn length_matches(&self) -> bool {
+        self.length as usize + 3 == self.datagram_length
+    }
+
+    /// Determines whether the datagram is shorter than announced by the server,
+    /// see `BrowserProtocolError::Truncated`.
+    pub fn is_truncated(&self) -> bool {
+        self.datagram_length < self.length as usize + 3
+    }
+}
+
+/// The server responds to all client requests with an SVR_RESP.
+const SVR_RESP: u8 = 0x05;
+
+/// Parses the value of a VIA endpoint, the NetBIOS name of the machine
+/// followed by a comma separated list of `nic:port` pairs.
+fn parse_via_parameters(parameters: &str) -> Result<ViaInfo, BrowserProtocolError> {
+    let missing = |field, found: &str| BrowserProtocolError::UnexpectedToken {
+        expected: BrowserProtocolToken::ValueOf(field),
+        found: BrowserProtocolToken::Literal(found.to_string()),
+    };
+
+    let mut parts = parameters.split(',');
+    let machine_name = parts.next().unwrap_or_default();
+    if machine_name.is_empty() {
+        return Err(missing(BrowserProtocolField::ViaMachineName, parameters));
+    }
+
+    let mut addresses = Vec::new();
+    for address in parts {
+        let (nic, port) = match address.find(':') {
+            Some(idx) => (&address[..idx], &address[(idx + 1)..]),
+            None => return Err(missing(BrowserProtocolField::ViaPort, address)),
+        };
+
+        if nic.is_empty() {
+            return Err(missing(BrowserProtocolField::ViaNic, address));
+        }
+        if port.is_empty() {
+            return Err(missing(BrowserProtocolField::ViaPort, address));
+        }
+
+        addresses.push(ViaAddress {
+            nic: nic.to_owned(),
+            port: port.to_owned(),
+        });
+    }
+
+    if addresses.is_empty() {
+        return Err(missing(BrowserProtocolField::ViaNic, parameters));
+    }
+
+    Ok(ViaInfo {
+        machine_name: machine_name.to_owned(),
+        addresses,
+    })
+}
+
+/// Validates the header of an SVR_RESP datagram and returns it along with the body.
+/// If `lenient` is set, a length that doesn't match the size of the datagram is accepted,
+/// and the body is taken to be the rest of the datagram.
+pub(crate) fn parse_response_header(
+    buffer: &[u8],
+    lenient: bool,
+) -> Result<(ResponseHeader, &[u8]), BrowserProtocolError> {
+    if buffer.is_empty() {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
+            found: BrowserProtocolToken::EndOfMessage,
+        });
+    }
+
+    if buffer[0] != SVR_RESP {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::MessageIdentifier(SVR_RESP),
+            found: BrowserProtocolToken::MessageIdentifier(buffer[0]),
+        });
+    }
+
+    if buffer.len() < 3 {
+        return Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::MessageLength,
+            found: BrowserProtocolToken::EndOfMessage,
+        });
+    }
+
+    let header = ResponseHeader {
+        kind: buffer[0],
+        length: u16::from_le_bytes([buffer[1], buffer[2]]),
+        datagram_length: buffer.len(),
+    };
+    if !lenient && header.is_truncated() {
+        return Err(BrowserProtocolError::Truncated {
+            datagram: buffer.len(),
+            header: header.length as usize + 3,
+        });
+    }
+    if !lenient && !header.length_matches() {
+        return Err(BrowserProtocolError::LengthMismatch {
+            datagram: buffer.len(),
+            header: header.length as usize + 3,
+        });
+    }
+
+    Ok((header, &buffer[3..]))
+}
+
+/// Parses the instances described in SVR_RESP datagrams that weren't received by this crate,
+/// e.g. ones extracted from a packet capture. Each datagram is paired with the address of the
+/// host that sent it, and must start with the SVR_RESP header. A datagram that can't be parsed
+/// yields a single error, after which parsing continues with the next datagram.
+///
+/// # Examples
+/// ```
+/// use std::net::{IpAddr, Ipv4Addr};
+///
+/// let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+/// let body = b"ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;";
+/// let mut datagram = vec![0x05, body.len() as u8, 0x00];
+/// datagram.extend_from_slice(body);
+///
+/// let instances = mssql_browser::parse_responses(vec![(addr, &datagram[..])])
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// assert_eq!(instances[0].instance_name, "SQLEXPRESS");
+/// ```
+pub fn parse_responses<'a, I>(
+    datagrams: I,
+) -> impl Iterator<Item = Result<InstanceInfo, BrowserProtocolError>> + 'a
+where
+    I: IntoIterator<Item = (IpAddr, &'a [u8])>,
+    I::IntoIter: 'a,
+{
+    datagrams
+        .into_iter()
+        .flat_map(|(addr, datagram)| ResponseInstances::new(addr, datagram))
+}
+
+/// Iterates over the instances described in a single SVR_RESP datagram.
+struct ResponseInstances<'a> {
+    addr: IpAddr,
+    header: Option<ResponseHeader>,
+    body: &'a str,
+    error: Option<BrowserProtocolError>,
+}
+
+impl<'a> ResponseInstances<'a> {
+    fn new(addr: IpAddr, datagram: &'a [u8]) -> ResponseInstances<'a> {
+        // TODO: Decode mbcs string
+        let body = parse_response_header(datagram, false).and_then(|(header, body)| {
+            let body = std::str::from_utf8(body).map_err(BrowserProtocolError::InvalidUtf8)?;
+            Ok((header, body))
+        });
+
+        match body {
+            Ok((header, body)) => ResponseInstances {
+                addr,
+                header: Some(header),
+                body,
+                error: None,
+            },
+            Err(e) => ResponseInstances {
+                addr,
+                header: None,
+                body: "",
+                error: Some(e),
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for ResponseInstances<'a> {
+    type Item = Result<InstanceInfo, BrowserProtocolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+
+        if self.body.is_empty() {
+            return None;
+        }
+
+        match parse_instance_info(self.addr, self.body, false) {
+            Ok((mut instance, consumed)) => {
+                self.body = &self.body[consumed..];
+                instance.header = self.header;
+                Some(Ok(instance))
+            }
+            Err(e) => {
+                self.body = "";
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 struct SplitIteratorWithPosition<'a> {
     inner: std::str::Split<'a, char>,
     position: usize,
+    len: usize,
 }
 
 impl<'a> SplitIteratorWithPosition<'a> {
-    fn new(inner: std::str::Split<'a, char>) -> SplitIteratorWithPosition<'a> {
+    fn new(string: &'a str, separator: char) -> SplitIteratorWithPosition<'a> {
         SplitIteratorWithPosition {
-            inner: inner,
+            inner: string.split(separator),
             position: 0,
+            len: string.len(),
         }
     }
 
     fn string_position(&self) -> usize {
-        self.position
+        // The last part isn't followed by a separator, so don't count one
+        std::cmp::min(self.position, self.len)
     }
 }
 
@@ -140,9 +737,14 @@ impl<'a> Iterator for SplitIteratorWithPosition<'a> {
     }
 }
 
+/// Parses the information of a single instance from the start of `string`, returning it together
+/// with the number of bytes consumed. Unless `strict` is set, the instance information may appear
+/// in any order, interleaved with the endpoints, and unknown identifiers anywhere in the response
+/// are collected into `InstanceInfo::unknown` rather than failing.
 pub(crate) fn parse_instance_info(
     addr: IpAddr,
     string: &str,
+    strict: bool,
 ) -> Result<(InstanceInfo, usize), BrowserProtocolError> {
     #[inline]
     fn expect_next<'a, T: Iterator<Item = &'a str>>(
@@ -180,39 +782,73 @@ pub(crate) fn parse_instance_info(
             })
     }
 
-    let mut iterator = SplitIteratorWithPosition::new(string.split(';'));
-
-    // Instance information
-    expect_next(
-        &mut iterator,
-        "ServerName",
-        BrowserProtocolField::ServerName,
-    )?;
-    let server_name = consume_next(&mut iterator, BrowserProtocolField::ServerName)?;
-    expect_next(
-        &mut iterator,
-        "InstanceName",
-        BrowserProtocolField::InstanceName,
-    )?;
-    let instance_name = consume_next(&mut iterator, BrowserProtocolField::InstanceName)?;
-    expect_next(
-        &mut iterator,
-        "IsClustered",
-        BrowserProtocolField::IsClustered,
-    )?;
-    let is_clustered_str = consume_next(&mut iterator, BrowserProtocolField::IsClustered)?;
-    let is_clustered = match is_clustered_str {
-        "Yes" => true,
-        "No" => false,
-        v => {
+    fn require(
+        value: Option<&str>,
+        field: BrowserProtocolField,
+    ) -> Result<&str, BrowserProtocolError> {
+        value.ok_or(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::Identifier(field),
+            found: BrowserProtocolToken::EndOfMessage,
+        })
+    }
+
+    // A repeated instance field means the terminator of the record was lost,
+    // so the fields belong to the next instance and must not overwrite these
+    fn consume_once<'a, T: Iterator<Item = &'a str>>(
+        iterator: &mut T,
+        value: &mut Option<&'a str>,
+        identifier: &str,
+        field: BrowserProtocolField,
+    ) -> Result<(), BrowserProtocolError> {
+        if value.is_some() {
             return Err(BrowserProtocolError::UnexpectedToken {
-                expected: BrowserProtocolToken::ValueOf(BrowserProtocolField::IsClustered),
-                found: BrowserProtocolToken::Literal(v.to_string()),
-            })
+                expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
+                found: BrowserProtocolToken::Literal(identifier.to_string()),
+            });
         }
-    };
-    expect_next(&mut iterator, "Version", BrowserProtocolField::Version)?;
-    let version = consume_next(&mut iterator, BrowserProtocolField::Version)?;
+
+        *value = Some(consume_next(iterator, field)?);
+        Ok(())
+    }
+
+    let mut iterator = SplitIteratorWithPosition::new(string, ';');
+
+    // Instance information, which is only required to come first when parsing strictly
+    let mut server_name = None;
+    let mut instance_name = None;
+    let mut is_clustered_str = None;
+    let mut version = None;
+    if strict {
+        expect_next(
+            &mut iterator,
+            "ServerName",
+            BrowserProtocolField::ServerName,
+        )?;
+        server_name = Some(consume_next(
+            &mut iterator,
+            BrowserProtocolField::ServerName,
+        )?);
+        expect_next(
+            &mut iterator,
+            "InstanceName",
+            BrowserProtocolField::InstanceName,
+        )?;
+        instance_name = Some(consume_next(
+            &mut iterator,
+            BrowserProtocolField::InstanceName,
+        )?);
+        expect_next(
+            &mut iterator,
+            "IsClustered",
+            BrowserProtocolField::IsClustered,
+        )?;
+        is_clustered_str = Some(consume_next(
+            &mut iterator,
+            BrowserProtocolField::IsClustered,
+        )?);
+        expect_next(&mut iterator, "Version", BrowserProtocolField::Version)?;
+        version = Some(consume_next(&mut iterator, BrowserProtocolField::Version)?);
+    }
 
     // Supported protocols
     let mut np_info: Option<NamedPipeInfo> = None;
@@ -222,9 +858,42 @@ pub(crate) fn parse_instance_info(
     let mut spx_info: Option<SpxInfo> = None;
     let mut adsp_info: Option<AdspInfo> = None;
     let mut bv_info: Option<BvInfo> = None;
+    let mut unknown = Vec::new();
 
     loop {
         match iterator.next() {
+            Some(x @ "ServerName") if !strict => {
+                consume_once(
+                    &mut iterator,
+                    &mut server_name,
+                    x,
+                    BrowserProtocolField::ServerName,
+                )?;
+            }
+            Some(x @ "InstanceName") if !strict => {
+                consume_once(
+                    &mut iterator,
+                    &mut instance_name,
+                    x,
+                    BrowserProtocolField::InstanceName,
+                )?;
+            }
+            Some(x @ "IsClustered") if !strict => {
+                consume_once(
+                    &mut iterator,
+                    &mut is_clustered_str,
+                    x,
+                    BrowserProtocolField::IsClustered,
+                )?;
+            }
+            Some(x @ "Version") if !strict => {
+                consume_once(
+                    &mut iterator,
+                    &mut version,
+                    x,
+                    BrowserProtocolField::Version,
+                )?;
+            }
             Some("np") => {
                 let pipe_name = consume_next(&mut iterator, BrowserProtocolField::NamedPipeName)?;
                 np_info = Some(NamedPipeInfo {
@@ -244,32 +913,7 @@ pub(crate) fn parse_instance_info(
             }
             Some("via") => {
                 let parameters = consume_next(&mut iterator, BrowserProtocolField::ViaMachineName)?;
-                let comma_idx =
-                    parameters
-                        .find(',')
-                        .ok_or_else(|| BrowserProtocolError::UnexpectedToken {
-                            expected: BrowserProtocolToken::ViaParameters,
-                            found: BrowserProtocolToken::Literal(parameters.to_string()),
-                        })?;
-                let machine_name = &parameters[0..comma_idx];
-                let mut nic_port_parts = (&parameters[(comma_idx + 1)..]).split(&[',', ':'][..]);
-                let mut addresses = Vec::new();
-                while let Some(nic) = nic_port_parts.next() {
-                    let port = nic_port_parts.next().ok_or_else(|| {
-                        BrowserProtocolError::UnexpectedToken {
-                            expected: BrowserProtocolToken::ViaParameters,
-                            found: BrowserProtocolToken::Literal(parameters.to_string()),
-                        }
-                    })?;
-                    addresses.push(ViaAddress {
-                        nic: nic.to_owned(),
-                        port: port.to_owned(),
-                    });
-                }
-                via_info = Some(ViaInfo {
-                    machine_name: machine_name.to_owned(),
-                    addresses,
-                });
+                via_info = Some(parse_via_parameters(parameters)?);
             }
             Some("rpc") => {
                 let computer_name =
@@ -303,6 +947,23 @@ pub(crate) fn parse_instance_info(
                 });
             }
             Some("") => break,
+            Some(x) if !strict => {
+                let value = iterator
+                    .next()
+                    .ok_or(BrowserProtocolError::UnexpectedToken {
+                        expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
+                        found: BrowserProtocolToken::EndOfMessage,
+                    })?;
+
+                // An empty value is the terminator of the record, which the key can't consume
+                if value.is_empty() {
+                    return Err(BrowserProtocolError::UnexpectedToken {
+                        expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
+                        found: BrowserProtocolToken::Literal(x.to_string()),
+                    });
+                }
+                unknown.push((x.to_owned(), value.to_owned()));
+            }
             Some(x) => {
                 return Err(BrowserProtocolError::UnexpectedToken {
                     expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
@@ -320,6 +981,20 @@ pub(crate) fn parse_instance_info(
 
     let consumed = iterator.string_position();
 
+    let server_name = require(server_name, BrowserProtocolField::ServerName)?;
+    let instance_name = require(instance_name, BrowserProtocolField::InstanceName)?;
+    let is_clustered = match require(is_clustered_str, BrowserProtocolField::IsClustered)? {
+        "Yes" => true,
+        "No" => false,
+        v => {
+            return Err(BrowserProtocolError::UnexpectedToken {
+                expected: BrowserProtocolToken::ValueOf(BrowserProtocolField::IsClustered),
+                found: BrowserProtocolToken::Literal(v.to_string()),
+            })
+        }
+    };
+    let version = require(version, BrowserProtocolField::Version)?;
+
     Ok((
         InstanceInfo {
             addr,
@@ -334,6 +1009,12 @@ pub(crate) fn parse_instance_info(
             spx_info,
             adsp_info,
             bv_info,
+            unknown,
+            dac_info: None,
+            rtt: None,
+            source: None,
+            discovery_method: DiscoveryMethod::Unicast,
+            header: None,
         },
         consumed,
     ))
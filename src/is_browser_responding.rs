@@ -0,0 +1,77 @@
+use super::error::*;
+use super::info::*;
+use super::options::BrowserOptions;
+use super::socket::{bind_socket, connect_deadline, recv_deadline, UdpSocket, UdpSocketFactory};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// The CLNT_UCAST_EX packet is a unicast request that is generated by clients that are trying to identify
+/// the list of database instances and their network protocol connection information installed on a single machine.
+const CLNT_UCAST_EX: u8 = 0x03;
+
+/// Checks whether a browser service is answering on the given host, without parsing
+/// any instance information. Returns `Ok(false)` if the host refused the request or
+/// no well-formed response was received within the timeout.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host to check.
+/// * `timeout` - How long to wait for a response.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn is_browser_responding(
+    remote_addr: IpAddr,
+    timeout: Duration,
+) -> Result<
+    bool,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    is_browser_responding_inner(remote_addr, timeout, &mut factory).await
+}
+
+/// Checks whether a browser service is answering on the given host, without parsing
+/// any instance information. Returns `Ok(false)` if the host refused the request or
+/// no well-formed response was received within the timeout.
+///
+/// # Arguments
+/// * `remote_addr` - The address of the remote host to check.
+/// * `timeout` - How long to wait for a response.
+pub async fn is_browser_responding_inner<SF: UdpSocketFactory>(
+    remote_addr: IpAddr,
+    timeout: Duration,
+    socket_factory: &mut SF,
+) -> Result<bool, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let options = BrowserOptions::default();
+    let mut socket = bind_socket(socket_factory, &remote_addr, &options, false)
+        .await
+        .map_err(BrowserError::BindFailed)?;
+
+    let remote = SocketAddr::new(remote_addr, 1434);
+    let deadline = options.now() + timeout;
+    connect_deadline(&mut socket, &remote, Some(deadline), &*options.clock).await?;
+
+    let buffer = [CLNT_UCAST_EX];
+    socket
+        .send_to(&buffer, &remote)
+        .await
+        .map_err(|e| BrowserError::SendFailed(remote, e))?;
+
+    let mut buffer = vec![0u8; 65535 + 3];
+
+    loop {
+        match recv_deadline(&mut socket, &mut buffer, Some(deadline), &*options.clock).await {
+            Ok(Some(bytes_received)) => {
+                // Any SVR_RESP shows the browser is running, even one with a wrong length
+                if parse_response_header(&buffer[..bytes_received], true).is_ok() {
+                    return Ok(true);
+                }
+            }
+            Ok(None) => return Ok(false),
+            // The host is up, but nothing is listening on the browser port
+            Err(e) if <SF::Socket as UdpSocket>::is_connection_refused(&e) => return Ok(false),
+            Err(e) => return Err(BrowserError::ReceiveFailed(e)),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use super::info::*;
+use serde_json::{json, Value};
+
+impl InstanceInfo {
+    /// Converts the instance into a JSON object with a fixed shape, for tools that emit JSON
+    /// without depending on the layout of this struct. The endpoints are flattened into a list
+    /// of objects tagged with their `protocol`, in protocol order. Fields that weren't received
+    /// are `null`, and the round trip time is given in fractional milliseconds.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::net::{IpAddr, Ipv4Addr};
+    ///
+    /// let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    /// let body = b"ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;";
+    /// let mut datagram = vec![0x05, body.len() as u8, 0x00];
+    /// datagram.extend_from_slice(body);
+    ///
+    /// let instance = mssql_browser::parse_responses(vec![(addr, &datagram[..])])
+    ///     .next()
+    ///     .unwrap()
+    ///     .unwrap();
+    /// let json = instance.to_json();
+    /// assert_eq!(json["instance_name"], "SQLEXPRESS");
+    /// assert_eq!(json["endpoints"][0]["protocol"], "tcp");
+    /// assert_eq!(json["endpoints"][0]["port"], 1433);
+    /// ```
+    pub fn to_json(&self) -> Value {
+        json!({
+            "address": self.addr.to_string(),
+            "server_name": self.server_name,
+            "instance_name": self.instance_name,
+            "is_clustered": self.is_clustered,
+            "version": self.version,
+            "endpoints": self.endpoints_json(),
+            "unknown": self
+                .unknown
+                .iter()
+                .map(|(key, value)| json!({ "key": key, "value": value }))
+                .collect::<Vec<_>>(),
+            "dac_port": self.dac_info.as_ref().map(|dac| dac.port),
+            "rtt_ms": self.rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+            "source": self.source.map(|source| source.to_string()),
+        })
+    }
+
+    fn endpoints_json(&self) -> Vec<Value> {
+        let np = self
+            .np_info
+            .as_ref()
+            .map(|np| json!({ "protocol": "np", "pipe_name": np.name }));
+        let tcp = self
+            .tcp_info
+            .as_ref()
+            .map(|tcp| json!({ "protocol": "tcp", "port": tcp.port }));
+        let via = self.via_info.as_ref().map(|via| {
+            json!({
+                "protocol": "via",
+                "machine_name": via.machine_name,
+                "addresses": via
+                    .addresses
+                    .iter()
+                    .map(|address| json!({ "nic": address.nic, "port": address.port }))
+                    .collect::<Vec<_>>(),
+            })
+        });
+        let rpc = self
+            .rpc_info
+            .as_ref()
+            .map(|rpc| json!({ "protocol": "rpc", "computer_name": rpc.computer_name }));
+        let spx = self
+            .spx_info
+            .as_ref()
+            .map(|spx| json!({ "protocol": "spx", "service_name": spx.service_name }));
+        let adsp = self
+            .adsp_info
+            .as_ref()
+            .map(|adsp| json!({ "protocol": "adsp", "object_name": adsp.object_name }));
+        let bv = self.bv_info.as_ref().map(|bv| {
+            json!({
+                "protocol": "bv",
+                "item_name": bv.item_name,
+                "group_name": bv.group_name,
+                "org_name": bv.org_name,
+            })
+        });
+
+        vec![np, tcp, via, rpc, spx, adsp, bv]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
@@ -10,9 +10,16 @@
 //!
 //! ## Examples
 //! Below are a few different ways to get endpoint information of MSSQL server instances.
+//! All operations give up after 5 seconds (`DEFAULT_TIMEOUT`) without a response, use the `_with_options` variants to configure this.
+//! The functions used below require the `tokio` or `async-std` feature. Without either, the crate builds without any
+//! async runtime, leaving the parsing functions such as `parse_responses` and the `custom_socket` module, which runs
+//! the operations on the sockets of a custom `UdpSocketFactory`, or on blocking standard library sockets via
+//! `custom_socket::BlockingSocketFactory`.
 //!
 //! ### Discover endpoint information of instances within network
 //! ```rust
+//! # #[cfg(any(feature = "tokio", feature = "async-std"))]
+//! # mod example {
 //! use std::net::{ IpAddr, Ipv4Addr };
 //! use std::error::Error;
 //! use mssql_browser::{ browse, BrowserError };
@@ -21,16 +28,19 @@
 //!   let broadcast_addr = IpAddr::V4(Ipv4Addr::BROADCAST);
 //!   let mut iterator = browse(broadcast_addr).await?;
 //!   
-//!   while let instance = iterator.next().await? {
+//!   while let Some(instance) = iterator.next().await? {
 //!     println!("Found instance {} on host {}.", instance.instance_name, instance.addr);
 //!   }
 //!   
 //!   Ok(())
 //! }
+//! # }
 //! ```
 //!
 //! ### Discover endpoint information of instances on host
 //! ```rust
+//! # #[cfg(any(feature = "tokio", feature = "async-std"))]
+//! # mod example {
 //! use std::net::{ IpAddr, Ipv4Addr };
 //! use std::error::Error;
 //! use mssql_browser::{ browse_host, BrowserError };
@@ -45,32 +55,36 @@
 //!   
 //!   Ok(())
 //! }
+//! # }
 //! ```
 //!
 //! ### Discover endpoint information of specific instance
 //! ```rust
+//! # #[cfg(any(feature = "tokio", feature = "async-std"))]
+//! # mod example {
 //! use std::net::{ IpAddr, Ipv4Addr };
 //! use std::error::Error;
-//! use mssql_browser::{ browse_instance, BrowserError };
+//! use mssql_browser::{ browse_instance, BrowserError, Endpoint };
 //!
 //! async fn run() -> Result<(), Box<dyn Error>> {
 //!   let host_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 //!   let instance = browse_instance(host_addr, "MSSQLSERVER").await?;
 //!   
-//!   if let Some(tcp) = instance.tcp_info {
-//!     println!("Instance is available via TCP on port {}", tcp.port);
-//!   }
-//!  
-//!   if let Some(np) = instance.np_info {
-//!     println!("Instance is available via named pipe {}", np.name);
+//!   match instance.preferred_endpoint() {
+//!     Some(Endpoint::Tcp(addr)) => println!("Instance is available via TCP on {}", addr),
+//!     Some(Endpoint::NamedPipe(name)) => println!("Instance is available via named pipe {}", name),
+//!     None => println!("Instance can't be connected to"),
 //!   }
 //!  
 //!   Ok(())
 //! }
+//! # }
 //! ```
 //!
 //! ### Discover DAC endpoint information
 //! ```rust
+//! # #[cfg(any(feature = "tokio", feature = "async-std"))]
+//! # mod example {
 //! use std::net::{ IpAddr, Ipv4Addr };
 //! use std::error::Error;
 //! use mssql_browser::{ browse_instance_dac, BrowserError };
@@ -83,39 +97,136 @@
 //!  
 //!   Ok(())
 //! }
+//! # }
 //! ```
 
+mod broadcast;
+mod clock;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod connect;
 mod error;
+mod in_flight;
 mod info;
+#[cfg(feature = "json")]
+mod json;
+mod observer;
+mod options;
+mod registry;
+mod retry;
 mod socket;
+mod stats;
+mod version;
 
 mod browse;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod browse_channel;
+#[cfg(feature = "ipnet")]
+mod browse_cidr;
+mod browse_for_each;
 mod browse_host;
+mod browse_host_by_name;
+mod browse_host_stream;
+mod browse_host_with_dac;
 mod browse_instance;
 mod browse_instance_dac;
+mod browse_instances;
+mod browse_multicast;
+mod browse_subnet;
+mod browse_tcp_endpoints;
+mod browser_dac_version;
+mod is_browser_responding;
+mod session;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
-/// Maximum length of an instance name
-pub const MAX_INSTANCE_NAME_LEN: usize = 32;
+/// Maximum length of an instance name in bytes, the limit MC-SQLR places on the INSTANCENAME field.
+/// SQL Server setup only allows names of up to 16 characters, but the protocol permits longer ones.
+pub const MAX_INSTANCE_NAME_LEN: usize = 255;
 
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use connect::TcpStream;
+pub use broadcast::*;
+pub use clock::{Clock, ManualClock, SystemClock};
 pub use error::*;
 pub use info::*;
+pub use observer::*;
+pub use options::*;
+pub use registry::{InstanceRegistry, RegisteredInstance};
+pub use retry::RetryPolicy;
+pub use stats::*;
+pub use version::*;
 
 #[cfg(any(feature = "tokio", feature = "async-std"))]
-pub use browse::browse;
+pub use browse::{browse, browse_with_options};
 pub use browse::AsyncInstanceIterator;
 #[cfg(any(feature = "tokio", feature = "async-std"))]
-pub use browse_host::browse_host;
-pub use browse_host::InstanceIterator;
+pub use browse_channel::{browse_to_channel, JoinHandle};
+#[cfg(all(feature = "ipnet", any(feature = "tokio", feature = "async-std")))]
+pub use browse_cidr::browse_cidr;
+#[cfg(feature = "ipnet")]
+pub use browse_cidr::HostScanResult;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_for_each::browse_for_each;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_host::{browse_host, browse_host_with_options};
+pub use browse_host::{InstanceIntoIter, InstanceIterator};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_host_by_name::{browse_host_by_name, browse_host_by_name_with_options};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_host_stream::{browse_host_stream, browse_host_stream_with_options};
+pub use browse_host_stream::HostInstanceStream;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_host_with_dac::{browse_host_with_dac, browse_host_with_dac_with_options};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_instance::{browse_instance, browse_instance_with_options};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_instance_dac::{browse_instance_dac, browse_instance_dac_with_options};
+pub use browse_instance_dac::{parse_dac_response, DEFAULT_DAC_VERSION};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_instances::{browse_instances, browse_instances_with_options};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_multicast::{browse_multicast_v6, browse_multicast_v6_with_options};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_subnet::{browse_subnet, browse_subnet_with_options};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browse_tcp_endpoints::browse_tcp_endpoints;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use browser_dac_version::{browser_dac_version, browser_dac_version_with_options};
 #[cfg(any(feature = "tokio", feature = "async-std"))]
-pub use browse_instance::browse_instance;
+pub use is_browser_responding::is_browser_responding;
 #[cfg(any(feature = "tokio", feature = "async-std"))]
-pub use browse_instance_dac::browse_instance_dac;
+pub use session::browser_session;
+pub use session::BrowserSession;
 
 /// Types and functions related to using a custom socket implementation
 pub mod custom_socket {
     pub use super::browse::browse_inner as browse;
+    pub use super::browse::browse_with_options_inner as browse_with_options;
+    #[cfg(feature = "ipnet")]
+    pub use super::browse_cidr::browse_cidr_inner as browse_cidr;
+    pub use super::browse_for_each::browse_for_each_inner as browse_for_each;
     pub use super::browse_host::browse_host_inner as browse_host;
+    pub use super::browse_host::browse_host_with_options_inner as browse_host_with_options;
+    pub use super::browse_host_by_name::browse_host_by_name_inner as browse_host_by_name;
+    pub use super::browse_host_by_name::browse_host_by_name_with_options_inner as browse_host_by_name_with_options;
+    pub use super::browse_host_stream::browse_host_stream_inner as browse_host_stream;
+    pub use super::browse_host_stream::browse_host_stream_with_options_inner as browse_host_stream_with_options;
+    pub use super::browse_host_with_dac::browse_host_with_dac_inner as browse_host_with_dac;
+    pub use super::browse_host_with_dac::browse_host_with_dac_with_options_inner as browse_host_with_dac_with_options;
     pub use super::browse_instance::browse_instance_inner as browse_instance;
+    pub use super::browse_instance::browse_instance_with_options_inner as browse_instance_with_options;
     pub use super::browse_instance_dac::browse_instance_dac_inner as browse_instance_dac;
+    pub use super::browse_instance_dac::browse_instance_dac_with_options_inner as browse_instance_dac_with_options;
+    pub use super::browse_instances::browse_instances_inner as browse_instances;
+    pub use super::browse_instances::browse_instances_with_options_inner as browse_instances_with_options;
+    pub use super::browse_multicast::browse_multicast_v6_inner as browse_multicast_v6;
+    pub use super::browse_multicast::browse_multicast_v6_with_options_inner as browse_multicast_v6_with_options;
+    pub use super::browse_subnet::browse_subnet_inner as browse_subnet;
+    pub use super::browse_subnet::browse_subnet_with_options_inner as browse_subnet_with_options;
+    pub use super::browse_tcp_endpoints::browse_tcp_endpoints_inner as browse_tcp_endpoints;
+    pub use super::browser_dac_version::browser_dac_version_inner as browser_dac_version;
+    pub use super::browser_dac_version::browser_dac_version_with_options_inner as browser_dac_version_with_options;
+    pub use super::is_browser_responding::is_browser_responding_inner as is_browser_responding;
+    pub use super::session::browser_session_inner as browser_session;
     pub use super::socket::*;
 }
@@ -0,0 +1,59 @@
+use std::net::SocketAddr;
+
+/// The direction in which an observed datagram travelled
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DatagramDirection {
+    /// The datagram was sent to the peer
+    Sent,
+
+    /// The datagram was received from the peer
+    Received,
+}
+
+/// A hook that gets notified of every datagram sent or received by the browser operations.
+/// Mainly useful for diagnosing interoperability issues, see `HexDump` for formatting the data.
+pub trait DatagramObserver: Send + Sync {
+    /// Called for every datagram sent to or received from `peer`.
+    fn observe(&self, direction: DatagramDirection, peer: &SocketAddr, data: &[u8]);
+}
+
+impl<F: Fn(DatagramDirection, &SocketAddr, &[u8]) + Send + Sync> DatagramObserver for F {
+    fn observe(&self, direction: DatagramDirection, peer: &SocketAddr, data: &[u8]) {
+        self(direction, peer, data)
+    }
+}
+
+/// Formats a byte slice as a classic hex dump, 16 bytes per line
+/// followed by their printable ASCII representation.
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl<'a> std::fmt::Display for HexDump<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (line, chunk) in self.0.chunks(16).enumerate() {
+            if line > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "{:08x} ", line * 16)?;
+            for i in 0..16 {
+                match chunk.get(i) {
+                    Some(b) => write!(f, " {:02x}", b)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+
+            write!(f, "  |")?;
+            for b in chunk {
+                let c = if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            write!(f, "|")?;
+        }
+
+        Ok(())
+    }
+}
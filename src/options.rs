@@ -0,0 +1,576 @@
+use super::broadcast::Ipv4Interface;
+use super::browse_instance_dac::DEFAULT_DAC_VERSION;
+use super::clock::{Clock, SystemClock};
+use super::error::BrowserProtocolError;
+use super::observer::{DatagramDirection, DatagramObserver};
+use super::retry::{jittered, RetryPolicy};
+use super::socket::SocketConfig;
+use super::stats::{BrowseOperation, BrowseStats, OperationStats};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The timeout applied to browser operations unless overridden via `BrowserOptions::timeout`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The delay between transmissions of the `browse` request unless overridden via `BrowserOptions::probe_interval`.
+pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The size of the largest possible SVR_RESP datagram, the 3 byte header followed by up to 65535 bytes.
+const MAX_RESPONSE_LEN: usize = 3 + 65535;
+
+/// Options that control the behaviour of the different browser operations.
+/// The defaults match the behaviour of the functions that don't take options.
+#[derive(Clone)]
+pub struct BrowserOptions {
+    observer: Option<Arc<dyn DatagramObserver>>,
+    stats: Option<Arc<BrowseStats>>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) local_ip: Option<IpAddr>,
+    route_source_ip: bool,
+    pub(crate) local_ports: (u16, u16),
+    pub(crate) browser_ports: Vec<u16>,
+    pub(crate) address_preference: Option<AddressPreference>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) retry: Option<RetryPolicy>,
+    pub(crate) dac_version: u8,
+    pub(crate) broadcast: bool,
+    pub(crate) quiet_period: Option<Duration>,
+    pub(crate) expected_responders: Option<usize>,
+    pub(crate) recv_buffer_size: usize,
+    pub(crate) concurrency: usize,
+    pub(crate) strict_parsing: bool,
+    pub(crate) lenient_length: bool,
+    max_response_size: usize,
+    reuse_address: bool,
+    pub(crate) connected: bool,
+    socket_recv_buffer_size: Option<usize>,
+    socket_send_buffer_size: Option<usize>,
+    pub(crate) multicast_interface_v6: Option<u32>,
+    pub(crate) join_multicast_group: bool,
+    pub(crate) dual_stack: bool,
+    pub(crate) interfaces: Vec<Ipv4Interface>,
+    pub(crate) reject_duplicate_instances: bool,
+    pub(crate) probe_count: usize,
+    probe_interval: Duration,
+    probe_jitter: f64,
+}
+
+impl Default for BrowserOptions {
+    fn default() -> BrowserOptions {
+        BrowserOptions {
+            observer: None,
+            stats: None,
+            clock: Arc::new(SystemClock),
+            local_ip: None,
+            route_source_ip: false,
+            local_ports: (0, 0),
+            browser_ports: vec![1434],
+            address_preference: None,
+            timeout: Some(DEFAULT_TIMEOUT),
+            retry: None,
+            dac_version: DEFAULT_DAC_VERSION,
+            broadcast: true,
+            quiet_period: None,
+            expected_responders: None,
+            recv_buffer_size: MAX_RESPONSE_LEN,
+            concurrency: 256,
+            strict_parsing: false,
+            lenient_length: false,
+            max_response_size: MAX_RESPONSE_LEN,
+            reuse_address: false,
+            connected: true,
+            socket_recv_buffer_size: None,
+            socket_send_buffer_size: None,
+            multicast_interface_v6: None,
+            join_multicast_group: false,
+            dual_stack: false,
+            interfaces: Vec::new(),
+            reject_duplicate_instances: false,
+            probe_count: 1,
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+            probe_jitter: 0.0,
+        }
+    }
+}
+
+impl BrowserOptions {
+    /// Creates options with all settings at their default.
+    pub fn new() -> BrowserOptions {
+        BrowserOptions::default()
+    }
+
+    /// Installs an observer that is notified of every datagram sent and received.
+    pub fn observer<O: DatagramObserver + 'static>(mut self, observer: O) -> BrowserOptions {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Accumulates counters about the operations run with these options into `stats`.
+    /// The same stats can be shared between any number of options and operations.
+    pub fn stats(mut self, stats: Arc<BrowseStats>) -> BrowserOptions {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Replaces the clock the timeouts, retransmissions and round-trip times are based on,
+    /// `SystemClock` by default. See `ManualClock` for testing timeouts without real delays.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> BrowserOptions {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Binds the socket to the given local address instead of the unspecified address,
+    /// which selects the network interface the request is sent from. On hosts with
+    /// multiple interfaces this can be combined with a directed broadcast address to
+    /// sweep each network segment separately.
+    /// Sockets used to reach hosts of the other IP version are bound to the unspecified address.
+    pub fn local_ip(mut self, addr: IpAddr) -> BrowserOptions {
+        self.local_ip = Some(addr);
+        self
+    }
+
+    /// Makes operations without a configured local address bind their socket to the address
+    /// of the interface the routing table selects for reaching the remote host, rather than the
+    /// unspecified address. The route is looked up by connecting a throwaway socket, which sends
+    /// nothing. Useful on hosts with complex routing, where the source address the OS picks per
+    /// datagram can't be reached on the return path. Falls back to the unspecified address when
+    /// the lookup fails, e.g. for broadcast addresses. Disabled by default.
+    pub fn route_source_ip(mut self, enabled: bool) -> BrowserOptions {
+        self.route_source_ip = enabled;
+        self
+    }
+
+    /// Binds the socket to the given local port instead of an ephemeral one,
+    /// so requests are sent from a port permitted by egress filtering.
+    pub fn local_port(self, port: u16) -> BrowserOptions {
+        self.local_port_range(port..=port)
+    }
+
+    /// Binds the socket to the first available local port in the given range
+    /// instead of an ephemeral one. If none of them can be bound, the error of the last
+    /// attempt is returned.
+    pub fn local_port_range(mut self, ports: RangeInclusive<u16>) -> BrowserOptions {
+        self.local_ports = (*ports.start(), *ports.end());
+        self
+    }
+
+    /// Sends the requests of `browse_host` and `browse_instance` to the given UDP port instead
+    /// of 1434, for browser services behind a port forward.
+    pub fn browser_port(self, port: u16) -> BrowserOptions {
+        self.browser_ports(vec![port])
+    }
+
+    /// Makes `browse_host`, `browse_instance` and the requests of a `BrowserSession` try each of
+    /// the given UDP ports in order until one of them yields a valid response, for browser
+    /// services behind a port forward on an unknown port. A port is given up on when it times out, refuses the request or answers
+    /// with an invalid response, in which case the next one is tried with the full timeout.
+    /// If none of them answers, the error of the last port is returned. Only port 1434 by default.
+    ///
+    /// # Panics
+    /// Panics if `ports` is empty.
+    pub fn browser_ports(mut self, ports: Vec<u16>) -> BrowserOptions {
+        assert!(!ports.is_empty(), "at least one browser port is required");
+        self.browser_ports = ports;
+        self
+    }
+
+    /// Selects which of the addresses a host name resolves to are probed by `browse_host_by_name`.
+    /// Without a preference, which is the default, all resolved addresses are probed concurrently
+    /// and the first to respond is used. The `Prefer*` preferences probe the addresses in turn.
+    pub fn address_preference(mut self, preference: Option<AddressPreference>) -> BrowserOptions {
+        self.address_preference = preference;
+        self
+    }
+
+    /// Sets how long to wait for a response before giving up, `DEFAULT_TIMEOUT` by default.
+    /// For `browse` this is the time during which responses are collected after sending the request.
+    /// Connecting the socket is bounded by the same timeout. Passing `None` waits indefinitely.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> BrowserOptions {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Makes `browse_host`, `browse_instance`, `browse_instance_dac` and the requests of a
+    /// `BrowserSession` retransmit their request as described by the given policy while no
+    /// response arrives, within the timeout.
+    /// Round trip times are measured from the most recent transmission. Disabled by default.
+    pub fn retry(mut self, policy: Option<RetryPolicy>) -> BrowserOptions {
+        self.retry = policy;
+        self
+    }
+
+    /// Sets the version byte of the CLNT_UCAST_DAC request, `DEFAULT_DAC_VERSION` by default,
+    /// for probing newer revisions of the DAC protocol. `browse_instance_dac` then only accepts
+    /// responses that report the same version, while `browser_dac_version` accepts any.
+    pub fn dac_version(mut self, version: u8) -> BrowserOptions {
+        self.dac_version = version;
+        self
+    }
+
+    /// Makes `browse` stop listening once no new responder has answered for the given period,
+    /// while still stopping at the timeout at the latest. This returns quickly on quiet networks
+    /// without missing slow responders on busy ones. Disabled by default.
+    pub fn quiet_period(mut self, period: Option<Duration>) -> BrowserOptions {
+        self.quiet_period = period;
+        self
+    }
+
+    /// Makes `browse` stop listening once the given number of distinct addresses have responded,
+    /// while still stopping at the timeout at the latest. Useful when the number of hosts running
+    /// a browser service is known in advance. Disabled by default.
+    pub fn expected_responders(mut self, count: Option<usize>) -> BrowserOptions {
+        self.expected_responders = count;
+        self
+    }
+
+    /// Sets whether `browse` enables the broadcast option on its socket, enabled by default.
+    /// It's never enabled when browsing via a multicast or IPv6 address, as it isn't needed there.
+    /// Disabling it allows enumerating instances via a unicast IPv4 address on
+    /// systems where sending broadcasts isn't permitted.
+    pub fn broadcast(mut self, enabled: bool) -> BrowserOptions {
+        self.broadcast = enabled;
+        self
+    }
+
+    /// Sets how many times `browse` sends its request, 1 by default. Sending it more than once,
+    /// spaced by the probe interval, improves the chance of reaching every host on lossy networks.
+    /// Hosts answer every request that reaches them, the instances they describe are only
+    /// returned once though.
+    pub fn probe_count(mut self, count: usize) -> BrowserOptions {
+        assert!(count >= 1, "probe count must be at least 1");
+        self.probe_count = count;
+        self
+    }
+
+    /// Sets the delay between consecutive transmissions of the `browse` request,
+    /// `DEFAULT_PROBE_INTERVAL` by default. Only applies when more than one probe is sent.
+    pub fn probe_interval(mut self, interval: Duration) -> BrowserOptions {
+        self.probe_interval = interval;
+        self
+    }
+
+    /// Randomly varies the delay between transmissions of the `browse` request by up to the given
+    /// fraction of the probe interval in either direction, so clients browsing at the same time
+    /// spread out their requests and the responses to them. Disabled, i.e. 0, by default.
+    pub fn probe_jitter(mut self, fraction: f64) -> BrowserOptions {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "probe jitter must be between 0 and 1"
+        );
+        self.probe_jitter = fraction;
+        self
+    }
+
+    /// Makes `browse` also send the request to the default address of the other IP version,
+    /// the IPv4 limited broadcast address or the IPv6 link-local all-nodes multicast address,
+    /// using a second socket. Responses to both requests are merged into one iterator, the
+    /// address they were sent from tells which IP version they arrived over. Disabled by default.
+    pub fn dual_stack(mut self, enabled: bool) -> BrowserOptions {
+        self.dual_stack = enabled;
+        self
+    }
+
+    /// Makes `browse` of an IPv4 broadcast address additionally send the request from each of
+    /// the given local interfaces to the directed broadcast address of its subnet, using a socket
+    /// bound to the interface address, which reaches subnets a single broadcast socket doesn't on
+    /// multi-homed hosts. Responses to all requests are merged into one iterator. Empty by default.
+    /// The interfaces of the local host can be enumerated via `local_ipv4_interfaces`.
+    pub fn interfaces(mut self, interfaces: Vec<Ipv4Interface>) -> BrowserOptions {
+        self.interfaces = interfaces;
+        self
+    }
+
+    /// Sets the size of the buffer responses of `browse`, `browse_instance` and `browse_instances`
+    /// are received into. Datagrams larger than this are truncated and fail to parse.
+    /// Defaults to the protocol maximum of 65538 bytes.
+    ///
+    /// # Panics
+    /// Panics if `size` is smaller than the 3 byte response header.
+    pub fn recv_buffer_size(mut self, size: usize) -> BrowserOptions {
+        assert!(
+            size >= 3,
+            "receive buffer must be able to hold the response header"
+        );
+        self.recv_buffer_size = size;
+        self
+    }
+
+    /// Sets how many hosts operations that scan multiple hosts, like `browse_cidr`,
+    /// query at the same time. Defaults to 256, enough to scan a /24 network within a single timeout.
+    ///
+    /// # Panics
+    /// Panics if `limit` is zero.
+    pub fn concurrency(mut self, limit: usize) -> BrowserOptions {
+        assert!(limit > 0, "concurrency limit must be at least 1");
+        self.concurrency = limit;
+        self
+    }
+
+    /// Sets whether responses that deviate from the documented protocol are rejected. When disabled,
+    /// which is the default, identifiers that aren't part of the protocol are collected into
+    /// `InstanceInfo::unknown` and the instance fields may appear in any order, so extensions sent by
+    /// newer servers or containerized editions don't break discovery.
+    pub fn strict_parsing(mut self, strict: bool) -> BrowserOptions {
+        self.strict_parsing = strict;
+        self
+    }
+
+    /// Sets whether responses whose header specifies a length that doesn't match the size of
+    /// the datagram are parsed anyway, as some servers count the header or a trailing null.
+    /// Such responses are rejected with `BrowserProtocolError::LengthMismatch`, or
+    /// `BrowserProtocolError::Truncated` if the datagram is too short, by default.
+    /// `InstanceInfo::header` keeps both sizes, see `ResponseHeader::length_matches`.
+    pub fn lenient_length(mut self, enabled: bool) -> BrowserOptions {
+        self.lenient_length = enabled;
+        self
+    }
+
+    /// Sets whether a host response that lists the same instance name more than once is rejected
+    /// with `BrowserProtocolError::DuplicateInstance`. When disabled, which is the default,
+    /// only the first entry for each instance is returned by `browse_host`.
+    pub fn reject_duplicate_instances(mut self, reject: bool) -> BrowserOptions {
+        self.reject_duplicate_instances = reject;
+        self
+    }
+
+    /// Sets the size of the largest response that is parsed, including its 3 byte header.
+    /// By default this is 65538 bytes, the largest response the protocol allows for, so that
+    /// no valid response of `browse_host` is rejected.
+    /// Larger responses are rejected with `BrowserProtocolError::ResponseTooLarge`,
+    /// which guards against hostile hosts when probing untrusted networks.
+    pub fn max_response_size(mut self, size: usize) -> BrowserOptions {
+        self.max_response_size = size;
+        self
+    }
+
+    /// Sets whether the operations that talk to a single host connect their socket to it,
+    /// which makes the system drop datagrams from any other address. When disabled, the socket
+    /// isn't connected and responses are accepted from any port of the host, while datagrams
+    /// from other addresses are dropped by the operation itself. This helps with stateful
+    /// firewalls and NAT devices that mishandle connected UDP sockets, or hosts that answer
+    /// from a different port. Enabled by default.
+    pub fn connected(mut self, enabled: bool) -> BrowserOptions {
+        self.connected = enabled;
+        self
+    }
+
+    /// Sets whether the socket may bind to a local address that is already in use,
+    /// e.g. to share a fixed `local_port` between several scanners. Disabled by default.
+    pub fn reuse_address(mut self, enabled: bool) -> BrowserOptions {
+        self.reuse_address = enabled;
+        self
+    }
+
+    /// Sets the size of the kernel receive buffer of the socket, instead of the system default.
+    /// Raising it avoids dropping responses when many hosts answer a broadcast at once.
+    pub fn socket_recv_buffer_size(mut self, size: Option<usize>) -> BrowserOptions {
+        self.socket_recv_buffer_size = size;
+        self
+    }
+
+    /// Sets the size of the kernel send buffer of the socket, instead of the system default.
+    pub fn socket_send_buffer_size(mut self, size: Option<usize>) -> BrowserOptions {
+        self.socket_send_buffer_size = size;
+        self
+    }
+
+    /// Sends IPv6 multicast requests of `browse` from the interface with the given index,
+    /// rather than the one selected by the system. Link-local multicast groups such as
+    /// `ff02::1` exist once per interface, so without it discovery might not reach any host.
+    /// Interface indexes are listed by e.g. `ip link` on Linux or `netsh interface ipv6 show
+    /// interfaces` on Windows. Defaults to `None`, see also `browse_multicast_v6`.
+    pub fn multicast_interface_v6(mut self, index: Option<u32>) -> BrowserOptions {
+        self.multicast_interface_v6 = index;
+        self
+    }
+
+    /// Makes `browse` join the multicast group it sends the request to, e.g. `239.255.255.250`,
+    /// before listening for responses, and leave it again when the iterator is dropped.
+    /// Hosts answer with unicast datagrams, so this is only needed in networks whose switches
+    /// or routers forward multicast traffic to group members only, e.g. with IGMP snooping.
+    /// IPv4 groups are joined on the interface of the address the socket is bound to, see
+    /// `local_ip`, IPv6 groups on the interface of `multicast_interface_v6`, falling back to
+    /// the one selected by the system. Has no effect for other addresses. Defaults to `false`.
+    pub fn join_multicast_group(mut self, enabled: bool) -> BrowserOptions {
+        self.join_multicast_group = enabled;
+        self
+    }
+
+    /// Gets the socket options to request when binding a socket.
+    pub(crate) fn socket_config(&self, broadcast: bool) -> SocketConfig {
+        SocketConfig {
+            broadcast,
+            reuse_address: self.reuse_address,
+            recv_buffer_size: self.socket_recv_buffer_size,
+            send_buffer_size: self.socket_send_buffer_size,
+            multicast_interface_v6: self.multicast_interface_v6,
+        }
+    }
+
+    /// Gets the local address to bind to when communicating with `remote_addr`.
+    pub(crate) fn bind_ip(&self, remote_addr: &IpAddr) -> IpAddr {
+        match self.local_ip {
+            Some(addr) if addr.is_ipv4() == remote_addr.is_ipv4() => addr,
+            _ if self.route_source_ip => {
+                route_source_ip(remote_addr).unwrap_or_else(|| unspecified_ip(remote_addr))
+            }
+            _ => unspecified_ip(remote_addr),
+        }
+    }
+
+    /// Selects the addresses to probe out of those a host name resolved to,
+    /// as configured via `address_preference`.
+    pub(crate) fn select_addresses(&self, addrs: &[IpAddr]) -> Vec<IpAddr> {
+        match self.address_preference {
+            Some(preference) => preference.select(addrs),
+            None => addrs.to_vec(),
+        }
+    }
+
+    /// Gets the delay until the next transmission of the `browse` request,
+    /// the probe interval randomly varied by the configured jitter.
+    pub(crate) fn probe_delay(&self) -> Duration {
+        jittered(self.probe_interval, self.probe_jitter)
+    }
+
+    /// Checks the size of a received datagram against the configured maximum response size.
+    pub(crate) fn check_response_size(&self, datagram: &[u8]) -> Result<(), BrowserProtocolError> {
+        if datagram.len() > self.max_response_size {
+            return Err(BrowserProtocolError::ResponseTooLarge {
+                limit: self.max_response_size,
+                actual: datagram.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Gets the current point in time according to the configured clock.
+    #[inline]
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    #[inline]
+    pub(crate) fn notify(&self, direction: DatagramDirection, peer: &SocketAddr, data: &[u8]) {
+        if let Some(observer) = &self.observer {
+            observer.observe(direction, peer, data);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record<F: FnOnce(&OperationStats)>(&self, operation: BrowseOperation, f: F) {
+        if let Some(stats) = &self.stats {
+            f(stats.operation(operation));
+        }
+    }
+}
+
+/// Gets the unspecified address of the IP version of `remote_addr`.
+fn unspecified_ip(remote_addr: &IpAddr) -> IpAddr {
+    match remote_addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+/// Looks up the local address the routing table selects for reaching `remote_addr`,
+/// by connecting a UDP socket to it, which doesn't send anything.
+fn route_source_ip(remote_addr: &IpAddr) -> Option<IpAddr> {
+    if remote_addr.is_unspecified() {
+        return None;
+    }
+
+    let socket = std::net::UdpSocket::bind(SocketAddr::new(unspecified_ip(remote_addr), 0)).ok()?;
+    socket.connect(SocketAddr::new(*remote_addr, 1434)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+impl std::fmt::Debug for BrowserOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BrowserOptions")
+            .field("observer", &self.observer.as_ref().map(|_| ".."))
+            .field("stats", &self.stats)
+            .field("clock", &"..")
+            .field("local_ip", &self.local_ip)
+            .field("route_source_ip", &self.route_source_ip)
+            .field("local_ports", &(self.local_ports.0..=self.local_ports.1))
+            .field("browser_ports", &self.browser_ports)
+            .field("address_preference", &self.address_preference)
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .field("dac_version", &self.dac_version)
+            .field("broadcast", &self.broadcast)
+            .field("quiet_period", &self.quiet_period)
+            .field("expected_responders", &self.expected_responders)
+            .field("recv_buffer_size", &self.recv_buffer_size)
+            .field("concurrency", &self.concurrency)
+            .field("strict_parsing", &self.strict_parsing)
+            .field("lenient_length", &self.lenient_length)
+            .field("max_response_size", &self.max_response_size)
+            .field("reuse_address", &self.reuse_address)
+            .field("connected", &self.connected)
+            .field("socket_recv_buffer_size", &self.socket_recv_buffer_size)
+            .field("socket_send_buffer_size", &self.socket_send_buffer_size)
+            .field("multicast_interface_v6", &self.multicast_interface_v6)
+            .field("join_multicast_group", &self.join_multicast_group)
+            .field("dual_stack", &self.dual_stack)
+            .field("interfaces", &self.interfaces)
+            .field(
+                "reject_duplicate_instances",
+                &self.reject_duplicate_instances,
+            )
+            .field("probe_count", &self.probe_count)
+            .field("probe_interval", &self.probe_interval)
+            .field("probe_jitter", &self.probe_jitter)
+            .finish()
+    }
+}
+
+/// Controls which of the addresses a host name resolves to are probed, for dual-stack hosts
+/// with both A and AAAA records, see `BrowserOptions::address_preference`.
+/// Without a preference, all resolved addresses are probed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressPreference {
+    /// Only probe IPv4 addresses.
+    V4Only,
+
+    /// Only probe IPv6 addresses.
+    V6Only,
+
+    /// Probe the IPv4 addresses, or the IPv6 addresses if there are none.
+    PreferV4,
+
+    /// Probe the IPv6 addresses, or the IPv4 addresses if there are none.
+    PreferV6,
+}
+
+impl AddressPreference {
+    /// Selects the addresses to probe out of the resolved `addrs`, keeping their order.
+    /// Returns an empty `Vec` if none of the addresses are acceptable.
+    pub fn select(self, addrs: &[IpAddr]) -> Vec<IpAddr> {
+        let v4 = || {
+            addrs
+                .iter()
+                .copied()
+                .filter(IpAddr::is_ipv4)
+                .collect::<Vec<_>>()
+        };
+        let v6 = || {
+            addrs
+                .iter()
+                .copied()
+                .filter(IpAddr::is_ipv6)
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            AddressPreference::V4Only => v4(),
+            AddressPreference::V6Only => v6(),
+            AddressPreference::PreferV4 => Some(v4()).filter(|x| !x.is_empty()).unwrap_or_else(v6),
+            AddressPreference::PreferV6 => Some(v6()).filter(|x| !x.is_empty()).unwrap_or_else(v4),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::info::InstanceInfo;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// Collects the instances found by repeated discovery passes, e.g. of an inventory tool that
+/// browses periodically. Instances are identified by the identity of their host and their
+/// instance name, so an instance reported again replaces the information known about it,
+/// including its endpoints, while remembering when it was first seen.
+#[derive(Debug, Default)]
+pub struct InstanceRegistry {
+    instances: BTreeMap<(String, String), RegisteredInstance>,
+}
+
+/// An instance known to an `InstanceRegistry`.
+#[derive(Debug)]
+pub struct RegisteredInstance {
+    /// The information most recently received about the instance.
+    pub info: InstanceInfo,
+
+    /// When the instance was inserted into the registry for the first time.
+    pub first_seen: SystemTime,
+
+    /// When the instance was inserted into the registry most recently.
+    pub last_seen: SystemTime,
+}
+
+impl InstanceRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> InstanceRegistry {
+        InstanceRegistry::default()
+    }
+
+    /// Inserts an instance that was seen just now, see `insert_at`.
+    pub fn insert(&mut self, info: InstanceInfo) -> bool {
+        self.insert_at(info, SystemTime::now())
+    }
+
+    /// Inserts an instance that was seen at the given time, replacing the information known
+    /// about the same instance, if any, unless that was seen later. Instances are identified by `InstanceInfo::host_identity`,
+    /// falling back to the address for hosts without one, and their case insensitive name.
+    /// Returns whether the instance wasn't known before.
+    pub fn insert_at(&mut self, info: InstanceInfo, seen_at: SystemTime) -> bool {
+        let host = info
+            .host_identity()
+            .unwrap_or_else(|| info.addr.to_string());
+        let key = (host, info.instance_name.to_ascii_uppercase());
+
+        match self.instances.get_mut(&key) {
+            Some(known) => {
+                // Information received out of order doesn't replace more recent information
+                if seen_at >= known.last_seen {
+                    known.info = info;
+                    known.last_seen = seen_at;
+                }
+                known.first_seen = known.first_seen.min(seen_at);
+                false
+            }
+            None => {
+                let instance = RegisteredInstance {
+                    info,
+                    first_seen: seen_at,
+                    last_seen: seen_at,
+                };
+                self.instances.insert(key, instance);
+                true
+            }
+        }
+    }
+
+    /// Removes the instances that weren't seen since the given time, e.g. to forget
+    /// instances that didn't answer the last few passes. Returns the removed instances.
+    pub fn remove_unseen_since(&mut self, since: SystemTime) -> Vec<RegisteredInstance> {
+        let stale = self
+            .instances
+            .iter()
+            .filter(|(_, instance)| instance.last_seen < since)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        stale
+            .into_iter()
+            .filter_map(|key| self.instances.remove(&key))
+            .collect()
+    }
+
+    /// Iterates over the known instances, ordered by host and instance name.
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredInstance> {
+        self.instances.values()
+    }
+
+    /// Gets the number of known instances.
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Determines whether no instances are known.
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
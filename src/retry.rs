@@ -0,0 +1,98 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How often and when the unicast operations retransmit their request while no response
+/// arrives, set via `BrowserOptions::retry`. The delay before each retransmission starts at
+/// the initial delay and doubles with every attempt, up to the maximum delay. All attempts
+/// share the timeout configured in the options, which ends retransmission early.
+///
+/// # Examples
+/// ```
+/// use mssql_browser::{BrowserOptions, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let options = BrowserOptions::new().retry(Some(
+///     RetryPolicy::new(4)
+///         .initial_delay(Duration::from_millis(200))
+///         .jitter(0.2),
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Creates a policy that sends the request at most `attempts` times in total, starting with
+    /// a delay of 250 milliseconds before the first retransmission and at most 2 seconds between
+    /// any two, without jitter.
+    ///
+    /// # Panics
+    /// Panics if `attempts` is 0.
+    pub fn new(attempts: u32) -> RetryPolicy {
+        assert!(attempts >= 1, "at least one attempt is required");
+        RetryPolicy {
+            attempts,
+            initial_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(2),
+            jitter: 0.0,
+        }
+    }
+
+    /// Sets the delay between the first transmission of the request and its retransmission.
+    pub fn initial_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the upper bound of the delay between two transmissions of the request.
+    pub fn max_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Randomly varies each delay by up to the given fraction of it in either direction,
+    /// so clients that lost their requests at the same time don't retransmit in lockstep.
+    ///
+    /// # Panics
+    /// Panics if `fraction` isn't between 0 and 1.
+    pub fn jitter(mut self, fraction: f64) -> RetryPolicy {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "retry jitter must be between 0 and 1"
+        );
+        self.jitter = fraction;
+        self
+    }
+
+    /// Gets the delay after the given transmission, counting from 1, before the request is
+    /// sent again. Returns `None` once all attempts are used up.
+    pub(crate) fn delay_after(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= self.attempts {
+            return None;
+        }
+
+        let factor = 2u32.saturating_pow(attempt - 1);
+        let delay = self
+            .initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        Some(jittered(delay, self.jitter))
+    }
+}
+
+/// Randomly varies `duration` by up to the given fraction of it in either direction.
+pub(crate) fn jittered(duration: Duration, fraction: f64) -> Duration {
+    if fraction == 0.0 {
+        return duration;
+    }
+
+    // A random number in [-1, 1], good enough to spread out requests
+    let random = RandomState::new().build_hasher().finish();
+    let factor = (random as f64 / u64::MAX as f64) * 2.0 - 1.0;
+    duration.mul_f64((1.0 + factor * fraction).max(0.0))
+}
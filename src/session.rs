@@ -0,0 +1,335 @@
+use super::browse_host::{parse_host_response, InstanceIterator, CLNT_UCAST_EX};
+use super::browse_instance::{check_instance_name, instance_request, parse_instance_response};
+use super::browse_instance_dac::{dac_request, parse_dac_response_version};
+use super::error::*;
+use super::in_flight::InFlight;
+use super::info::*;
+use super::observer::DatagramDirection;
+use super::options::BrowserOptions;
+use super::socket::{
+    bind_socket_at, receive_error, recv_from_deadline, try_next_port, UdpSocket, UdpSocketFactory,
+};
+use super::stats::BrowseOperation;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// Creates a session that reuses a single socket for any number of requests.
+/// The socket is bound to the local address configured in the options, or the unspecified
+/// IPv4 address, so set `BrowserOptions::local_ip` to query IPv6 hosts.
+///
+/// # Arguments
+/// * `options` - The options to use for the session and all of its requests.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub async fn browser_session(
+    options: &BrowserOptions,
+) -> Result<
+    BrowserSession<<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket>,
+    BrowserError<
+        <super::socket::DefaultSocketFactory as UdpSocketFactory>::Error,
+        <<super::socket::DefaultSocketFactory as UdpSocketFactory>::Socket as UdpSocket>::Error,
+    >,
+> {
+    let mut factory = super::socket::DefaultSocketFactory::new();
+    browser_session_inner(options, &mut factory).await
+}
+
+/// Creates a session that reuses a single socket for any number of requests.
+/// The socket is bound to the local address configured in the options, or the unspecified
+/// IPv4 address, so set `BrowserOptions::local_ip` to query IPv6 hosts.
+///
+/// # Arguments
+/// * `options` - The options to use for the session and all of its requests.
+pub async fn browser_session_inner<SF: UdpSocketFactory>(
+    options: &BrowserOptions,
+    socket_factory: &mut SF,
+) -> Result<BrowserSession<SF::Socket>, BrowserError<SF::Error, <SF::Socket as UdpSocket>::Error>> {
+    let local_ip = options
+        .local_ip
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let socket = bind_socket_at(socket_factory, local_ip, options, false)
+        .await
+        .map_err(BrowserError::BindFailed)?;
+
+    Ok(BrowserSession {
+        socket,
+        in_flight: InFlight::default(),
+        options: options.clone(),
+    })
+}
+
+/// Sends requests to any number of hosts over a single socket, which avoids
+/// binding a new socket for each request when polling the same instances repeatedly.
+/// Requests are sent one at a time, responses from other addresses are ignored.
+///
+/// Like the standalone operations, each request is retransmitted as configured by
+/// `BrowserOptions::retry` and tried on each of the `BrowserOptions::browser_ports` in turn.
+/// Responses that don't match the request, e.g. late responses to an earlier request that
+/// timed out, are skipped until the timeout expires.
+pub struct BrowserSession<S: UdpSocket> {
+    socket: S,
+    in_flight: InFlight,
+    options: BrowserOptions,
+}
+
+impl<S: UdpSocket> BrowserSession<S> {
+    /// Gets information about the given instance.
+    ///
+    /// # Arguments
+    /// * `remote_addr` - The address of the remote host on which the instance is running.
+    /// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+    pub async fn instance(
+        &mut self,
+        remote_addr: IpAddr,
+        instance_name: &str,
+    ) -> Result<InstanceInfo, BrowserError<Infallible, S::Error>> {
+        check_instance_name(instance_name)?;
+
+        let request = instance_request(instance_name);
+        let mut buffer = vec![0u8; self.options.recv_buffer_size];
+        self.request(
+            remote_addr,
+            &request,
+            &mut buffer,
+            BrowseOperation::Instance,
+            |options, remote, datagram, rtt| {
+                let mut instance = parse_instance_response(remote.ip(), datagram, options)?;
+
+                // Late responses to a previous request that timed out
+                if !instance.instance_name.eq_ignore_ascii_case(instance_name) {
+                    return Ok(None);
+                }
+
+                instance.rtt = Some(rtt);
+                instance.source = Some(remote);
+                Ok(Some(instance))
+            },
+        )
+        .await
+    }
+
+    /// Gets DAC information about the given instance.
+    /// DAC responses don't name their instance, so a late response to an earlier DAC request
+    /// for another instance on the same host can't be told apart.
+    ///
+    /// # Arguments
+    /// * `remote_addr` - The address of the remote host on which the instance is running.
+    /// * `instance_name` - The name of the instance, must be less than `MAX_INSTANCE_NAME_LEN` characters.
+    pub async fn dac(
+        &mut self,
+        remote_addr: IpAddr,
+        instance_name: &str,
+    ) -> Result<DacInfo, BrowserError<Infallible, S::Error>> {
+        check_instance_name(instance_name)?;
+
+        let version = self.options.dac_version;
+        let request = dac_request(instance_name, version);
+        let mut buffer = vec![0u8; self.options.recv_buffer_size];
+        self.request(
+            remote_addr,
+            &request,
+            &mut buffer,
+            BrowseOperation::Dac,
+            |_, _, datagram, _| {
+                parse_dac_response_version(datagram, version)
+                    .map(Some)
+                    .map_err(|e| e.with_response(datagram))
+            },
+        )
+        .await
+    }
+
+    /// Discovers any SQL Server instances running on the given host.
+    ///
+    /// # Arguments
+    /// * `remote_addr` - The address of the remote host of which to retrieve information
+    ///   about the instances running on it.
+    pub async fn host(
+        &mut self,
+        remote_addr: IpAddr,
+    ) -> Result<InstanceIterator, BrowserError<Infallible, S::Error>> {
+        let mut buffer = vec![0u8; 65535 + 3];
+        self.request(
+            remote_addr,
+            &[CLNT_UCAST_EX],
+            &mut buffer,
+            BrowseOperation::Host,
+            |options, remote, datagram, rtt| {
+                parse_host_response(remote, datagram.to_vec(), rtt, options).map(Some)
+            },
+        )
+        .await
+    }
+
+    /// Gets the local address the session's socket is bound to, if the socket can tell.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Sends a request to the host, trying each of the configured browser ports in order,
+    /// and returns the first response that `parse` accepts. `parse` is given each datagram
+    /// received from the port along with the time since the request was last sent, and
+    /// returns `None` for responses to another request.
+    async fn request<T, F>(
+        &mut self,
+        remote_addr: IpAddr,
+        request: &[u8],
+        buffer: &mut [u8],
+        operation: BrowseOperation,
+        mut parse: F,
+    ) -> Result<T, BrowserError<Infallible, S::Error>>
+    where
+        F: FnMut(
+            &BrowserOptions,
+            SocketAddr,
+            &[u8],
+            Duration,
+        ) -> Result<Option<T>, BrowserProtocolError>,
+    {
+        let ports = self.options.browser_ports.clone();
+        let mut ports = ports.iter().peekable();
+        loop {
+            // There is at least one port, and the loop returns after the last one
+            let remote = SocketAddr::new(remote_addr, *ports.next().unwrap());
+            match self
+                .request_at(&remote, request, buffer, operation, &mut parse)
+                .await
+            {
+                Err(e) if try_next_port(&e) && ports.peek().is_some() => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a request to the given browser port, retransmitting it as configured by the retry
+    /// policy while no response is accepted, until the timeout expires. Responses that can't be
+    /// parsed are skipped, the last of them is returned if no valid one arrives in time.
+    async fn request_at<T, F>(
+        &mut self,
+        remote: &SocketAddr,
+        request: &[u8],
+        buffer: &mut [u8],
+        operation: BrowseOperation,
+        parse: &mut F,
+    ) -> Result<T, BrowserError<Infallible, S::Error>>
+    where
+        F: FnMut(
+            &BrowserOptions,
+            SocketAddr,
+            &[u8],
+            Duration,
+        ) -> Result<Option<T>, BrowserProtocolError>,
+    {
+        let deadline = self
+            .options
+            .timeout
+            .map(|timeout| self.options.now() + timeout);
+        let mut attempt = 0;
+        let mut parse_error = None;
+
+        loop {
+            let sent_at = self.send(remote, request, operation).await?;
+            attempt += 1;
+
+            let retry_at = self
+                .options
+                .retry
+                .as_ref()
+                .and_then(|policy| policy.delay_after(attempt))
+                .map(|delay| sent_at + delay);
+            let retry_due = match (retry_at, deadline) {
+                (Some(retry_at), Some(deadline)) => retry_at < deadline,
+                (retry_at, _) => retry_at.is_some(),
+            };
+            let wait_until = if retry_due { retry_at } else { deadline };
+
+            while let Some(bytes_received) =
+                self.recv(remote, buffer, wait_until, operation).await?
+            {
+                let rtt = self.options.now() - sent_at;
+                match parse(&self.options, *remote, &buffer[..bytes_received], rtt) {
+                    Ok(Some(value)) => {
+                        self.options.record(operation, |s| s.record_rtt(rtt));
+                        return Ok(value);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.options.record(operation, |s| s.record_parse_error());
+                        parse_error = Some(e);
+                    }
+                }
+            }
+
+            if !retry_due {
+                break;
+            }
+        }
+
+        match parse_error {
+            Some(e) => Err(BrowserError::ProtocolError(e)),
+            None => {
+                self.options.record(operation, |s| s.record_timeout());
+                Err(BrowserError::Timeout(*remote))
+            }
+        }
+    }
+
+    /// Sends a request to the given address, returning the point in time it was sent at.
+    async fn send(
+        &mut self,
+        remote: &SocketAddr,
+        request: &[u8],
+        operation: BrowseOperation,
+    ) -> Result<Instant, BrowserError<Infallible, S::Error>> {
+        let sent_at = self.options.now();
+        self.socket
+            .send_to(request, remote)
+            .await
+            .map_err(|e| BrowserError::SendFailed(*remote, e))?;
+        self.options
+            .notify(DatagramDirection::Sent, remote, request);
+        self.options.record(operation, |s| s.record_request_sent());
+        Ok(sent_at)
+    }
+
+    /// Receives the next datagram sent by the given address, giving up once `deadline` has passed.
+    /// Returns `None` if no datagram was received from the address before the deadline.
+    async fn recv(
+        &mut self,
+        remote: &SocketAddr,
+        buffer: &mut [u8],
+        deadline: Option<Instant>,
+        operation: BrowseOperation,
+    ) -> Result<Option<usize>, BrowserError<Infallible, S::Error>> {
+        loop {
+            self.in_flight.start(remote);
+            let received =
+                recv_from_deadline(&mut self.socket, buffer, deadline, &*self.options.clock).await;
+            self.in_flight.finish();
+            let received = received.map_err(|e| receive_error::<Infallible, S>(remote, e))?;
+
+            let (bytes_received, source) = match received {
+                Some(x) => x,
+                None => return Ok(None),
+            };
+
+            self.options.notify(
+                DatagramDirection::Received,
+                &source,
+                &buffer[..bytes_received],
+            );
+
+            if source == *remote {
+                self.options
+                    .record(operation, |s| s.record_response_received());
+                return Ok(Some(bytes_received));
+            }
+        }
+    }
+}
+
+impl<S: UdpSocket> Drop for BrowserSession<S> {
+    fn drop(&mut self) {
+        self.in_flight.report_drop("BrowserSession");
+    }
+}
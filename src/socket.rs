@@ -1,20 +1,76 @@
+use super::clock::Clock;
+use super::error::BrowserError;
+use super::observer::DatagramDirection;
+use super::options::BrowserOptions;
+use super::stats::BrowseOperation;
 use async_trait::async_trait;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
 
 /// A trait used to create `UdpSocket` instances.
 #[async_trait]
-pub trait UdpSocketFactory: Sized {
+pub trait UdpSocketFactory: Sized + Send {
     type Socket: UdpSocket;
     type Error: std::error::Error;
 
     /// Creates a UDP socket from the given address.
     async fn bind(&mut self, addr: &SocketAddr) -> Result<Self::Socket, Self::Error>;
+
+    /// Creates a UDP socket from the given address, applying the options in `config`
+    /// before binding it. This is what the browser operations use to create their sockets.
+    /// Implementations that can't apply some of the options may ignore them.
+    /// By default, the options are ignored and `bind` is used.
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        _config: &SocketConfig,
+    ) -> Result<Self::Socket, Self::Error> {
+        self.bind(addr).await
+    }
+
+    /// Resolves a host name to the addresses of the host, see `browse_host_by_name`.
+    /// By default, the name is resolved by the blocking resolver of the standard library.
+    async fn resolve(&mut self, host: &str) -> std::io::Result<Vec<IpAddr>> {
+        use std::net::ToSocketAddrs;
+
+        Ok((host, 0).to_socket_addrs()?.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// Socket options requested from a `UdpSocketFactory` when binding a socket.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct SocketConfig {
+    /// Whether the socket is allowed to send packets to a broadcast address (SO_BROADCAST).
+    pub broadcast: bool,
+
+    /// Whether the local address may be reused while bound by other sockets (SO_REUSEADDR).
+    pub reuse_address: bool,
+
+    /// The size of the kernel receive buffer (SO_RCVBUF), or `None` for the system default.
+    pub recv_buffer_size: Option<usize>,
+
+    /// The size of the kernel send buffer (SO_SNDBUF), or `None` for the system default.
+    pub send_buffer_size: Option<usize>,
+
+    /// The index of the interface IPv6 multicast packets are sent from (IPV6_MULTICAST_IF),
+    /// or `None` for the system default. Only applies to IPv6 sockets.
+    pub multicast_interface_v6: Option<u32>,
 }
 
 /// A generic contract for an UDP socket. Used to be agnostic of the
 /// underlying async framework used.
+///
+/// # Cancellation
+/// The futures returned by the receive methods must be cancellation safe: dropping one before
+/// it completes, e.g. because it lost a `select!` or its timeout expired, must leave any datagram
+/// it didn't return queued on the socket, to be returned by the next receive. The iterators of
+/// this crate rely on this to be cancellation safe themselves. A receive may only take a datagram
+/// off its queue in the same poll it completes in, which holds for receives built on a readiness
+/// based `poll_recv_from`, as in tokio and async-std, or on a channel. The implementations in
+/// this crate all satisfy this contract.
 #[async_trait]
-pub trait UdpSocket: Sized {
+pub trait UdpSocket: Sized + Send {
     type Error: std::error::Error;
 
     /// When enabled, this socket is allowed to send packets to a broadcast address.
@@ -24,6 +80,17 @@ pub trait UdpSocket: Sized {
     /// that are read via recv from the address specified in `addr`.
     async fn connect(&mut self, addr: &SocketAddr) -> Result<(), Self::Error>;
 
+    /// Connects the UDP socket like `connect`, giving up after `timeout`.
+    /// On success, returns `Some`, or `None` if the timeout expired.
+    /// By default, the timeout is ignored and `connect` is awaited to completion.
+    async fn connect_timeout(
+        &mut self,
+        addr: &SocketAddr,
+        _timeout: Duration,
+    ) -> Result<Option<()>, Self::Error> {
+        self.connect(addr).await.map(Some)
+    }
+
     /// Sends data on the socket to the remote address to which it is connected.
     /// On success, returns the number of bytes written.
     async fn send(&mut self, buf: &[u8]) -> Result<usize, Self::Error>;
@@ -39,15 +106,404 @@ pub trait UdpSocket: Sized {
     /// Receives a single datagram on the socket.
     /// On success, returns the number of bytes read and the origin.
     async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error>;
+
+    /// Receives a single datagram on the socket from the remote address to which it is connected,
+    /// giving up after `timeout`. On success, returns the number of bytes read, or `None` if the timeout expired.
+    /// By default, the timeout is ignored and `recv` is awaited to completion, so the receive is
+    /// unbounded. Implementations should override this for the browse timeouts to apply.
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, Self::Error> {
+        self.recv(buf).await.map(Some)
+    }
+
+    /// Receives a single datagram on the socket, giving up after `timeout`.
+    /// On success, returns the number of bytes read and the origin, or `None` if the timeout expired.
+    /// A datagram arriving as the timeout expires must either be returned or stay queued.
+    /// By default, the timeout is ignored and `recv_from` is awaited to completion, so the receive
+    /// is unbounded. Implementations should override this for the browse timeouts to apply.
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Self::Error> {
+        self.recv_from(buf).await.map(Some)
+    }
+
+    /// Determines whether the given error indicates that the remote host actively refused
+    /// the datagram, e.g. because of an ICMP port unreachable message. Returns `false` by default.
+    fn is_connection_refused(_error: &Self::Error) -> bool {
+        false
+    }
+
+    /// Gets the local address the socket is bound to, for diagnostics.
+    /// Returns `None` by default, for implementations that can't tell.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Joins the given IPv4 multicast group on the interface with the given address,
+    /// see `BrowserOptions::join_multicast_group`. Does nothing by default.
+    fn join_multicast_v4(
+        &self,
+        _group: &Ipv4Addr,
+        _interface: &Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Leaves an IPv4 multicast group joined via `join_multicast_v4`. Does nothing by default.
+    fn leave_multicast_v4(
+        &self,
+        _group: &Ipv4Addr,
+        _interface: &Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Joins the given IPv6 multicast group on the interface with the given index, or the
+    /// one selected by the system for index 0. Does nothing by default.
+    fn join_multicast_v6(&self, _group: &Ipv6Addr, _interface: u32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Leaves an IPv6 multicast group joined via `join_multicast_v6`. Does nothing by default.
+    fn leave_multicast_v6(&self, _group: &Ipv6Addr, _interface: u32) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
-#[cfg(feature = "tokio")]
+/// Binds a socket for communicating with `remote_addr` as configured in `options`,
+/// trying each of the configured local ports until one succeeds.
+pub(crate) async fn bind_socket<SF: UdpSocketFactory>(
+    socket_factory: &mut SF,
+    remote_addr: &IpAddr,
+    options: &BrowserOptions,
+    broadcast: bool,
+) -> Result<SF::Socket, SF::Error> {
+    bind_socket_at(
+        socket_factory,
+        options.bind_ip(remote_addr),
+        options,
+        broadcast,
+    )
+    .await
+}
+
+/// Binds a socket to the given local address as configured in `options`,
+/// trying each of the configured local ports until one succeeds.
+pub(crate) async fn bind_socket_at<SF: UdpSocketFactory>(
+    socket_factory: &mut SF,
+    ip: IpAddr,
+    options: &BrowserOptions,
+    broadcast: bool,
+) -> Result<SF::Socket, SF::Error> {
+    let (mut port, last_port) = options.local_ports;
+    let config = options.socket_config(broadcast);
+
+    loop {
+        match socket_factory
+            .bind_with(&SocketAddr::new(ip, port), &config)
+            .await
+        {
+            Ok(socket) => return Ok(socket),
+            Err(e) if port >= last_port => return Err(e),
+            Err(_) => port += 1,
+        }
+    }
+}
+
+/// Sends `request` over the socket connected to `remote` and receives a single datagram in
+/// response, retransmitting the request as configured by the retry policy in `options` while
+/// none arrives, until the timeout in `options` expires. Requests, responses and timeouts are
+/// recorded in the stats of the given operation. Returns the number of bytes received and the
+/// time since the request was last sent.
+pub(crate) async fn exchange<SFError: std::error::Error, S: UdpSocket>(
+    socket: &mut S,
+    request: &[u8],
+    buf: &mut [u8],
+    remote: &SocketAddr,
+    options: &BrowserOptions,
+    operation: BrowseOperation,
+) -> Result<(usize, Duration), BrowserError<SFError, S::Error>> {
+    let deadline = options.timeout.map(|timeout| options.now() + timeout);
+    let mut attempt = 0;
+
+    loop {
+        let sent_at = options.now();
+        socket
+            .send_to(request, remote)
+            .await
+            .map_err(|e| BrowserError::SendFailed(*remote, e))?;
+        options.notify(DatagramDirection::Sent, remote, request);
+        options.record(operation, |s| s.record_request_sent());
+        attempt += 1;
+
+        let retry_at = options
+            .retry
+            .as_ref()
+            .and_then(|policy| policy.delay_after(attempt))
+            .map(|delay| sent_at + delay);
+        let retry_due = match (retry_at, deadline) {
+            (Some(retry_at), Some(deadline)) => retry_at < deadline,
+            (retry_at, _) => retry_at.is_some(),
+        };
+
+        let wait_until = if retry_due { retry_at } else { deadline };
+        let received = recv_peer_deadline(socket, buf, remote, wait_until, options)
+            .await
+            .map_err(|e| receive_error::<SFError, S>(remote, e))?;
+
+        match received {
+            Some(bytes_received) => {
+                options.record(operation, |s| s.record_response_received());
+                options.notify(DatagramDirection::Received, remote, &buf[..bytes_received]);
+                return Ok((bytes_received, options.now() - sent_at));
+            }
+            None if retry_due => continue,
+            None => {
+                options.record(operation, |s| s.record_timeout());
+                return Err(BrowserError::Timeout(*remote));
+            }
+        }
+    }
+}
+
+/// Determines whether an operation failed in a way that suggests nothing is answering on the
+/// browser port it used, so the next port configured via `BrowserOptions::browser_ports`
+/// is worth trying.
+pub(crate) fn try_next_port<SFError: std::error::Error, SError: std::error::Error>(
+    error: &BrowserError<SFError, SError>,
+) -> bool {
+    matches!(
+        error,
+        BrowserError::Timeout(_)
+            | BrowserError::BrowserNotRunning(_)
+            | BrowserError::ProtocolError(_)
+    )
+}
+
+/// Connects the socket to `remote`, unless disabled via `BrowserOptions::connected`.
+/// The connect is subject to the timeout in `options`.
+pub(crate) async fn connect_socket<SFError: std::error::Error, S: UdpSocket>(
+    socket: &mut S,
+    remote: &SocketAddr,
+    options: &BrowserOptions,
+) -> Result<(), BrowserError<SFError, S::Error>> {
+    if options.connected {
+        let deadline = options.timeout.map(|timeout| options.now() + timeout);
+        connect_deadline(socket, remote, deadline, &*options.clock).await?;
+    }
+
+    Ok(())
+}
+
+/// Connects the socket to `remote`, giving up once `deadline` has passed according to `clock`.
+pub(crate) async fn connect_deadline<SFError: std::error::Error, S: UdpSocket>(
+    socket: &mut S,
+    remote: &SocketAddr,
+    deadline: Option<Instant>,
+    clock: &dyn Clock,
+) -> Result<(), BrowserError<SFError, S::Error>> {
+    let connected = match deadline {
+        Some(deadline) => {
+            let now = clock.now();
+            if now >= deadline {
+                return Err(BrowserError::ConnectTimeout(*remote));
+            }
+
+            socket.connect_timeout(remote, deadline - now).await
+        }
+        None => socket.connect(remote).await.map(Some),
+    };
+
+    match connected {
+        Ok(Some(())) => Ok(()),
+        Ok(None) => Err(BrowserError::ConnectTimeout(*remote)),
+        Err(e) => Err(BrowserError::ConnectFailed(*remote, e)),
+    }
+}
+
+/// Receives a single datagram sent by the host of `remote` on a socket set up via
+/// `connect_socket`, giving up once `deadline` has passed. Without a connection,
+/// datagrams from other IP addresses are dropped, while any source port is accepted.
+/// Returns `None` if no datagram was received before the deadline.
+pub(crate) async fn recv_peer_deadline<S: UdpSocket>(
+    socket: &mut S,
+    buf: &mut [u8],
+    remote: &SocketAddr,
+    deadline: Option<Instant>,
+    options: &BrowserOptions,
+) -> Result<Option<usize>, S::Error> {
+    if options.connected {
+        return recv_deadline(socket, buf, deadline, &*options.clock).await;
+    }
+
+    loop {
+        match recv_from_deadline(socket, buf, deadline, &*options.clock).await? {
+            Some((bytes_received, source)) if source.ip() == remote.ip() => {
+                return Ok(Some(bytes_received))
+            }
+            Some(_) => continue,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Maps an error received on a socket connected to `remote`, reporting refused
+/// datagrams as `BrowserError::BrowserNotRunning`.
+pub(crate) fn receive_error<SFError: std::error::Error, S: UdpSocket>(
+    remote: &SocketAddr,
+    error: S::Error,
+) -> BrowserError<SFError, S::Error> {
+    if S::is_connection_refused(&error) {
+        BrowserError::BrowserNotRunning(*remote)
+    } else {
+        BrowserError::ReceiveFailed(error)
+    }
+}
+
+/// Receives a single datagram on the connected socket, giving up once `deadline` has passed
+/// according to `clock`. Returns `None` if no datagram was received before the deadline.
+pub(crate) async fn recv_deadline<S: UdpSocket>(
+    socket: &mut S,
+    buf: &mut [u8],
+    deadline: Option<Instant>,
+    clock: &dyn Clock,
+) -> Result<Option<usize>, S::Error> {
+    match deadline {
+        Some(deadline) => {
+            let now = clock.now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            socket.recv_timeout(buf, deadline - now).await
+        }
+        None => socket.recv(buf).await.map(Some),
+    }
+}
+
+/// Receives a single datagram on the socket, giving up once `deadline` has passed
+/// according to `clock`. Returns `None` if no datagram was received before the deadline.
+pub(crate) async fn recv_from_deadline<S: UdpSocket>(
+    socket: &mut S,
+    buf: &mut [u8],
+    deadline: Option<Instant>,
+    clock: &dyn Clock,
+) -> Result<Option<(usize, SocketAddr)>, S::Error> {
+    match deadline {
+        Some(deadline) => {
+            let now = clock.now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            socket.recv_from_timeout(buf, deadline - now).await
+        }
+        None => socket.recv_from(buf).await.map(Some),
+    }
+}
+
+/// Creates a non-blocking standard library socket with the options in `config` applied, bound to `addr`.
+fn bind_std(addr: &SocketAddr, config: &SocketConfig) -> std::io::Result<std::net::UdpSocket> {
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+    let domain = match addr {
+        SocketAddr::V4(_) => Domain::ipv4(),
+        SocketAddr::V6(_) => Domain::ipv6(),
+    };
+
+    let socket = Socket::new(domain, Type::dgram(), Some(Protocol::udp()))?;
+    socket.set_broadcast(config.broadcast)?;
+    socket.set_reuse_address(config.reuse_address)?;
+    if let Some(size) = config.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+    if let Some(size) = config.send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let (Some(index), SocketAddr::V6(_)) = (config.multicast_interface_v6, addr) {
+        socket.set_multicast_if_v6(index)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&SockAddr::from(*addr))?;
+
+    Ok(socket.into_udp_socket())
+}
+
+/// A socket factory that hands out a socket created by the application instead of binding one,
+/// e.g. one inherited via socket activation, or created with options this crate doesn't expose.
+/// The socket is handed out to the first operation that binds one, any further attempt fails
+/// with `SocketTaken`, so operations binding more than one socket, like `browse` in dual-stack
+/// mode or `browse_instances`, can't be used with it. The bind address and the options
+/// requested via `SocketConfig` are ignored, apply them when creating the socket instead.
+#[derive(Debug)]
+pub struct PreboundSocketFactory<S> {
+    socket: Option<S>,
+}
+
+impl<S> PreboundSocketFactory<S> {
+    /// Creates a factory that hands out the given socket.
+    pub fn new(socket: S) -> PreboundSocketFactory<S> {
+        PreboundSocketFactory {
+            socket: Some(socket),
+        }
+    }
+
+    /// Gets the socket back if it wasn't handed out yet.
+    pub fn into_inner(self) -> Option<S> {
+        self.socket
+    }
+}
+
+#[async_trait]
+impl<S: UdpSocket + Send> UdpSocketFactory for PreboundSocketFactory<S> {
+    type Socket = S;
+    type Error = SocketTaken;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<S, SocketTaken> {
+        self.socket.take().ok_or(SocketTaken)
+    }
+}
+
+/// The error returned by `PreboundSocketFactory` once its socket was handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocketTaken;
+
+impl std::fmt::Display for SocketTaken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the pre-bound socket was already handed out")
+    }
+}
+
+impl std::error::Error for SocketTaken {}
+
+/// The socket factory used by the functions that don't take one. This is the tokio factory
+/// when the `tokio` feature is enabled, unless the `prefer-async-std` feature is enabled too.
+#[cfg(all(feature = "tokio", not(feature = "prefer-async-std")))]
 pub type DefaultSocketFactory = TokioSocketFactory;
 
-#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+/// The socket factory used by the functions that don't take one. This is the async-std factory
+/// when the `tokio` feature is disabled, or the `prefer-async-std` feature is enabled.
+#[cfg(all(
+    feature = "async-std",
+    any(not(feature = "tokio"), feature = "prefer-async-std")
+))]
 pub type DefaultSocketFactory = AsyncStdSocketFactory;
 
+/// Creates the socket factory used by the functions that don't take one, whichever runtime
+/// is selected via the crate features. Useful for calling the functions in `custom_socket`
+/// from code that is generic over the socket factory.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub fn default_socket_factory() -> DefaultSocketFactory {
+    DefaultSocketFactory::new()
+}
+
 #[cfg(feature = "tokio")]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct TokioSocketFactory;
 
 #[cfg(feature = "tokio")]
@@ -66,6 +522,19 @@ impl UdpSocketFactory for TokioSocketFactory {
     async fn bind(&mut self, addr: &SocketAddr) -> Result<Self::Socket, Self::Error> {
         tokio::net::UdpSocket::bind(addr).await
     }
+
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        config: &SocketConfig,
+    ) -> Result<Self::Socket, Self::Error> {
+        tokio::net::UdpSocket::from_std(bind_std(addr, config)?)
+    }
+
+    async fn resolve(&mut self, host: &str) -> std::io::Result<Vec<IpAddr>> {
+        let addrs = tokio::net::lookup_host((host, 0)).await?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
 }
 
 #[cfg(feature = "tokio")]
@@ -81,6 +550,17 @@ impl UdpSocket for tokio::net::UdpSocket {
         Self::connect(self, addr).await
     }
 
+    async fn connect_timeout(
+        &mut self,
+        addr: &SocketAddr,
+        timeout: Duration,
+    ) -> Result<Option<()>, Self::Error> {
+        match tokio::time::timeout(timeout, Self::connect(self, addr)).await {
+            Ok(res) => res.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
     async fn send(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         Self::send(self, buf).await
     }
@@ -96,9 +576,64 @@ impl UdpSocket for tokio::net::UdpSocket {
     async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
         Self::recv_from(self, buf).await
     }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, Self::Error> {
+        match tokio::time::timeout(timeout, Self::recv(self, buf)).await {
+            Ok(res) => res.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Self::Error> {
+        match tokio::time::timeout(timeout, Self::recv_from(self, buf)).await {
+            Ok(res) => res.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn is_connection_refused(error: &Self::Error) -> bool {
+        // Windows reports an ICMP port unreachable as a connection reset
+        matches!(
+            error.kind(),
+            tokio::io::ErrorKind::ConnectionRefused | tokio::io::ErrorKind::ConnectionReset
+        )
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        tokio::net::UdpSocket::local_addr(self).ok()
+    }
+
+    fn join_multicast_v4(&self, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<(), Self::Error> {
+        Self::join_multicast_v4(self, *group, *interface)
+    }
+
+    fn leave_multicast_v4(
+        &self,
+        group: &Ipv4Addr,
+        interface: &Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        Self::leave_multicast_v4(self, *group, *interface)
+    }
+
+    fn join_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), Self::Error> {
+        Self::join_multicast_v6(self, group, interface)
+    }
+
+    fn leave_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), Self::Error> {
+        Self::leave_multicast_v6(self, group, interface)
+    }
 }
 
 #[cfg(feature = "async-std")]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct AsyncStdSocketFactory;
 
 #[cfg(feature = "async-std")]
@@ -117,6 +652,21 @@ impl UdpSocketFactory for AsyncStdSocketFactory {
     async fn bind(&mut self, addr: &SocketAddr) -> Result<Self::Socket, Self::Error> {
         async_std::net::UdpSocket::bind(addr).await
     }
+
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        config: &SocketConfig,
+    ) -> Result<Self::Socket, Self::Error> {
+        Ok(async_std::net::UdpSocket::from(bind_std(addr, config)?))
+    }
+
+    async fn resolve(&mut self, host: &str) -> std::io::Result<Vec<IpAddr>> {
+        use async_std::net::ToSocketAddrs;
+
+        let addrs = (host, 0).to_socket_addrs().await?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
 }
 
 #[cfg(feature = "async-std")]
@@ -132,6 +682,17 @@ impl UdpSocket for async_std::net::UdpSocket {
         Self::connect(self, addr).await
     }
 
+    async fn connect_timeout(
+        &mut self,
+        addr: &SocketAddr,
+        timeout: Duration,
+    ) -> Result<Option<()>, Self::Error> {
+        match async_std::future::timeout(timeout, Self::connect(self, addr)).await {
+            Ok(res) => res.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
     async fn send(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         Self::send(self, buf).await
     }
@@ -145,13 +706,200 @@ impl UdpSocket for async_std::net::UdpSocket {
     }
 
     async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
-        use std::net::ToSocketAddrs;
+        Self::recv_from(self, buf).await
+    }
 
-        match Self::recv_from(self, buf).await {
-            Ok((recv_bytes, addr)) => {
-                Ok((recv_bytes, addr.to_socket_addrs().unwrap().next().unwrap()))
-            }
-            Err(x) => Err(x),
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, Self::Error> {
+        match async_std::future::timeout(timeout, Self::recv(self, buf)).await {
+            Ok(res) => res.map(Some),
+            Err(_) => Ok(None),
         }
     }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Self::Error> {
+        match async_std::future::timeout(timeout, Self::recv_from(self, buf)).await {
+            Ok(res) => res.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn is_connection_refused(error: &Self::Error) -> bool {
+        // Windows reports an ICMP port unreachable as a connection reset
+        matches!(
+            error.kind(),
+            async_std::io::ErrorKind::ConnectionRefused | async_std::io::ErrorKind::ConnectionReset
+        )
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        async_std::net::UdpSocket::local_addr(self).ok()
+    }
+
+    fn join_multicast_v4(&self, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<(), Self::Error> {
+        Self::join_multicast_v4(self, *group, *interface)
+    }
+
+    fn leave_multicast_v4(
+        &self,
+        group: &Ipv4Addr,
+        interface: &Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        Self::leave_multicast_v4(self, *group, *interface)
+    }
+
+    fn join_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), Self::Error> {
+        Self::join_multicast_v6(self, group, interface)
+    }
+
+    fn leave_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), Self::Error> {
+        Self::leave_multicast_v6(self, group, interface)
+    }
+}
+
+/// A socket factory creating blocking standard library sockets, for running the operations
+/// of `custom_socket` without an async runtime, e.g. via `futures::executor::block_on`.
+/// Every operation blocks the calling thread until it completes. Timeouts are implemented
+/// via the read timeout of the socket, so no timer thread is needed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockingSocketFactory;
+
+impl BlockingSocketFactory {
+    pub fn new() -> BlockingSocketFactory {
+        BlockingSocketFactory
+    }
+}
+
+#[async_trait]
+impl UdpSocketFactory for BlockingSocketFactory {
+    type Error = std::io::Error;
+    type Socket = std::net::UdpSocket;
+
+    async fn bind(&mut self, addr: &SocketAddr) -> Result<Self::Socket, Self::Error> {
+        std::net::UdpSocket::bind(addr)
+    }
+
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        config: &SocketConfig,
+    ) -> Result<Self::Socket, Self::Error> {
+        let socket = bind_std(addr, config)?;
+        socket.set_nonblocking(false)?;
+        Ok(socket)
+    }
+}
+
+/// Sets the read timeout of a blocking socket. A zero timeout would disable it, so the
+/// smallest timeout supported by all platforms is used instead.
+fn set_read_timeout(
+    socket: &std::net::UdpSocket,
+    timeout: Option<Duration>,
+) -> std::io::Result<()> {
+    socket.set_read_timeout(timeout.map(|timeout| timeout.max(Duration::from_millis(1))))
+}
+
+/// Maps the error of a read that ran into the read timeout to `None`. Unix platforms report
+/// an expired read timeout as `WouldBlock`, Windows as `TimedOut`.
+fn read_timed_out<T>(result: std::io::Result<T>) -> std::io::Result<Option<T>> {
+    match result {
+        Ok(x) => Ok(Some(x)),
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[async_trait]
+impl UdpSocket for std::net::UdpSocket {
+    type Error = std::io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), Self::Error> {
+        Self::set_broadcast(self, true)
+    }
+
+    async fn connect(&mut self, addr: &SocketAddr) -> Result<(), Self::Error> {
+        Self::connect(self, addr)
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Self::send(self, buf)
+    }
+
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<usize, Self::Error> {
+        Self::send_to(self, buf, addr)
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        set_read_timeout(self, None)?;
+        Self::recv(self, buf)
+    }
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        set_read_timeout(self, None)?;
+        Self::recv_from(self, buf)
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, Self::Error> {
+        set_read_timeout(self, Some(timeout))?;
+        read_timed_out(Self::recv(self, buf))
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Self::Error> {
+        set_read_timeout(self, Some(timeout))?;
+        read_timed_out(Self::recv_from(self, buf))
+    }
+
+    fn is_connection_refused(error: &Self::Error) -> bool {
+        // Windows reports an ICMP port unreachable as a connection reset
+        matches!(
+            error.kind(),
+            std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset
+        )
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        std::net::UdpSocket::local_addr(self).ok()
+    }
+
+    fn join_multicast_v4(&self, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<(), Self::Error> {
+        Self::join_multicast_v4(self, group, interface)
+    }
+
+    fn leave_multicast_v4(
+        &self,
+        group: &Ipv4Addr,
+        interface: &Ipv4Addr,
+    ) -> Result<(), Self::Error> {
+        Self::leave_multicast_v4(self, group, interface)
+    }
+
+    fn join_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), Self::Error> {
+        Self::join_multicast_v6(self, group, interface)
+    }
+
+    fn leave_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), Self::Error> {
+        Self::leave_multicast_v6(self, group, interface)
+    }
 }
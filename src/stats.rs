@@ -0,0 +1,254 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The upper bounds of the buckets of the round trip time histogram kept by `OperationStats`.
+/// Round trip times above the last bound are counted in an additional overflow bucket.
+pub const RTT_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(1000),
+];
+
+/// The different operations for which `BrowseStats` keeps separate counters.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum BrowseOperation {
+    /// Broadcast or multicast discovery via `browse`
+    Broadcast,
+
+    /// Discovery of all instances on a host via `browse_host`
+    Host,
+
+    /// Requests for a specific instance via `browse_instance` or `browse_instances`
+    Instance,
+
+    /// Requests for the DAC port of an instance via `browse_instance_dac`
+    Dac,
+}
+
+impl BrowseOperation {
+    /// All operations, in the order in which they are reported.
+    pub const ALL: [BrowseOperation; 4] = [
+        BrowseOperation::Broadcast,
+        BrowseOperation::Host,
+        BrowseOperation::Instance,
+        BrowseOperation::Dac,
+    ];
+
+    /// Gets the label used for this operation, e.g. `instance`.
+    pub fn label(self) -> &'static str {
+        match self {
+            BrowseOperation::Broadcast => "broadcast",
+            BrowseOperation::Host => "host",
+            BrowseOperation::Instance => "instance",
+            BrowseOperation::Dac => "dac",
+        }
+    }
+}
+
+/// Counters accumulated over all operations that were given these stats via
+/// `BrowserOptions::stats`, kept separately per operation. All counters only ever increase,
+/// so they can be exported as-is, e.g. in the Prometheus text format using `Display`.
+///
+/// # Examples
+/// ```
+/// use mssql_browser::{BrowseOperation, BrowseStats, BrowserOptions};
+/// use std::sync::Arc;
+///
+/// let stats = Arc::new(BrowseStats::new());
+/// let options = BrowserOptions::new().stats(stats.clone());
+///
+/// // .. run some operations with `options` ..
+///
+/// assert_eq!(stats.operation(BrowseOperation::Instance).requests_sent(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct BrowseStats {
+    broadcast: OperationStats,
+    host: OperationStats,
+    instance: OperationStats,
+    dac: OperationStats,
+}
+
+impl BrowseStats {
+    /// Creates stats with all counters at zero.
+    pub fn new() -> BrowseStats {
+        BrowseStats::default()
+    }
+
+    /// Gets the counters of the given operation.
+    pub fn operation(&self, operation: BrowseOperation) -> &OperationStats {
+        match operation {
+            BrowseOperation::Broadcast => &self.broadcast,
+            BrowseOperation::Host => &self.host,
+            BrowseOperation::Instance => &self.instance,
+            BrowseOperation::Dac => &self.dac,
+        }
+    }
+
+    /// Writes a counter of every operation in the Prometheus text exposition format.
+    fn write_counter<F: Fn(&OperationStats) -> u64>(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        name: &str,
+        help: &str,
+        value: F,
+    ) -> std::fmt::Result {
+        writeln!(f, "# HELP mssql_browser_{}_total {}", name, help)?;
+        writeln!(f, "# TYPE mssql_browser_{}_total counter", name)?;
+        for operation in BrowseOperation::ALL.iter() {
+            writeln!(
+                f,
+                "mssql_browser_{}_total{{operation=\"{}\"}} {}",
+                name,
+                operation.label(),
+                value(self.operation(*operation))
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for BrowseStats {
+    /// Writes the counters in the Prometheus text exposition format,
+    /// labeled by operation.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_counter(f, "requests_sent", "Requests sent", |s| s.requests_sent())?;
+        self.write_counter(f, "responses_received", "Datagrams received", |s| {
+            s.responses_received()
+        })?;
+        self.write_counter(f, "parse_errors", "Invalid responses received", |s| {
+            s.parse_errors()
+        })?;
+        self.write_counter(f, "timeouts", "Requests that timed out", |s| s.timeouts())?;
+
+        writeln!(
+            f,
+            "# HELP mssql_browser_rtt_seconds Round trip time of responses"
+        )?;
+        writeln!(f, "# TYPE mssql_browser_rtt_seconds histogram")?;
+        for operation in BrowseOperation::ALL.iter() {
+            let stats = self.operation(*operation);
+            let label = operation.label();
+            let mut cumulative = 0;
+
+            for (bound, count) in RTT_BUCKETS.iter().zip(stats.rtt_bucket_counts().iter()) {
+                cumulative += count;
+                writeln!(
+                    f,
+                    "mssql_browser_rtt_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                    label,
+                    bound.as_secs_f64(),
+                    cumulative
+                )?;
+            }
+
+            writeln!(
+                f,
+                "mssql_browser_rtt_seconds_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+                label,
+                stats.rtt_count()
+            )?;
+            writeln!(
+                f,
+                "mssql_browser_rtt_seconds_sum{{operation=\"{}\"}} {}",
+                label,
+                stats.rtt_sum().as_secs_f64()
+            )?;
+            writeln!(
+                f,
+                "mssql_browser_rtt_seconds_count{{operation=\"{}\"}} {}",
+                label,
+                stats.rtt_count()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counters of a single operation, see `BrowseStats`.
+#[derive(Debug, Default)]
+pub struct OperationStats {
+    requests_sent: AtomicU64,
+    responses_received: AtomicU64,
+    parse_errors: AtomicU64,
+    timeouts: AtomicU64,
+    rtt_buckets: [AtomicU64; 9],
+    rtt_sum_micros: AtomicU64,
+}
+
+impl OperationStats {
+    /// Gets the number of requests sent.
+    pub fn requests_sent(&self) -> u64 {
+        self.requests_sent.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of datagrams received in response, including invalid ones.
+    pub fn responses_received(&self) -> u64 {
+        self.responses_received.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of received datagrams that couldn't be parsed.
+    pub fn parse_errors(&self) -> u64 {
+        self.parse_errors.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of requests for which no response was received within the timeout.
+    /// Broadcast discovery always listens until its timeout, which isn't counted.
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of round trip times recorded per bucket of `RTT_BUCKETS`,
+    /// followed by the number of round trip times above the last bucket.
+    pub fn rtt_bucket_counts(&self) -> [u64; 9] {
+        let mut counts = [0; 9];
+        for (count, bucket) in counts.iter_mut().zip(self.rtt_buckets.iter()) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+        counts
+    }
+
+    /// Gets the number of round trip times recorded.
+    pub fn rtt_count(&self) -> u64 {
+        self.rtt_bucket_counts().iter().sum()
+    }
+
+    /// Gets the sum of all round trip times recorded.
+    pub fn rtt_sum(&self) -> Duration {
+        Duration::from_micros(self.rtt_sum_micros.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn record_request_sent(&self) {
+        self.requests_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_response_received(&self) {
+        self.responses_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rtt(&self, rtt: Duration) {
+        let bucket = RTT_BUCKETS
+            .iter()
+            .position(|bound| rtt <= *bound)
+            .unwrap_or(RTT_BUCKETS.len());
+
+        self.rtt_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.rtt_sum_micros
+            .fetch_add(rtt.as_micros() as u64, Ordering::Relaxed);
+    }
+}
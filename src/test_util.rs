@@ -0,0 +1,425 @@
+//! An in-memory network for testing code built on this crate without binding OS sockets,
+//! e.g. in sandboxes that block UDP. Sockets bound through a `MemorySocketFactory` exchange
+//! datagrams with each other through the real `custom_socket` code paths.
+
+use super::socket::{SocketConfig, UdpSocket, UdpSocketFactory};
+use async_trait::async_trait;
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, Either};
+use futures::StreamExt;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The first port assigned to sockets bound to port 0.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+type Datagram = (Vec<u8>, SocketAddr);
+
+/// An in-memory network that the sockets of its `MemorySocketFactory` send datagrams over.
+/// Datagrams sent to a broadcast or multicast address are delivered to every socket bound to
+/// the destination port, other datagrams to the socket bound to the destination address or
+/// the unspecified address with the destination port.
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    state: Arc<Mutex<NetworkState>>,
+    timer: Arc<Timer>,
+}
+
+#[derive(Default)]
+struct NetworkState {
+    sockets: HashMap<SocketAddr, mpsc::UnboundedSender<Datagram>>,
+    hosts: HashMap<String, Vec<IpAddr>>,
+    multicast_events: Vec<(SocketAddr, MulticastEvent)>,
+    next_port: u16,
+}
+
+/// A multicast group joined or left by a socket on a `MemoryNetwork`,
+/// see `MemoryNetwork::multicast_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulticastEvent {
+    /// The IPv4 group was joined on the interface with the given address.
+    JoinV4(Ipv4Addr, Ipv4Addr),
+
+    /// The IPv4 group was left on the interface with the given address.
+    LeaveV4(Ipv4Addr, Ipv4Addr),
+
+    /// The IPv6 group was joined on the interface with the given index.
+    JoinV6(Ipv6Addr, u32),
+
+    /// The IPv6 group was left on the interface with the given index.
+    LeaveV6(Ipv6Addr, u32),
+}
+
+impl MemoryNetwork {
+    /// Creates an empty network.
+    pub fn new() -> MemoryNetwork {
+        MemoryNetwork::default()
+    }
+
+    /// Creates a socket factory that binds sockets on this network.
+    pub fn socket_factory(&self) -> MemorySocketFactory {
+        MemorySocketFactory {
+            network: self.clone(),
+        }
+    }
+
+    /// Registers a host name that the socket factories of this network resolve to the given
+    /// addresses, in the given order. Names are matched case-insensitively.
+    pub fn add_host(&self, name: &str, addrs: Vec<IpAddr>) {
+        let mut state = self.state.lock().unwrap();
+        state.hosts.insert(name.to_ascii_lowercase(), addrs);
+    }
+
+    /// Gets the multicast groups the sockets on this network joined and left so far, in order,
+    /// along with the address of the socket. Membership doesn't affect delivery, which reaches
+    /// every socket bound to the destination port.
+    pub fn multicast_events(&self) -> Vec<(SocketAddr, MulticastEvent)> {
+        self.state.lock().unwrap().multicast_events.clone()
+    }
+
+    /// Binds a socket on this network, e.g. for a fake browser service on port 1434.
+    /// Port 0 picks an unused port.
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<MemorySocket> {
+        let mut state = self.state.lock().unwrap();
+        let mut addr = addr;
+
+        if addr.port() == 0 {
+            loop {
+                let port = state.next_port.max(FIRST_EPHEMERAL_PORT);
+                state.next_port = port.checked_add(1).unwrap_or(FIRST_EPHEMERAL_PORT);
+                addr.set_port(port);
+                if !state.sockets.contains_key(&addr) {
+                    break;
+                }
+            }
+        } else if state.sockets.contains_key(&addr) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+
+        let (sender, receiver) = mpsc::unbounded();
+        state.sockets.insert(addr, sender);
+
+        Ok(MemorySocket {
+            network: self.clone(),
+            local_addr: addr,
+            peer: None,
+            receiver,
+        })
+    }
+
+    fn deliver(&self, source: SocketAddr, destination: &SocketAddr, data: &[u8]) {
+        let state = self.state.lock().unwrap();
+        let to_all = match destination.ip() {
+            IpAddr::V4(ip) => ip.is_broadcast() || ip.is_multicast(),
+            IpAddr::V6(ip) => ip.is_multicast(),
+        };
+
+        for (addr, sender) in &state.sockets {
+            let matches = addr.port() == destination.port()
+                && (to_all || addr.ip() == destination.ip() || addr.ip().is_unspecified());
+
+            if matches && *addr != source {
+                let _ = sender.unbounded_send((data.to_vec(), source));
+            }
+        }
+    }
+
+    /// Completes after the given duration, without depending on the timer of an async runtime.
+    /// All receive timeouts on the network share a single timer thread, which runs while any
+    /// of them is pending.
+    fn delay(&self, duration: Duration) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        let mut queue = self.timer.queue.lock().unwrap();
+        queue.pending.push(PendingDelay {
+            deadline: Instant::now() + duration,
+            sender,
+        });
+
+        if queue.running {
+            self.timer.changed.notify_one();
+        } else {
+            queue.running = true;
+            let timer = self.timer.clone();
+            std::thread::spawn(move || timer.run());
+        }
+
+        receiver
+    }
+}
+
+/// The timer shared by the sockets of a `MemoryNetwork`, which completes each delay
+/// at its deadline.
+#[derive(Default)]
+struct Timer {
+    queue: Mutex<TimerQueue>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct TimerQueue {
+    pending: BinaryHeap<PendingDelay>,
+    running: bool,
+}
+
+impl Timer {
+    /// Completes the pending delays as their deadlines pass, until none are left.
+    fn run(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            let deadline = match queue.pending.peek() {
+                Some(delay) => delay.deadline,
+                None => {
+                    queue.running = false;
+                    return;
+                }
+            };
+
+            let now = Instant::now();
+            if deadline <= now {
+                let delay = queue.pending.pop().unwrap();
+                let _ = delay.sender.send(());
+            } else {
+                queue = self.changed.wait_timeout(queue, deadline - now).unwrap().0;
+            }
+        }
+    }
+}
+
+/// A delay waiting in the `Timer`, ordered so the earliest deadline is at the top of the heap.
+struct PendingDelay {
+    deadline: Instant,
+    sender: oneshot::Sender<()>,
+}
+
+impl PartialEq for PendingDelay {
+    fn eq(&self, other: &PendingDelay) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for PendingDelay {}
+
+impl PartialOrd for PendingDelay {
+    fn partial_cmp(&self, other: &PendingDelay) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PendingDelay {
+    fn cmp(&self, other: &PendingDelay) -> Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+/// A `UdpSocketFactory` that binds sockets on a `MemoryNetwork`, see `MemoryNetwork::socket_factory`.
+#[derive(Clone)]
+pub struct MemorySocketFactory {
+    network: MemoryNetwork,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MemorySocketFactory {
+    type Socket = MemorySocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, addr: &SocketAddr) -> Result<MemorySocket, io::Error> {
+        self.network.bind(*addr)
+    }
+
+    async fn resolve(&mut self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let state = self.network.state.lock().unwrap();
+        state
+            .hosts
+            .get(&host.to_ascii_lowercase())
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        _config: &SocketConfig,
+    ) -> Result<MemorySocket, io::Error> {
+        self.network.bind(*addr)
+    }
+}
+
+/// A socket bound on a `MemoryNetwork`, unbound again when dropped.
+pub struct MemorySocket {
+    network: MemoryNetwork,
+    local_addr: SocketAddr,
+    peer: Option<SocketAddr>,
+    receiver: mpsc::UnboundedReceiver<Datagram>,
+}
+
+impl MemorySocket {
+    /// Gets the address this socket is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Receives the next datagram, from the connected peer only if connected.
+    async fn recv_datagram(&mut self) -> io::Result<Datagram> {
+        loop {
+            let (data, source) = self
+                .receiver
+                .next()
+                .await
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+
+            match self.peer {
+                Some(peer) if peer != source => continue,
+                _ => return Ok((data, source)),
+            }
+        }
+    }
+
+    fn record_multicast(&self, event: MulticastEvent) -> Result<(), io::Error> {
+        let mut state = self.network.state.lock().unwrap();
+        state.multicast_events.push((self.local_addr, event));
+        Ok(())
+    }
+
+    async fn recv_datagram_timeout(&mut self, timeout: Duration) -> io::Result<Option<Datagram>> {
+        let delay = self.network.delay(timeout);
+        let recv = Box::pin(self.recv_datagram());
+        match future::select(recv, delay).await {
+            Either::Left((received, _)) => received.map(Some),
+            Either::Right(_) => Ok(None),
+        }
+    }
+}
+
+impl Drop for MemorySocket {
+    fn drop(&mut self) {
+        self.network
+            .state
+            .lock()
+            .unwrap()
+            .sockets
+            .remove(&self.local_addr);
+    }
+}
+
+/// Copies a received datagram into `buf`, truncating it like a real socket would.
+fn copy_datagram(buf: &mut [u8], data: &[u8]) -> usize {
+    let len = std::cmp::min(buf.len(), data.len());
+    buf[..len].copy_from_slice(&data[..len]);
+    len
+}
+
+#[async_trait]
+impl UdpSocket for MemorySocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, addr: &SocketAddr) -> Result<(), io::Error> {
+        self.peer = Some(*addr);
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        let peer = self
+            .peer
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        self.send_to(buf, &peer).await
+    }
+
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.network.deliver(self.local_addr, addr, buf);
+        Ok(buf.len())
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let (data, _) = self.recv_datagram().await?;
+        Ok(copy_datagram(buf, &data))
+    }
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        let (data, source) = self.recv_datagram().await?;
+        Ok((copy_datagram(buf, &data), source))
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        let received = self.recv_datagram_timeout(timeout).await?;
+        Ok(received.map(|(data, _)| copy_datagram(buf, &data)))
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        let received = self.recv_datagram_timeout(timeout).await?;
+        Ok(received.map(|(data, source)| (copy_datagram(buf, &data), source)))
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(self.local_addr)
+    }
+
+    fn join_multicast_v4(&self, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<(), io::Error> {
+        self.record_multicast(MulticastEvent::JoinV4(*group, *interface))
+    }
+
+    fn leave_multicast_v4(&self, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<(), io::Error> {
+        self.record_multicast(MulticastEvent::LeaveV4(*group, *interface))
+    }
+
+    fn join_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), io::Error> {
+        self.record_multicast(MulticastEvent::JoinV6(*group, interface))
+    }
+
+    fn leave_multicast_v6(&self, group: &Ipv6Addr, interface: u32) -> Result<(), io::Error> {
+        self.record_multicast(MulticastEvent::LeaveV6(*group, interface))
+    }
+}
+
+/// Builds an SVR_RESP datagram with the given response data, e.g.
+/// `ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;`.
+pub fn svr_resp(data: &str) -> Vec<u8> {
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    response.extend_from_slice(data.as_bytes());
+    response
+}
+
+/// Acts as a minimal browser service, answering the next request received on `socket`
+/// with the canned `response`. Returns the request and the address it was received from.
+///
+/// # Examples
+/// ```
+/// use mssql_browser::custom_socket::browse_host;
+/// use mssql_browser::test_util::{answer, svr_resp, MemoryNetwork};
+/// use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+///
+/// let network = MemoryNetwork::new();
+/// let server_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+/// let mut server = network.bind(SocketAddr::new(server_ip, 1434)).unwrap();
+/// let response = svr_resp("ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;");
+///
+/// let mut factory = network.socket_factory();
+/// let (instances, request) = futures::executor::block_on(async {
+///     futures::join!(browse_host(server_ip, &mut factory), answer(&mut server, &response))
+/// });
+///
+/// assert_eq!(request.unwrap().0, vec![0x03]);
+/// let instance = instances.unwrap().next().unwrap().unwrap();
+/// assert_eq!(instance.instance_name, "SQLEXPRESS");
+/// ```
+pub async fn answer(socket: &mut MemorySocket, response: &[u8]) -> io::Result<Datagram> {
+    let (request, source) = socket.recv_datagram().await?;
+    socket.send_to(response, &source).await?;
+    Ok((request, source))
+}
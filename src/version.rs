@@ -0,0 +1,276 @@
+use super::info::InstanceInfo;
+
+/// A parsed VERSION_STRING as sent by the server, e.g. `15.0.2000.5`.
+/// Versions are ordered by major, minor, build and revision, so later builds compare greater.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct SqlServerVersion {
+    /// The major version, e.g. 15 for SQL Server 2019
+    pub major: u32,
+
+    /// The minor version, e.g. 50 for SQL Server 2008 R2
+    pub minor: u32,
+
+    /// The build number
+    pub build: u32,
+
+    /// The revision of the build
+    pub revision: u32,
+}
+
+impl SqlServerVersion {
+    /// Parses a version string consisting of up to four dot separated numbers.
+    /// Missing trailing components are treated as zero.
+    pub fn parse(version: &str) -> Option<SqlServerVersion> {
+        let mut parts = [0u32; 4];
+
+        for (i, part) in version.split('.').enumerate() {
+            *parts.get_mut(i)? = part.parse().ok()?;
+        }
+
+        Some(SqlServerVersion {
+            major: parts[0],
+            minor: parts[1],
+            build: parts[2],
+            revision: parts[3],
+        })
+    }
+
+    /// Determines whether this is the given build or a later one, e.g. to flag instances
+    /// below a patch baseline. Note that this compares builds across releases as well.
+    pub fn at_least(&self, other: &SqlServerVersion) -> bool {
+        self >= other
+    }
+
+    /// Gets the marketing name of the release, e.g. `SQL Server 2019`.
+    pub fn product_name(&self) -> Option<&'static str> {
+        match (self.major, self.minor) {
+            (16, _) => Some("SQL Server 2022"),
+            (15, _) => Some("SQL Server 2019"),
+            (14, _) => Some("SQL Server 2017"),
+            (13, _) => Some("SQL Server 2016"),
+            (12, _) => Some("SQL Server 2014"),
+            (11, _) => Some("SQL Server 2012"),
+            (10, 50) => Some("SQL Server 2008 R2"),
+            (10, _) => Some("SQL Server 2008"),
+            (9, _) => Some("SQL Server 2005"),
+            (8, _) => Some("SQL Server 2000"),
+            _ => None,
+        }
+    }
+
+    /// Gets the service pack or cumulative update level of this build, e.g. `2019 CU18`.
+    ///
+    /// This is a best-effort lookup in a table of well-known builds which reports the most
+    /// recent known update at or below this build, so security fixes released on top of an
+    /// update report that update. Returns `None` for releases that aren't known and
+    /// for builds newer than the most recent known update.
+    #[cfg(feature = "update-levels")]
+    pub fn update_level(&self) -> Option<String> {
+        let (release, builds) = update_levels::BUILDS
+            .iter()
+            .find(|(major, minor, _, _)| *major == self.major && *minor == self.minor)
+            .map(|(_, _, release, builds)| (release, builds))?;
+
+        let current = (self.build, self.revision);
+        let index = builds
+            .iter()
+            .rposition(|(build, revision, _)| (*build, *revision) <= current)?;
+
+        // Anything past the newest known update could belong to any later update
+        let (build, revision, level) = builds[index];
+        if index == builds.len() - 1 && (build, revision) != current {
+            return None;
+        }
+
+        Some(format!("{} {}", release, level))
+    }
+}
+
+impl std::fmt::Display for SqlServerVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+impl InstanceInfo {
+    /// Parses the version string sent by the server.
+    /// Returns `None` if it isn't a valid version.
+    pub fn parsed_version(&self) -> Option<SqlServerVersion> {
+        SqlServerVersion::parse(&self.version)
+    }
+
+    /// Guesses whether the instance is run by a cloud service, such as Azure SQL Managed Instance,
+    /// rather than being a self-hosted SQL Server, e.g. to categorize discovered instances.
+    ///
+    /// This is a best-effort heuristic, as the protocol doesn't report it: the instance looks
+    /// managed if it reports build 12.0.2000, which Azure SQL reports regardless of the engine
+    /// actually deployed, or if it lists no endpoint at all, as managed services don't expose
+    /// the protocols of the underlying host. Self-hosted instances can match as well, e.g. the
+    /// RTM release of SQL Server 2014 is build 12.0.2000.8 too.
+    pub fn looks_managed(&self) -> bool {
+        let azure_version = match self.parsed_version() {
+            Some(version) => (version.major, version.minor, version.build) == (12, 0, 2000),
+            None => false,
+        };
+
+        azure_version || self.endpoint_count() == 0
+    }
+}
+
+#[cfg(feature = "update-levels")]
+mod update_levels {
+    type Release = (
+        u32,
+        u32,
+        &'static str,
+        &'static [(u32, u32, &'static str)],
+    );
+
+    /// Well-known builds per release, in ascending order
+    pub(super) const BUILDS: &[Release] = &[
+        (
+            16,
+            0,
+            "2022",
+            &[
+                (1000, 6, "RTM"),
+                (4003, 1, "CU1"),
+                (4015, 1, "CU2"),
+                (4025, 1, "CU3"),
+                (4035, 4, "CU4"),
+                (4045, 3, "CU5"),
+                (4055, 4, "CU6"),
+                (4065, 3, "CU7"),
+                (4075, 1, "CU8"),
+                (4085, 2, "CU9"),
+                (4095, 4, "CU10"),
+            ],
+        ),
+        (
+            15,
+            0,
+            "2019",
+            &[
+                (2000, 5, "RTM"),
+                (4003, 23, "CU1"),
+                (4013, 40, "CU2"),
+                (4023, 6, "CU3"),
+                (4033, 1, "CU4"),
+                (4043, 16, "CU5"),
+                (4053, 23, "CU6"),
+                (4063, 15, "CU7"),
+                (4073, 23, "CU8"),
+                (4102, 2, "CU9"),
+                (4123, 1, "CU10"),
+                (4138, 2, "CU11"),
+                (4153, 1, "CU12"),
+                (4178, 1, "CU13"),
+                (4188, 2, "CU14"),
+                (4198, 2, "CU15"),
+                (4223, 1, "CU16"),
+                (4249, 2, "CU17"),
+                (4261, 1, "CU18"),
+            ],
+        ),
+        (
+            14,
+            0,
+            "2017",
+            &[
+                (1000, 169, "RTM"),
+                (3006, 16, "CU1"),
+                (3008, 27, "CU2"),
+                (3015, 40, "CU3"),
+                (3022, 28, "CU4"),
+                (3023, 8, "CU5"),
+                (3025, 34, "CU6"),
+                (3026, 27, "CU7"),
+                (3029, 16, "CU8"),
+                (3030, 27, "CU9"),
+                (3037, 1, "CU10"),
+                (3038, 14, "CU11"),
+                (3045, 24, "CU12"),
+                (3048, 4, "CU13"),
+                (3076, 1, "CU14"),
+                (3162, 1, "CU15"),
+                (3223, 3, "CU16"),
+                (3238, 1, "CU17"),
+                (3257, 3, "CU18"),
+                (3281, 6, "CU19"),
+                (3294, 2, "CU20"),
+                (3335, 7, "CU21"),
+                (3356, 20, "CU22"),
+                (3381, 3, "CU23"),
+                (3391, 2, "CU24"),
+                (3401, 7, "CU25"),
+                (3411, 3, "CU26"),
+                (3421, 10, "CU27"),
+                (3430, 2, "CU28"),
+                (3436, 1, "CU29"),
+                (3451, 2, "CU30"),
+                (3456, 2, "CU31"),
+            ],
+        ),
+        (
+            13,
+            0,
+            "2016",
+            &[
+                (1601, 5, "RTM"),
+                (4001, 0, "SP1"),
+                (5026, 0, "SP2"),
+                (6300, 2, "SP3"),
+            ],
+        ),
+        (
+            12,
+            0,
+            "2014",
+            &[
+                (2000, 8, "RTM"),
+                (4100, 1, "SP1"),
+                (5000, 0, "SP2"),
+                (6024, 0, "SP3"),
+            ],
+        ),
+        (
+            11,
+            0,
+            "2012",
+            &[
+                (2100, 60, "RTM"),
+                (3000, 0, "SP1"),
+                (5058, 0, "SP2"),
+                (6020, 0, "SP3"),
+                (7001, 0, "SP4"),
+            ],
+        ),
+        (
+            10,
+            50,
+            "2008 R2",
+            &[
+                (1600, 1, "RTM"),
+                (2500, 0, "SP1"),
+                (4000, 0, "SP2"),
+                (6000, 34, "SP3"),
+            ],
+        ),
+        (
+            10,
+            0,
+            "2008",
+            &[
+                (1600, 22, "RTM"),
+                (2531, 0, "SP1"),
+                (4000, 0, "SP2"),
+                (5500, 0, "SP3"),
+                (6000, 29, "SP4"),
+            ],
+        ),
+    ];
+}
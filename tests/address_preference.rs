@@ -0,0 +1,20 @@
+use mssql_browser::AddressPreference;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const V4: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+const V6: IpAddr = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1));
+
+#[test]
+fn only_variants_filter_by_family() {
+    assert_eq!(AddressPreference::V4Only.select(&[V6, V4]), vec![V4]);
+    assert_eq!(AddressPreference::V6Only.select(&[V6, V4]), vec![V6]);
+    assert!(AddressPreference::V6Only.select(&[V4]).is_empty());
+}
+
+#[test]
+fn prefer_variants_fall_back_to_the_other_family() {
+    assert_eq!(AddressPreference::PreferV4.select(&[V6, V4]), vec![V4]);
+    assert_eq!(AddressPreference::PreferV4.select(&[V6]), vec![V6]);
+    assert_eq!(AddressPreference::PreferV6.select(&[V6, V4]), vec![V6]);
+    assert_eq!(AddressPreference::PreferV6.select(&[V4]), vec![V4]);
+}
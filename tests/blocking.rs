@@ -0,0 +1,79 @@
+use mssql_browser::custom_socket::{self, BlockingSocketFactory, UdpSocket};
+use mssql_browser::{BrowserError, BrowserOptions};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+#[test]
+fn read_timeout_is_reported_as_none() {
+    let mut socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut buffer = [0u8; 16];
+
+    let started_at = Instant::now();
+    let received = futures::executor::block_on(UdpSocket::recv_from_timeout(
+        &mut socket,
+        &mut buffer,
+        Duration::from_millis(50),
+    ))
+    .unwrap();
+
+    assert!(received.is_none());
+    assert!(started_at.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn non_responding_host_times_out_promptly() {
+    // Without a connection, port unreachable messages aren't reported on the socket
+    let options = BrowserOptions::new()
+        .connected(false)
+        .timeout(Some(Duration::from_millis(100)));
+    let remote_addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    let started_at = Instant::now();
+    let result = futures::executor::block_on(custom_socket::browse_host_with_options(
+        remote_addr,
+        &options,
+        &mut BlockingSocketFactory::new(),
+    ));
+
+    match result {
+        Err(BrowserError::Timeout(addr)) => assert_eq!(addr, SocketAddr::new(remote_addr, 1434)),
+        Err(e) => panic!("expected Timeout, got {:?}", e),
+        Ok(_) => panic!("expected Timeout, got an iterator"),
+    }
+    assert!(started_at.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn session_requests_complete_on_blocking_sockets() {
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_port = server.local_addr().unwrap().port();
+    let responder = std::thread::spawn(move || {
+        let body =
+            "ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;";
+        let mut response = vec![0x05];
+        response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+        response.extend_from_slice(body.as_bytes());
+
+        let mut buffer = [0u8; 64];
+        let (_, client) = server.recv_from(&mut buffer).unwrap();
+        server.send_to(&response, client).unwrap();
+    });
+
+    let options = BrowserOptions::new()
+        .browser_port(server_port)
+        .timeout(Some(Duration::from_secs(1)));
+    let mut factory = BlockingSocketFactory::new();
+
+    let mut session =
+        futures::executor::block_on(custom_socket::browser_session(&options, &mut factory))
+            .unwrap();
+
+    let started_at = Instant::now();
+    let info = futures::executor::block_on(
+        session.instance(IpAddr::V4(Ipv4Addr::LOCALHOST), "SQLEXPRESS"),
+    );
+    responder.join().unwrap();
+
+    assert_eq!(info.unwrap().instance_name, "SQLEXPRESS");
+    assert!(started_at.elapsed() < Duration::from_secs(1));
+}
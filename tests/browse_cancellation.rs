@@ -0,0 +1,172 @@
+use async_trait::async_trait;
+use futures::FutureExt;
+use mssql_browser::custom_socket::{self, UdpSocket, UdpSocketFactory};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// A queued datagram, or `None` to stall the next receive once
+type Step = Option<(Vec<u8>, SocketAddr)>;
+
+struct MockSocketFactory {
+    steps: VecDeque<Step>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MockSocketFactory {
+    type Socket = MockSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<MockSocket, io::Error> {
+        Ok(MockSocket {
+            steps: std::mem::take(&mut self.steps),
+        })
+    }
+}
+
+struct MockSocket {
+    steps: VecDeque<Step>,
+}
+
+#[async_trait]
+impl UdpSocket for MockSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        Ok(buf.len())
+    }
+
+    async fn send_to(&mut self, buf: &[u8], _addr: &SocketAddr) -> Result<usize, io::Error> {
+        Ok(buf.len())
+    }
+
+    async fn recv(&mut self, _buf: &mut [u8]) -> Result<usize, io::Error> {
+        unimplemented!()
+    }
+
+    async fn recv_from(&mut self, _buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        unimplemented!()
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        match self.steps.pop_front() {
+            Some(Some((response, source))) => {
+                buf[..response.len()].copy_from_slice(&response);
+                Ok(Some((response.len(), source)))
+            }
+            Some(None) => futures::future::pending().await,
+            None => Ok(None),
+        }
+    }
+}
+
+fn svr_resp(server_name: &str) -> Vec<u8> {
+    let body = format!(
+        "ServerName;{};InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+        server_name
+    );
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+fn responder(last_octet: u8) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_octet)), 1434)
+}
+
+#[test]
+fn next_can_be_dropped_while_receiving() {
+    let mut factory = MockSocketFactory {
+        steps: vec![
+            Some((svr_resp("SRV1"), responder(1))),
+            None,
+            Some((svr_resp("SRV2"), responder(2))),
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    futures::executor::block_on(async {
+        let mut iterator = custom_socket::browse(IpAddr::V4(Ipv4Addr::BROADCAST), &mut factory)
+            .await
+            .unwrap();
+
+        let first = iterator.next().await.unwrap().unwrap();
+        assert_eq!(first.server_name, "SRV1");
+
+        // Poll once while the receive is pending, then drop the future
+        assert!(iterator.next().now_or_never().is_none());
+
+        let second = iterator.next().await.unwrap().unwrap();
+        assert_eq!(second.server_name, "SRV2");
+
+        assert!(iterator.next().await.unwrap().is_none());
+    });
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn dropping_iterator_while_receiving_is_traced() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    struct CountingSubscriber(Arc<AtomicUsize>);
+
+    impl tracing::Subscriber for CountingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    let events = Arc::new(AtomicUsize::new(0));
+    let subscriber = CountingSubscriber(events.clone());
+    let mut factory = MockSocketFactory {
+        steps: vec![None].into_iter().collect(),
+    };
+
+    tracing::subscriber::with_default(subscriber, || {
+        futures::executor::block_on(async {
+            let mut iterator = custom_socket::browse(IpAddr::V4(Ipv4Addr::BROADCAST), &mut factory)
+                .await
+                .unwrap();
+
+            assert!(iterator.next().now_or_never().is_none());
+            assert_eq!(events.load(Ordering::SeqCst), 0);
+
+            drop(iterator);
+            assert_eq!(events.load(Ordering::SeqCst), 1);
+        });
+    });
+}
@@ -0,0 +1,19 @@
+#![cfg(feature = "tokio")]
+
+use mssql_browser::{browse_to_channel, BrowserOptions};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+#[tokio::test]
+async fn task_stops_when_the_receiver_is_dropped() {
+    // Nothing answers on the loopback address, so browsing would go on until the timeout
+    let options = BrowserOptions::new().timeout(Some(Duration::from_secs(30)));
+    let (task, receiver) = browse_to_channel(IpAddr::V4(Ipv4Addr::LOCALHOST), &options);
+    drop(receiver);
+
+    let stopped = tokio::time::timeout(Duration::from_secs(2), task).await;
+    assert!(
+        stopped.is_ok(),
+        "the task kept browsing after the receiver was dropped"
+    );
+}
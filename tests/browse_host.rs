@@ -0,0 +1,251 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, PreboundSocketFactory, UdpSocket, UdpSocketFactory};
+use mssql_browser::{BrowserError, BrowserProtocolError, BrowserProtocolToken};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct MockSocketFactory {
+    sent: Arc<Mutex<Vec<(Vec<u8>, SocketAddr)>>>,
+    response: Vec<u8>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MockSocketFactory {
+    type Socket = MockSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<MockSocket, io::Error> {
+        Ok(MockSocket {
+            sent: self.sent.clone(),
+            response: Some(std::mem::take(&mut self.response)),
+        })
+    }
+}
+
+/// Records the requests sent and answers with a single canned response
+struct MockSocket {
+    sent: Arc<Mutex<Vec<(Vec<u8>, SocketAddr)>>>,
+    response: Option<Vec<u8>>,
+}
+
+impl MockSocket {
+    fn respond(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let response = self.response.take()?;
+        buf[..response.len()].copy_from_slice(&response);
+        Some(response.len())
+    }
+}
+
+#[async_trait]
+impl UdpSocket for MockSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn send(&mut self, _buf: &[u8]) -> Result<usize, io::Error> {
+        unimplemented!()
+    }
+
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.sent.lock().unwrap().push((buf.to_vec(), *addr));
+        Ok(buf.len())
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        Ok(self.respond(buf).unwrap_or(0))
+    }
+
+    async fn recv_from(&mut self, _buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        unimplemented!()
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        Ok(self.respond(buf))
+    }
+}
+
+fn svr_resp(body: &str) -> Vec<u8> {
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+#[test]
+fn second_instance_is_parsed_from_where_the_first_ends() {
+    let body = "ServerName;SRV1;InstanceName;MSSQLSERVER;IsClustered;No;Version;15.0.2000.5;tcp;1433;;\
+                ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;14.0.1000.169;tcp;50123;";
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+
+    let mut factory = MockSocketFactory {
+        sent: Arc::new(Mutex::new(Vec::new())),
+        response,
+    };
+    let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    let mut instances =
+        futures::executor::block_on(custom_socket::browse_host(host, &mut factory)).unwrap();
+
+    let first = instances.next().unwrap().unwrap();
+    assert_eq!(first.instance_name, "MSSQLSERVER");
+    assert_eq!(first.tcp_info.unwrap().port, 1433);
+
+    // The second record starts right after the `;;` ending the first one
+    let second = instances.next().unwrap().unwrap();
+    assert_eq!(second.server_name, "SRV1");
+    assert_eq!(second.instance_name, "SQLEXPRESS");
+    assert_eq!(second.version, "14.0.1000.169");
+    assert_eq!(second.tcp_info.unwrap().port, 50123);
+
+    // The last record ends with a single semicolon, which must not be
+    // counted past the end of the datagram
+    assert!(instances.next().unwrap().is_none());
+}
+
+#[test]
+fn browse_host_returns_every_instance_on_the_host() {
+    let body = "ServerName;SRV1;InstanceName;MSSQLSERVER;IsClustered;No;Version;15.0.2000.5;tcp;1433;np;\\\\SRV1\\pipe\\sql\\query;;\
+                ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;14.0.1000.169;tcp;50123;;\
+                ServerName;SRV1;InstanceName;REPORTING;IsClustered;Yes;Version;13.0.1601.5;np;\\\\SRV1\\pipe\\MSSQL$REPORTING\\sql\\query;;";
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let mut factory = MockSocketFactory {
+        sent: sent.clone(),
+        response: svr_resp(body),
+    };
+    let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    let instances = futures::executor::block_on(custom_socket::browse_host(host, &mut factory))
+        .unwrap()
+        .into_vec()
+        .unwrap();
+
+    assert_eq!(
+        *sent.lock().unwrap(),
+        vec![(vec![0x03], SocketAddr::new(host, 1434))]
+    );
+
+    let names = instances
+        .iter()
+        .map(|instance| instance.instance_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["MSSQLSERVER", "SQLEXPRESS", "REPORTING"]);
+
+    assert_eq!(instances[0].tcp_info.as_ref().unwrap().port, 1433);
+    assert_eq!(instances[1].tcp_info.as_ref().unwrap().port, 50123);
+    assert!(instances[2].tcp_info.is_none());
+    assert!(instances[2].is_clustered);
+    assert_eq!(
+        instances[2].np_info.as_ref().unwrap().name,
+        "\\\\SRV1\\pipe\\MSSQL$REPORTING\\sql\\query"
+    );
+    assert!(instances.iter().all(|instance| instance.addr == host));
+
+    assert_eq!(instances[0].protocols(), vec!["np", "tcp"]);
+    assert_eq!(instances[1].endpoint_count(), 1);
+}
+
+#[test]
+fn into_partial_skips_invalid_instances() {
+    let body = "ServerName;SRV1;InstanceName;MSSQLSERVER;IsClustered;No;Version;15.0.2000.5;tcp;1433;;\
+                ServerName;SRV1;InstanceName;BROKEN;IsClustered;Maybe;Version;14.0.1000.169;tcp;50123;;\
+                ServerName;SRV1;InstanceName;REPORTING;IsClustered;Yes;Version;13.0.1601.5;tcp;99999;;\
+                ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;14.0.1000.169;tcp;50124;;";
+
+    let mut factory = MockSocketFactory {
+        sent: Arc::new(Mutex::new(Vec::new())),
+        response: svr_resp(body),
+    };
+    let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    let (instances, errors) =
+        futures::executor::block_on(custom_socket::browse_host(host, &mut factory))
+            .unwrap()
+            .into_partial();
+
+    let names = instances
+        .iter()
+        .map(|instance| instance.instance_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["MSSQLSERVER", "SQLEXPRESS"]);
+
+    assert_eq!(errors.len(), 2);
+    match &errors[0] {
+        BrowserProtocolError::InvalidInstance { offset, record, .. } => {
+            assert_eq!(&body[*offset - 3..][..record.len()], record);
+            assert!(record.starts_with("ServerName;SRV1;InstanceName;BROKEN;"));
+            assert!(record.ends_with(";;"));
+        }
+        e => panic!("expected InvalidInstance, got {:?}", e),
+    }
+    match errors[1].reason() {
+        BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::TcpPort,
+            ..
+        } => {}
+        e => panic!("expected an invalid TCP port, got {:?}", e),
+    }
+}
+
+#[test]
+fn into_partial_resumes_after_the_record_terminator() {
+    // The record following the invalid one lists its fields in a different order
+    let body = "ServerName;SRV1;InstanceName;BROKEN;IsClustered;Maybe;Version;14.0.1000.169;tcp;50123;;\
+                InstanceName;SQLEXPRESS;ServerName;SRV1;IsClustered;No;Version;14.0.1000.169;tcp;50124;;";
+
+    let mut factory = MockSocketFactory {
+        sent: Arc::new(Mutex::new(Vec::new())),
+        response: svr_resp(body),
+    };
+    let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    let (instances, errors) =
+        futures::executor::block_on(custom_socket::browse_host(host, &mut factory))
+            .unwrap()
+            .into_partial();
+
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].instance_name, "SQLEXPRESS");
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn browse_host_uses_prebound_socket() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let socket = MockSocket {
+        sent: sent.clone(),
+        response: Some(svr_resp(
+            "ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+        )),
+    };
+    let mut factory = PreboundSocketFactory::new(socket);
+    let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    let instance = futures::executor::block_on(custom_socket::browse_host(host, &mut factory))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(instance.instance_name, "SQLEXPRESS");
+    assert_eq!(sent.lock().unwrap().len(), 1);
+
+    // The socket was handed out to the first operation
+    match futures::executor::block_on(custom_socket::browse_host(host, &mut factory)) {
+        Err(BrowserError::BindFailed(_)) => {}
+        other => panic!("expected BindFailed, got {:?}", other.map(|_| ())),
+    }
+}
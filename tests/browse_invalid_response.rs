@@ -0,0 +1,235 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, UdpSocket, UdpSocketFactory};
+use mssql_browser::{BrowserError, BrowserOptions, BrowserProtocolError};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+struct MockSocketFactory {
+    responses: VecDeque<(Vec<u8>, SocketAddr)>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MockSocketFactory {
+    type Socket = MockSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<MockSocket, io::Error> {
+        Ok(MockSocket {
+            responses: std::mem::take(&mut self.responses),
+        })
+    }
+}
+
+/// Hands out the queued responses one at a time, then times out
+struct MockSocket {
+    responses: VecDeque<(Vec<u8>, SocketAddr)>,
+}
+
+impl MockSocket {
+    fn respond(&mut self, buf: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        let (response, source) = self.responses.pop_front()?;
+        buf[..response.len()].copy_from_slice(&response);
+        Some((response.len(), source))
+    }
+}
+
+#[async_trait]
+impl UdpSocket for MockSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        Ok(buf.len())
+    }
+
+    async fn send_to(&mut self, buf: &[u8], _addr: &SocketAddr) -> Result<usize, io::Error> {
+        Ok(buf.len())
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        Ok(self.respond(buf).map_or(0, |(len, _)| len))
+    }
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        self.respond(buf)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::WouldBlock))
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        Ok(self.respond(buf).map(|(len, _)| len))
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        Ok(self.respond(buf))
+    }
+}
+
+fn svr_resp(body: &str) -> Vec<u8> {
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+fn responder(last_octet: u8) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_octet)), 1434)
+}
+
+#[test]
+fn invalid_response_does_not_abort_browse() {
+    let mut factory = MockSocketFactory {
+        responses: vec![
+            (
+                svr_resp(
+                    "ServerName;SRV1;InstanceName;A;IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+                ),
+                responder(1),
+            ),
+            (vec![0x05, 0x00, 0x00, b'x'], responder(2)),
+            (
+                svr_resp(
+                    "ServerName;SRV3;InstanceName;B;IsClustered;No;Version;15.0.2000.5;tcp;1434;;",
+                ),
+                responder(3),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    futures::executor::block_on(async {
+        let mut iterator = custom_socket::browse(IpAddr::V4(Ipv4Addr::BROADCAST), &mut factory)
+            .await
+            .unwrap();
+
+        let first = iterator.next().await.unwrap().unwrap();
+        assert_eq!(first.server_name, "SRV1");
+
+        match iterator.next().await {
+            Err(BrowserError::ProtocolError(e)) => {
+                assert_eq!(e.response(), Some(&[0x05, 0x00, 0x00, b'x'][..]));
+                match e.reason() {
+                    BrowserProtocolError::LengthMismatch { .. } => {}
+                    other => panic!("expected a length mismatch, got {:?}", other),
+                }
+            }
+            other => panic!("expected a protocol error, got {:?}", other),
+        }
+        assert_eq!(iterator.last_source(), responder(2));
+
+        let second = iterator.next().await.unwrap().unwrap();
+        assert_eq!(second.server_name, "SRV3");
+
+        assert!(iterator.next().await.unwrap().is_none());
+    });
+}
+
+#[test]
+fn length_mismatch_is_accepted_when_lenient() {
+    let body = "ServerName;SRV1;InstanceName;A;IsClustered;No;Version;15.0.2000.5;tcp;1433;;";
+    // The length wrongly includes the header
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16 + 3).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+
+    let mut factory = MockSocketFactory {
+        responses: vec![(response, responder(1))].into_iter().collect(),
+    };
+    let options = BrowserOptions::new().lenient_length(true);
+
+    futures::executor::block_on(async {
+        let mut iterator = custom_socket::browse_with_options(
+            IpAddr::V4(Ipv4Addr::BROADCAST),
+            &options,
+            &mut factory,
+        )
+        .await
+        .unwrap();
+
+        let instance = iterator.next().await.unwrap().unwrap();
+        assert_eq!(instance.server_name, "SRV1");
+
+        let header = instance.header.unwrap();
+        assert!(!header.length_matches());
+        assert_eq!(header.length as usize, body.len() + 3);
+        assert_eq!(header.datagram_length, body.len() + 3);
+    });
+}
+
+#[test]
+fn response_split_across_datagrams_is_parsed_per_datagram() {
+    let mut factory = MockSocketFactory {
+        responses: vec![
+            (
+                svr_resp(
+                    "ServerName;SRV1;InstanceName;A;IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+                ),
+                responder(1),
+            ),
+            (
+                svr_resp(
+                    "ServerName;SRV1;InstanceName;B;IsClustered;No;Version;15.0.2000.5;tcp;1434;;",
+                ),
+                responder(1),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    let instances = futures::executor::block_on(async {
+        let mut iterator = custom_socket::browse(IpAddr::V4(Ipv4Addr::BROADCAST), &mut factory)
+            .await
+            .unwrap();
+
+        let mut instances = Vec::new();
+        while let Some(instance) = iterator.next().await.unwrap() {
+            assert_eq!(instance.source, Some(responder(1)));
+            instances.push(instance.instance_name);
+        }
+        instances
+    });
+
+    assert_eq!(instances, vec!["A", "B"]);
+}
+
+#[test]
+fn header_only_instance_response_is_reported_as_empty() {
+    let mut factory = MockSocketFactory {
+        responses: vec![(vec![0x05, 0x00, 0x00], responder(1))]
+            .into_iter()
+            .collect(),
+    };
+
+    let result = futures::executor::block_on(custom_socket::browse_instance(
+        responder(1).ip(),
+        "SQLEXPRESS",
+        &mut factory,
+    ));
+
+    match result {
+        Err(BrowserError::ProtocolError(e)) => {
+            assert!(matches!(e.reason(), BrowserProtocolError::EmptyResponse));
+            assert_eq!(e.response(), Some(&[0x05, 0x00, 0x00][..]));
+        }
+        Err(e) => panic!("expected EmptyResponse, got {:?}", e),
+        Ok(info) => panic!("expected EmptyResponse, got {:?}", info),
+    }
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, UdpSocket, UdpSocketFactory};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct MockSocketFactory {
+    sent_to: Arc<Mutex<Vec<SocketAddr>>>,
+    responses: VecDeque<(Vec<u8>, SocketAddr)>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MockSocketFactory {
+    type Socket = MockSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<MockSocket, io::Error> {
+        Ok(MockSocket {
+            sent_to: self.sent_to.clone(),
+            responses: std::mem::take(&mut self.responses),
+        })
+    }
+}
+
+/// A socket that refuses to be connected, as a connected socket
+/// would only receive the datagrams of a single responder
+struct MockSocket {
+    sent_to: Arc<Mutex<Vec<SocketAddr>>>,
+    responses: VecDeque<(Vec<u8>, SocketAddr)>,
+}
+
+#[async_trait]
+impl UdpSocket for MockSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        panic!("browse must not connect its socket")
+    }
+
+    async fn send(&mut self, _buf: &[u8]) -> Result<usize, io::Error> {
+        panic!("browse must not connect its socket")
+    }
+
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.sent_to.lock().unwrap().push(*addr);
+        Ok(buf.len())
+    }
+
+    async fn recv(&mut self, _buf: &mut [u8]) -> Result<usize, io::Error> {
+        panic!("browse must not connect its socket")
+    }
+
+    async fn recv_from(&mut self, _buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        unimplemented!()
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        _buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        panic!("browse must not connect its socket")
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        Ok(self.responses.pop_front().map(|(response, source)| {
+            buf[..response.len()].copy_from_slice(&response);
+            (response.len(), source)
+        }))
+    }
+}
+
+fn svr_resp(server_name: &str) -> Vec<u8> {
+    let body = format!(
+        "ServerName;{};InstanceName;MSSQLSERVER;IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+        server_name
+    );
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+fn responder(last_octet: u8) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, last_octet)), 1434)
+}
+
+#[test]
+fn browse_receives_from_every_responder() {
+    let sent_to = Arc::new(Mutex::new(Vec::new()));
+    let mut factory = MockSocketFactory {
+        sent_to: sent_to.clone(),
+        responses: (1..=3)
+            .map(|i| (svr_resp(&format!("SRV{}", i)), responder(i)))
+            .collect(),
+    };
+
+    let sources = futures::executor::block_on(async {
+        let mut iterator = custom_socket::browse(IpAddr::V4(Ipv4Addr::BROADCAST), &mut factory)
+            .await
+            .unwrap();
+
+        let mut sources = Vec::new();
+        while let Some(instance) = iterator.next().await.unwrap() {
+            assert_eq!(instance.addr, instance.source.unwrap().ip());
+            sources.push(instance.source.unwrap());
+        }
+        sources
+    });
+
+    assert_eq!(sources, vec![responder(1), responder(2), responder(3)]);
+    assert_eq!(
+        *sent_to.lock().unwrap(),
+        vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 1434)]
+    );
+}
@@ -0,0 +1,164 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, UdpSocket, UdpSocketFactory};
+use mssql_browser::{BrowserError, BrowserOptions, Clock, ManualClock, RetryPolicy};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const RESPONSE: &[u8] =
+    b"\x05\x55\x00ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;";
+
+struct SimulatedSocketFactory {
+    clock: ManualClock,
+    latency: Option<Duration>,
+    waits: Arc<Mutex<Vec<Duration>>>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for SimulatedSocketFactory {
+    type Socket = SimulatedSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<SimulatedSocket, io::Error> {
+        Ok(SimulatedSocket {
+            clock: self.clock.clone(),
+            latency: self.latency,
+            waits: self.waits.clone(),
+        })
+    }
+}
+
+/// Answers every request after the configured latency, or never, by advancing the clock
+/// instead of waiting. Records the timeout of every receive.
+struct SimulatedSocket {
+    clock: ManualClock,
+    latency: Option<Duration>,
+    waits: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl SimulatedSocket {
+    fn wait(&mut self, buf: &mut [u8], timeout: Duration) -> Option<usize> {
+        self.waits.lock().unwrap().push(timeout);
+        match self.latency {
+            Some(latency) if latency <= timeout => {
+                self.clock.advance(latency);
+                buf[..RESPONSE.len()].copy_from_slice(RESPONSE);
+                Some(RESPONSE.len())
+            }
+            _ => {
+                self.clock.advance(timeout);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl UdpSocket for SimulatedSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        Ok(buf.len())
+    }
+
+    async fn send_to(&mut self, buf: &[u8], _addr: &SocketAddr) -> Result<usize, io::Error> {
+        Ok(buf.len())
+    }
+
+    async fn recv(&mut self, _buf: &mut [u8]) -> Result<usize, io::Error> {
+        panic!("every receive must be bounded by the timeout")
+    }
+
+    async fn recv_from(&mut self, _buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        panic!("every receive must be bounded by the timeout")
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        Ok(self.wait(buf, timeout))
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        let source = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1434);
+        Ok(self.wait(buf, timeout).map(|len| (len, source)))
+    }
+}
+
+#[test]
+fn retransmissions_and_timeout_follow_the_clock() {
+    let clock = ManualClock::new();
+    let waits = Arc::new(Mutex::new(Vec::new()));
+    let mut factory = SimulatedSocketFactory {
+        clock: clock.clone(),
+        latency: None,
+        waits: waits.clone(),
+    };
+    let options = BrowserOptions::new()
+        .clock(clock.clone())
+        .timeout(Some(Duration::from_secs(10)))
+        .retry(Some(
+            RetryPolicy::new(3).initial_delay(Duration::from_secs(1)),
+        ));
+
+    let start = clock.now();
+    let remote_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let result = futures::executor::block_on(custom_socket::browse_host_with_options(
+        remote_addr,
+        &options,
+        &mut factory,
+    ));
+
+    match result {
+        Err(BrowserError::Timeout(addr)) => assert_eq!(addr, SocketAddr::new(remote_addr, 1434)),
+        Err(e) => panic!("expected Timeout, got {:?}", e),
+        Ok(_) => panic!("expected Timeout, got an iterator"),
+    }
+
+    assert_eq!(
+        *waits.lock().unwrap(),
+        vec![
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            Duration::from_secs(7)
+        ]
+    );
+    assert_eq!(clock.now() - start, Duration::from_secs(10));
+}
+
+#[test]
+fn rtt_is_measured_by_the_clock() {
+    let clock = ManualClock::new();
+    let mut factory = SimulatedSocketFactory {
+        clock: clock.clone(),
+        latency: Some(Duration::from_millis(30)),
+        waits: Arc::new(Mutex::new(Vec::new())),
+    };
+    let options = BrowserOptions::new().clock(clock);
+
+    let remote_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let mut instances = futures::executor::block_on(custom_socket::browse_host_with_options(
+        remote_addr,
+        &options,
+        &mut factory,
+    ))
+    .unwrap();
+
+    let instance = instances.next().unwrap().unwrap();
+    assert_eq!(instance.rtt, Some(Duration::from_millis(30)));
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "test-util")]
+
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, SocketConfig, UdpSocket, UdpSocketFactory};
+use mssql_browser::test_util::{MemoryNetwork, MemorySocket, MemorySocketFactory};
+use mssql_browser::{BrowserError, BrowserOptions};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Binds sockets on a `MemoryNetwork` of which every connect stalls until it times out
+struct StalledConnectFactory {
+    inner: MemorySocketFactory,
+    connect_timeouts: Arc<Mutex<Vec<Duration>>>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for StalledConnectFactory {
+    type Socket = StalledConnectSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, addr: &SocketAddr) -> Result<StalledConnectSocket, io::Error> {
+        self.bind_with(addr, &SocketConfig::default()).await
+    }
+
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        config: &SocketConfig,
+    ) -> Result<StalledConnectSocket, io::Error> {
+        Ok(StalledConnectSocket {
+            inner: self.inner.bind_with(addr, config).await?,
+            connect_timeouts: self.connect_timeouts.clone(),
+        })
+    }
+}
+
+struct StalledConnectSocket {
+    inner: MemorySocket,
+    connect_timeouts: Arc<Mutex<Vec<Duration>>>,
+}
+
+#[async_trait]
+impl UdpSocket for StalledConnectSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        self.inner.enable_broadcast().await
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        panic!("connect must be bounded by the timeout")
+    }
+
+    async fn connect_timeout(
+        &mut self,
+        _addr: &SocketAddr,
+        timeout: Duration,
+    ) -> Result<Option<()>, io::Error> {
+        self.connect_timeouts.lock().unwrap().push(timeout);
+        Ok(None)
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        self.inner.send(buf).await
+    }
+
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.inner.send_to(buf, addr).await
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        self.inner.recv(buf).await
+    }
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        self.inner.recv_from(buf).await
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        self.inner.recv_timeout(buf, timeout).await
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        self.inner.recv_from_timeout(buf, timeout).await
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        UdpSocket::local_addr(&self.inner)
+    }
+}
+
+#[test]
+fn stalled_connect_is_reported_as_connect_timeout() {
+    let network = MemoryNetwork::new();
+    let remote_addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let mut server = network.bind(SocketAddr::new(remote_addr, 1434)).unwrap();
+    let connect_timeouts = Arc::new(Mutex::new(Vec::new()));
+    let mut factory = StalledConnectFactory {
+        inner: network.socket_factory(),
+        connect_timeouts: connect_timeouts.clone(),
+    };
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(500)));
+
+    let result = futures::executor::block_on(custom_socket::browse_host_with_options(
+        remote_addr,
+        &options,
+        &mut factory,
+    ));
+
+    match result {
+        Err(BrowserError::ConnectTimeout(addr)) => {
+            assert_eq!(addr, SocketAddr::new(remote_addr, 1434))
+        }
+        Err(e) => panic!("expected ConnectTimeout, got {:?}", e),
+        Ok(_) => panic!("expected ConnectTimeout, got an iterator"),
+    }
+
+    let connect_timeouts = connect_timeouts.lock().unwrap();
+    assert_eq!(connect_timeouts.len(), 1);
+    assert!(connect_timeouts[0] <= Duration::from_millis(500));
+
+    // Nothing was sent before the socket was connected
+    let mut buf = [0u8; 16];
+    let received =
+        futures::executor::block_on(server.recv_from_timeout(&mut buf, Duration::from_millis(10)));
+    assert_eq!(received.unwrap(), None);
+}
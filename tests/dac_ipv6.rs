@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, UdpSocket, UdpSocketFactory};
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Everything the mock socket was asked to do
+#[derive(Default)]
+struct Recorded {
+    bound: Option<SocketAddr>,
+    connected: Option<SocketAddr>,
+    sent: Vec<u8>,
+}
+
+struct MockSocketFactory {
+    recorded: Arc<Mutex<Recorded>>,
+    response: Vec<u8>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MockSocketFactory {
+    type Socket = MockSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, addr: &SocketAddr) -> Result<MockSocket, io::Error> {
+        self.recorded.lock().unwrap().bound = Some(*addr);
+        Ok(MockSocket {
+            recorded: self.recorded.clone(),
+            response: self.response.clone(),
+        })
+    }
+}
+
+struct MockSocket {
+    recorded: Arc<Mutex<Recorded>>,
+    response: Vec<u8>,
+}
+
+impl MockSocket {
+    fn respond(&self, buf: &mut [u8]) -> usize {
+        let len = std::cmp::min(buf.len(), self.response.len());
+        buf[..len].copy_from_slice(&self.response[..len]);
+        len
+    }
+
+    fn peer(&self) -> SocketAddr {
+        self.recorded.lock().unwrap().connected.unwrap()
+    }
+}
+
+#[async_trait]
+impl UdpSocket for MockSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, addr: &SocketAddr) -> Result<(), io::Error> {
+        self.recorded.lock().unwrap().connected = Some(*addr);
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        self.recorded.lock().unwrap().sent = buf.to_vec();
+        Ok(buf.len())
+    }
+
+    async fn send_to(&mut self, buf: &[u8], _addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.send(buf).await
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        Ok(self.respond(buf))
+    }
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        Ok((self.respond(buf), self.peer()))
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        Ok(Some(self.respond(buf)))
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        Ok(Some((self.respond(buf), self.peer())))
+    }
+}
+
+#[test]
+fn browse_instance_dac_over_ipv6() {
+    let recorded = Arc::new(Mutex::new(Recorded::default()));
+    let mut factory = MockSocketFactory {
+        recorded: recorded.clone(),
+        response: vec![0x05, 0x06, 0x00, 0x01, 0x2D, 0x05],
+    };
+
+    let remote_addr = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x10));
+    let info = futures::executor::block_on(custom_socket::browse_instance_dac(
+        remote_addr,
+        "SQLEXPRESS",
+        &mut factory,
+    ))
+    .unwrap();
+
+    assert_eq!(info.port, 1325);
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(
+        recorded.bound,
+        Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0))
+    );
+    assert_eq!(recorded.connected, Some(SocketAddr::new(remote_addr, 1434)));
+    assert_eq!(recorded.sent, b"\x0F\x01SQLEXPRESS\0".to_vec());
+}
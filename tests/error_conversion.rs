@@ -0,0 +1,99 @@
+use mssql_browser::{BrowserError, BrowserProtocolError};
+use std::error::Error;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+/// The unified error type of a library wrapping several socket implementations
+#[derive(Debug)]
+struct WrappedError(String);
+
+impl std::fmt::Display for WrappedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "wrapped: {}", self.0)
+    }
+}
+
+impl std::error::Error for WrappedError {}
+
+impl From<io::Error> for WrappedError {
+    fn from(err: io::Error) -> WrappedError {
+        WrappedError(err.to_string())
+    }
+}
+
+fn remote() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1434)
+}
+
+#[test]
+fn socket_errors_are_converted_via_from() {
+    let error: BrowserError<io::Error, io::Error> =
+        BrowserError::SendFailed(remote(), io::ErrorKind::PermissionDenied.into());
+
+    match error.convert_socket_err::<WrappedError, WrappedError>() {
+        BrowserError::SendFailed(addr, WrappedError(message)) => {
+            assert_eq!(addr, remote());
+            assert_eq!(
+                message,
+                io::Error::from(io::ErrorKind::PermissionDenied).to_string()
+            );
+        }
+        e => panic!("expected SendFailed, got {:?}", e),
+    }
+}
+
+#[test]
+fn factory_errors_are_mapped_separately() {
+    let error: BrowserError<io::Error, io::Error> =
+        BrowserError::BindFailed(io::ErrorKind::AddrInUse.into());
+
+    let mapped = error.map_socket_err(
+        |_| WrappedError("factory".to_string()),
+        |_| WrappedError("socket".to_string()),
+    );
+    assert_eq!(mapped.to_string(), "bind failed: wrapped: factory");
+}
+
+#[test]
+fn errors_without_an_underlying_error_are_kept() {
+    let error: BrowserError<io::Error, io::Error> = BrowserError::Timeout(remote());
+
+    match error.convert_socket_err::<WrappedError, WrappedError>() {
+        BrowserError::Timeout(addr) => assert_eq!(addr, remote()),
+        e => panic!("expected Timeout, got {:?}", e),
+    }
+}
+
+#[test]
+fn underlying_errors_are_exposed_as_source() {
+    let error: BrowserError<io::Error, io::Error> =
+        BrowserError::SendFailed(remote(), io::ErrorKind::PermissionDenied.into());
+    let source = error.source().unwrap();
+    assert_eq!(
+        source.downcast_ref::<io::Error>().unwrap().kind(),
+        io::ErrorKind::PermissionDenied
+    );
+
+    // The protocol error the datagram is attached to is the source of the wrapper
+    let error: BrowserError<io::Error, io::Error> =
+        BrowserError::ProtocolError(BrowserProtocolError::InvalidResponse {
+            error: Box::new(BrowserProtocolError::EmptyResponse),
+            response: Vec::new(),
+        });
+    let wrapper = error.source().unwrap();
+    assert!(matches!(
+        wrapper.downcast_ref::<BrowserProtocolError>(),
+        Some(BrowserProtocolError::InvalidResponse { .. })
+    ));
+    assert!(matches!(
+        wrapper
+            .source()
+            .unwrap()
+            .downcast_ref::<BrowserProtocolError>(),
+        Some(BrowserProtocolError::EmptyResponse)
+    ));
+
+    assert!(BrowserError::<io::Error, io::Error>::Timeout(remote())
+        .source()
+        .is_none());
+}
@@ -0,0 +1,121 @@
+use mssql_browser::{parse_responses, BrowserProtocolError, BrowserProtocolToken};
+use std::net::{IpAddr, Ipv4Addr};
+
+fn svr_resp(body: &[u8]) -> Vec<u8> {
+    let mut datagram = vec![0x05];
+    datagram.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    datagram.extend_from_slice(body);
+    datagram
+}
+
+#[test]
+fn unknown_leading_fields_are_collected() {
+    let addr = IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2));
+    let datagram = svr_resp(
+        b"Edition;Azure SQL Edge;ServerName;3F2A1C0B9D8E;InstanceName;MSSQLSERVER;\
+          IsClustered;No;Version;15.0.2000.1565;tcp;1433;;",
+    );
+
+    let instances = parse_responses(vec![(addr, &datagram[..])])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(instances.len(), 1);
+    let instance = &instances[0];
+    assert_eq!(instance.server_name, "3F2A1C0B9D8E");
+    assert_eq!(instance.instance_name, "MSSQLSERVER");
+    assert!(!instance.is_clustered);
+    assert_eq!(instance.tcp_info.as_ref().unwrap().port, 1433);
+    assert_eq!(
+        instance.unknown,
+        vec![("Edition".to_string(), "Azure SQL Edge".to_string())]
+    );
+}
+
+#[test]
+fn missing_instance_information_is_rejected() {
+    let addr = IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2));
+    let datagram = svr_resp(b"ServerName;3F2A1C0B9D8E;IsClustered;No;Version;15.0.2000.1565;;");
+
+    let result = parse_responses(vec![(addr, &datagram[..])]).next().unwrap();
+    match result {
+        Err(BrowserProtocolError::UnexpectedToken { .. }) => {}
+        other => panic!(
+            "expected UnexpectedToken, got {:?}",
+            other.map(|i| i.instance_name)
+        ),
+    }
+}
+
+#[test]
+fn endpoint_identifier_error_lists_the_known_identifiers() {
+    let error = BrowserProtocolError::UnexpectedToken {
+        expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
+        found: BrowserProtocolToken::Literal("quic".to_string()),
+    };
+
+    assert_eq!(
+        error.to_string(),
+        "expected endpoint identifier (one of np, tcp, via, rpc, spx, adsp, bv) or semicolon, \
+         but found 'quic'"
+    );
+}
+
+#[test]
+fn truncated_response_is_told_apart_from_a_length_mismatch() {
+    let addr = IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2));
+    let mut datagram =
+        svr_resp(b"ServerName;SRV1;InstanceName;MSSQLSERVER;IsClustered;No;Version;15.0.2000.5;;");
+    datagram.truncate(40);
+
+    let result = parse_responses(vec![(addr, &datagram[..])]).next().unwrap();
+    match result {
+        Err(e) => match e.reason() {
+            BrowserProtocolError::Truncated { datagram, header } => {
+                assert_eq!(*datagram, 40);
+                assert!(*header > 40);
+            }
+            other => panic!("expected Truncated, got {:?}", other),
+        },
+        Ok(_) => panic!("expected Truncated, got an instance"),
+    }
+}
+
+#[test]
+fn records_are_not_merged_when_a_field_repeats() {
+    let addr = IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2));
+
+    // The unknown key must not take the terminator of the first record as its value
+    let datagram = svr_resp(
+        b"ServerName;A;InstanceName;X;IsClustered;No;Version;1;Hidden;;\
+          ServerName;B;InstanceName;Y;IsClustered;No;Version;1;;",
+    );
+    let result = parse_responses(vec![(addr, &datagram[..])]).next().unwrap();
+    match result.map(|instance| instance.instance_name) {
+        Err(e) => match e.reason() {
+            BrowserProtocolError::UnexpectedToken {
+                expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
+                found: BrowserProtocolToken::Literal(found),
+            } => assert_eq!(found, "Hidden"),
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        },
+        Ok(name) => panic!("expected UnexpectedToken, got instance {}", name),
+    }
+
+    // Nor may a repeated instance field overwrite the value of the first record
+    let datagram = svr_resp(
+        b"ServerName;A;InstanceName;X;IsClustered;No;Version;1;\
+          ServerName;B;InstanceName;Y;IsClustered;No;Version;1;;",
+    );
+    let result = parse_responses(vec![(addr, &datagram[..])]).next().unwrap();
+    match result.map(|instance| instance.instance_name) {
+        Err(e) => match e.reason() {
+            BrowserProtocolError::UnexpectedToken {
+                expected: BrowserProtocolToken::EndpointIdentifierOrSemicolon,
+                found: BrowserProtocolToken::Literal(found),
+            } => assert_eq!(found, "ServerName"),
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        },
+        Ok(name) => panic!("expected UnexpectedToken, got instance {}", name),
+    }
+}
@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, SocketConfig, UdpSocket, UdpSocketFactory};
+use mssql_browser::BrowserError;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// A factory that fails the test if any socket is bound
+struct NoNetwork;
+
+#[async_trait]
+impl UdpSocketFactory for NoNetwork {
+    type Socket = NoSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<NoSocket, io::Error> {
+        panic!("no socket should be bound for an invalid instance name")
+    }
+
+    async fn bind_with(
+        &mut self,
+        _addr: &SocketAddr,
+        _config: &SocketConfig,
+    ) -> Result<NoSocket, io::Error> {
+        panic!("no socket should be bound for an invalid instance name")
+    }
+}
+
+enum NoSocket {}
+
+#[async_trait]
+impl UdpSocket for NoSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        match *self {}
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        match *self {}
+    }
+
+    async fn send(&mut self, _buf: &[u8]) -> Result<usize, io::Error> {
+        match *self {}
+    }
+
+    async fn send_to(&mut self, _buf: &[u8], _addr: &SocketAddr) -> Result<usize, io::Error> {
+        match *self {}
+    }
+
+    async fn recv(&mut self, _buf: &mut [u8]) -> Result<usize, io::Error> {
+        match *self {}
+    }
+
+    async fn recv_from(&mut self, _buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        match *self {}
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        _buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        match *self {}
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        _buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        match *self {}
+    }
+}
+
+const REMOTE_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+#[test]
+fn empty_instance_name_is_rejected() {
+    let result = futures::executor::block_on(custom_socket::browse_instance(
+        REMOTE_ADDR,
+        "",
+        &mut NoNetwork,
+    ));
+    assert!(matches!(result, Err(BrowserError::InstanceNameEmpty)));
+
+    let result = futures::executor::block_on(custom_socket::browse_instance_dac(
+        REMOTE_ADDR,
+        "",
+        &mut NoNetwork,
+    ));
+    assert!(matches!(result, Err(BrowserError::InstanceNameEmpty)));
+}
+
+#[test]
+fn instance_name_with_invalid_character_is_rejected() {
+    for (instance_name, invalid) in &[("SRV\\SQL", '\\'), ("SQL;EXPRESS", ';'), ("SQL\0", '\0')] {
+        let result = futures::executor::block_on(custom_socket::browse_instance(
+            REMOTE_ADDR,
+            instance_name,
+            &mut NoNetwork,
+        ));
+        assert!(matches!(result, Err(BrowserError::InvalidInstanceName(c)) if c == *invalid));
+    }
+
+    let result = futures::executor::block_on(custom_socket::browse_instance_dac(
+        REMOTE_ADDR,
+        "SQL\tEXPRESS",
+        &mut NoNetwork,
+    ));
+    assert!(matches!(
+        result,
+        Err(BrowserError::InvalidInstanceName('\t'))
+    ));
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "test-util")]
+
+use mssql_browser::custom_socket::{self, UdpSocket};
+use mssql_browser::test_util::{svr_resp, MemoryNetwork, MemorySocket};
+use mssql_browser::{BrowserOptions, DiscoveryMethod, Ipv4Interface};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+const INTERFACE: Ipv4Addr = Ipv4Addr::new(10, 0, 1, 5);
+
+fn options() -> BrowserOptions {
+    BrowserOptions::new()
+        .timeout(Some(Duration::from_millis(300)))
+        .interfaces(vec![Ipv4Interface {
+            addr: INTERFACE,
+            prefix: 24,
+        }])
+}
+
+/// A browser service bound to the unspecified address, which receives limited as well as
+/// directed broadcasts
+fn bind_server(network: &MemoryNetwork) -> MemorySocket {
+    network
+        .bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1434))
+        .unwrap()
+}
+
+/// Collects the sources of the requests the server received without answering them
+fn request_sources(server: &mut MemorySocket) -> Vec<SocketAddr> {
+    let mut buf = [0u8; 16];
+    let mut sources = Vec::new();
+    while let Some((_, source)) =
+        futures::executor::block_on(server.recv_from_timeout(&mut buf, Duration::from_millis(10)))
+            .unwrap()
+    {
+        sources.push(source);
+    }
+    sources
+}
+
+#[test]
+fn request_is_sent_from_every_interface_and_responses_are_merged() {
+    let network = MemoryNetwork::new();
+    let mut server = bind_server(&network);
+    let mut factory = network.socket_factory();
+    let options = options();
+    let response = svr_resp(
+        "ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+    );
+
+    // Both requests are answered, the responses describe the same instance
+    let serve = async {
+        let mut buf = [0u8; 16];
+        let mut sources = Vec::new();
+        for _ in 0..2 {
+            let (_, source) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&response, &source).await.unwrap();
+            sources.push(source);
+        }
+        sources
+    };
+    let browse = async {
+        let mut iterator = custom_socket::browse_with_options(
+            IpAddr::V4(Ipv4Addr::BROADCAST),
+            &options,
+            &mut factory,
+        )
+        .await
+        .unwrap();
+
+        let instance = iterator.next().await.unwrap().unwrap();
+        assert_eq!(instance.server_name, "SRV1");
+        assert_eq!(instance.discovery_method, DiscoveryMethod::Broadcast);
+        assert!(iterator.next().await.unwrap().is_none());
+    };
+    let (_, mut sources) = futures::executor::block_on(async { futures::join!(browse, serve) });
+
+    // One request from the socket bound to the unspecified address, one from the interface
+    sources.sort_by_key(|source| source.ip() != IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert!(sources[0].ip().is_unspecified());
+    assert_eq!(sources[1].ip(), IpAddr::V4(INTERFACE));
+}
+
+#[test]
+fn interfaces_are_ignored_for_multicast() {
+    let network = MemoryNetwork::new();
+    let mut server = bind_server(&network);
+    let mut factory = network.socket_factory();
+
+    futures::executor::block_on(custom_socket::browse_with_options(
+        IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)),
+        &options(),
+        &mut factory,
+    ))
+    .unwrap();
+
+    let sources = request_sources(&mut server);
+    assert_eq!(sources.len(), 1);
+    assert!(sources[0].ip().is_unspecified());
+}
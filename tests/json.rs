@@ -0,0 +1,51 @@
+#![cfg(feature = "json")]
+
+use mssql_browser::parse_responses;
+use serde_json::json;
+use std::net::{IpAddr, Ipv4Addr};
+
+fn svr_resp(body: &[u8]) -> Vec<u8> {
+    let mut datagram = vec![0x05];
+    datagram.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    datagram.extend_from_slice(body);
+    datagram
+}
+
+#[test]
+fn endpoints_are_flattened_in_protocol_order() {
+    let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let datagram = svr_resp(
+        b"ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;Yes;Version;15.0.2000.5;\
+          tcp;1433;np;\\\\SRV1\\pipe\\sql\\query;via;SRV1,0:1433;bv;item;group;org;Edition;Express;;",
+    );
+
+    let instance = parse_responses(vec![(addr, &datagram[..])])
+        .next()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        instance.to_json(),
+        json!({
+            "address": "10.0.0.1",
+            "server_name": "SRV1",
+            "instance_name": "SQLEXPRESS",
+            "is_clustered": true,
+            "version": "15.0.2000.5",
+            "endpoints": [
+                { "protocol": "np", "pipe_name": "\\\\SRV1\\pipe\\sql\\query" },
+                { "protocol": "tcp", "port": 1433 },
+                {
+                    "protocol": "via",
+                    "machine_name": "SRV1",
+                    "addresses": [{ "nic": "0", "port": "1433" }]
+                },
+                { "protocol": "bv", "item_name": "item", "group_name": "group", "org_name": "org" }
+            ],
+            "unknown": [{ "key": "Edition", "value": "Express" }],
+            "dac_port": null,
+            "rtt_ms": null,
+            "source": null
+        })
+    );
+}
@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, UdpSocket, UdpSocketFactory};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct MockSocketFactory {
+    sent: Arc<Mutex<Vec<u8>>>,
+    response: Vec<u8>,
+}
+
+#[async_trait]
+impl UdpSocketFactory for MockSocketFactory {
+    type Socket = MockSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, _addr: &SocketAddr) -> Result<MockSocket, io::Error> {
+        Ok(MockSocket {
+            sent: self.sent.clone(),
+            response: self.response.clone(),
+        })
+    }
+}
+
+struct MockSocket {
+    sent: Arc<Mutex<Vec<u8>>>,
+    response: Vec<u8>,
+}
+
+impl MockSocket {
+    fn respond(&self, buf: &mut [u8]) -> usize {
+        buf[..self.response.len()].copy_from_slice(&self.response);
+        self.response.len()
+    }
+}
+
+#[async_trait]
+impl UdpSocket for MockSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn connect(&mut self, _addr: &SocketAddr) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        *self.sent.lock().unwrap() = buf.to_vec();
+        Ok(buf.len())
+    }
+
+    async fn send_to(&mut self, buf: &[u8], _addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.send(buf).await
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        Ok(self.respond(buf))
+    }
+
+    async fn recv_from(&mut self, _buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        unimplemented!()
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        Ok(Some(self.respond(buf)))
+    }
+}
+
+/// Browses an instance with the given name, checking the request and the parsed response
+fn browse_instance_with_name(instance_name: &str) {
+    let body = format!(
+        "ServerName;SRV1;InstanceName;{};IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+        instance_name
+    );
+    let mut response = vec![0x05];
+    response.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    response.extend_from_slice(body.as_bytes());
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let mut factory = MockSocketFactory {
+        sent: sent.clone(),
+        response,
+    };
+
+    let info = futures::executor::block_on(custom_socket::browse_instance(
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        instance_name,
+        &mut factory,
+    ))
+    .unwrap();
+
+    assert_eq!(info.instance_name, instance_name);
+
+    let mut request = vec![0x04];
+    request.extend_from_slice(instance_name.as_bytes());
+    request.push(0);
+    assert_eq!(*sent.lock().unwrap(), request);
+}
+
+#[test]
+fn browse_instance_with_40_character_name() {
+    browse_instance_with_name(&"A".repeat(40));
+}
+
+#[test]
+fn browse_instance_with_200_byte_name() {
+    browse_instance_with_name(&"A".repeat(200));
+}
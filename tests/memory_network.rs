@@ -0,0 +1,710 @@
+#![cfg(feature = "test-util")]
+
+use mssql_browser::custom_socket::{
+    browse_for_each, browse_host_by_name_with_options, browse_host_stream_with_options,
+    browse_host_with_dac_with_options, browse_host_with_options, browse_instance,
+    browse_instance_dac_with_options, browse_instance_with_options, browse_instances_with_options,
+    browse_tcp_endpoints, browse_with_options, browser_dac_version, UdpSocket,
+};
+use mssql_browser::test_util::{answer, svr_resp, MemoryNetwork};
+use mssql_browser::{
+    AddressPreference, BrowserError, BrowserOptions, BrowserProtocolError, BrowserProtocolToken,
+    RetryPolicy,
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+fn server_ip(last_octet: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_octet))
+}
+
+fn instance_response(server_name: &str, instance_name: &str) -> Vec<u8> {
+    svr_resp(&format!(
+        "ServerName;{};InstanceName;{};IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+        server_name, instance_name
+    ))
+}
+
+#[test]
+fn browse_instance_against_fake_server() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let response = instance_response("SRV1", "SQLEXPRESS");
+
+    let mut factory = network.socket_factory();
+    let (info, request) = futures::executor::block_on(async {
+        futures::join!(
+            browse_instance(server_ip(1), "SQLEXPRESS", &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+
+    assert_eq!(request.unwrap().0, b"\x04SQLEXPRESS\0".to_vec());
+    let info = info.unwrap();
+    assert_eq!(info.server_name, "SRV1");
+    assert_eq!(info.bytes_received(), Some(response.len()));
+    assert_eq!(info.tcp_info.unwrap().port, 1433);
+}
+
+#[test]
+fn browse_collects_responses_of_all_fake_servers() {
+    let network = MemoryNetwork::new();
+    let mut first = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let mut second = network.bind(SocketAddr::new(server_ip(2), 1434)).unwrap();
+    let first_response = instance_response("SRV1", "MSSQLSERVER");
+    let second_response = instance_response("SRV2", "MSSQLSERVER");
+
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_secs(5)))
+        .expected_responders(Some(2));
+    let mut factory = network.socket_factory();
+
+    let mut servers = futures::executor::block_on(async {
+        let browse = async {
+            let mut iterator =
+                browse_with_options(IpAddr::V4(Ipv4Addr::BROADCAST), &options, &mut factory)
+                    .await
+                    .unwrap();
+
+            let mut servers = Vec::new();
+            while let Some(instance) = iterator.next().await.unwrap() {
+                servers.push(instance.server_name);
+            }
+            servers
+        };
+
+        let (servers, first, second) = futures::join!(
+            browse,
+            answer(&mut first, &first_response),
+            answer(&mut second, &second_response)
+        );
+        assert_eq!(first.unwrap().0, vec![0x02]);
+        assert_eq!(second.unwrap().0, vec![0x02]);
+        servers
+    });
+
+    servers.sort();
+    assert_eq!(servers, vec!["SRV1", "SRV2"]);
+}
+
+#[test]
+fn repeated_probes_return_each_instance_once() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let response = instance_response("SRV1", "MSSQLSERVER");
+
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_millis(500)))
+        .probe_count(3)
+        .probe_interval(Duration::from_millis(20));
+    let mut factory = network.socket_factory();
+
+    let servers = futures::executor::block_on(async {
+        let browse = async {
+            let mut iterator =
+                browse_with_options(IpAddr::V4(Ipv4Addr::BROADCAST), &options, &mut factory)
+                    .await
+                    .unwrap();
+
+            let mut servers = Vec::new();
+            while let Some(instance) = iterator.next().await.unwrap() {
+                servers.push(instance.server_name);
+            }
+            servers
+        };
+
+        let serve = async {
+            for _ in 0..3 {
+                assert_eq!(answer(&mut server, &response).await.unwrap().0, vec![0x02]);
+            }
+        };
+
+        futures::join!(browse, serve).0
+    });
+
+    assert_eq!(servers, vec!["SRV1"]);
+}
+
+#[test]
+fn host_stream_returns_instances_of_every_response() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let first_response = instance_response("SRV1", "SQLEXPRESS");
+    let second_response = instance_response("SRV1", "MSSQLSERVER");
+
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(200)));
+    let mut factory = network.socket_factory();
+
+    let instances = futures::executor::block_on(async {
+        let browse = async {
+            let mut stream = browse_host_stream_with_options(server_ip(1), &options, &mut factory)
+                .await
+                .unwrap();
+
+            let mut instances = Vec::new();
+            while let Some(instance) = stream.next().await.unwrap() {
+                instances.push(instance.instance_name);
+            }
+            instances
+        };
+
+        let serve = async {
+            let (request, source) = answer(&mut server, &first_response).await.unwrap();
+            assert_eq!(request, vec![0x03]);
+            server.send_to(&second_response, &source).await.unwrap();
+        };
+
+        futures::join!(browse, serve).0
+    });
+
+    assert_eq!(instances, vec!["SQLEXPRESS", "MSSQLSERVER"]);
+}
+
+#[test]
+fn host_stream_next_can_be_dropped_while_receiving() {
+    use futures::FutureExt;
+
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let response = instance_response("SRV1", "SQLEXPRESS");
+
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(200)));
+    let mut factory = network.socket_factory();
+
+    futures::executor::block_on(async {
+        let mut stream = browse_host_stream_with_options(server_ip(1), &options, &mut factory)
+            .await
+            .unwrap();
+
+        // Poll once while the receive is pending, then drop the future
+        assert!(stream.next().now_or_never().is_none());
+
+        answer(&mut server, &response).await.unwrap();
+        let instance = stream.next().await.unwrap().unwrap();
+        assert_eq!(instance.instance_name, "SQLEXPRESS");
+    });
+}
+
+#[test]
+fn iterator_reports_the_local_address_requests_were_sent_from() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let response = instance_response("SRV1", "MSSQLSERVER");
+
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_millis(200)))
+        .expected_responders(Some(1));
+    let mut factory = network.socket_factory();
+
+    let (local_addr, source) = futures::executor::block_on(async {
+        let browse = async {
+            let mut iterator =
+                browse_with_options(IpAddr::V4(Ipv4Addr::BROADCAST), &options, &mut factory)
+                    .await
+                    .unwrap();
+
+            while iterator.next().await.unwrap().is_some() {}
+            iterator.local_addr()
+        };
+
+        let (local_addr, request) = futures::join!(browse, answer(&mut server, &response));
+        (local_addr, request.unwrap().1)
+    });
+
+    assert_eq!(local_addr, Some(source));
+}
+
+#[test]
+fn unconnected_socket_accepts_responses_from_any_port_of_the_host() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let mut other_port = network.bind(SocketAddr::new(server_ip(1), 1435)).unwrap();
+    let mut other_host = network.bind(SocketAddr::new(server_ip(2), 1434)).unwrap();
+    let stray_response = instance_response("SRV2", "SQLEXPRESS");
+    let response = instance_response("SRV1", "SQLEXPRESS");
+
+    let options = BrowserOptions::new()
+        .connected(false)
+        .timeout(Some(Duration::from_secs(5)));
+    let mut factory = network.socket_factory();
+
+    let info = futures::executor::block_on(async {
+        let serve = async {
+            let (_, source) = server.recv_from(&mut [0u8; 64]).await.unwrap();
+            other_host.send_to(&stray_response, &source).await.unwrap();
+            other_port.send_to(&response, &source).await.unwrap();
+        };
+
+        let (info, _) = futures::join!(
+            browse_instance_with_options(server_ip(1), "SQLEXPRESS", &options, &mut factory),
+            serve
+        );
+        info
+    });
+
+    assert_eq!(info.unwrap().server_name, "SRV1");
+}
+
+#[test]
+fn dac_version_is_reported_for_any_version() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    // A hypothetical version 2 response, one byte longer than version 1
+    let response = [0x05, 0x07, 0x00, 0x02, 0x9D, 0x05, 0x00];
+
+    let mut factory = network.socket_factory();
+    let (version, request) = futures::executor::block_on(async {
+        futures::join!(
+            browser_dac_version(server_ip(1), "MSSQLSERVER", &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+
+    assert_eq!(request.unwrap().0, b"\x0F\x01MSSQLSERVER\0".to_vec());
+    assert_eq!(version.unwrap(), 2);
+}
+
+#[test]
+fn dac_request_version_can_be_overridden() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let options = BrowserOptions::new().dac_version(2);
+    let mut factory = network.socket_factory();
+
+    let response = [0x05, 0x06, 0x00, 0x02, 0x2D, 0x05];
+    let (info, request) = futures::executor::block_on(async {
+        futures::join!(
+            browse_instance_dac_with_options(server_ip(1), "MSSQLSERVER", &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+
+    assert_eq!(request.unwrap().0, b"\x0F\x02MSSQLSERVER\0".to_vec());
+    assert_eq!(info.unwrap().port, 1325);
+
+    // A version 1 response doesn't match the requested version
+    let response = [0x05, 0x06, 0x00, 0x01, 0x2D, 0x05];
+    let (info, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_instance_dac_with_options(server_ip(1), "MSSQLSERVER", &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+
+    match info {
+        Err(BrowserError::ProtocolError(e)) => match e.reason() {
+            BrowserProtocolError::UnexpectedToken {
+                expected: BrowserProtocolToken::DacVersion(2),
+                found: BrowserProtocolToken::DacVersion(1),
+            } => {}
+            reason => panic!("expected a DAC version mismatch, got {:?}", reason),
+        },
+        other => panic!("expected a DAC version mismatch, got {:?}", other),
+    }
+
+    // A response too short to hold a version names the requested one
+    let response = [0x05, 0x03, 0x00];
+    let (info, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_instance_dac_with_options(server_ip(1), "MSSQLSERVER", &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+
+    match info {
+        Err(BrowserError::ProtocolError(e)) => match e.reason() {
+            BrowserProtocolError::UnexpectedToken {
+                expected: BrowserProtocolToken::DacVersion(2),
+                found: BrowserProtocolToken::EndOfMessage,
+            } => {}
+            reason => panic!("expected a missing DAC version, got {:?}", reason),
+        },
+        other => panic!("expected a missing DAC version, got {:?}", other),
+    }
+}
+
+#[test]
+fn for_each_rebroadcasts_until_dropped() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let responses = [
+        instance_response("SRV1", "MSSQLSERVER"),
+        instance_response("SRV1", "SQLEXPRESS"),
+        instance_response("SRV1", "MSSQLSERVER"),
+    ];
+
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(50)));
+    let mut factory = network.socket_factory();
+    let mut instances = Vec::new();
+
+    futures::executor::block_on(async {
+        let browse = browse_for_each(
+            IpAddr::V4(Ipv4Addr::BROADCAST),
+            &options,
+            |instance| instances.push(instance.instance_name),
+            &mut factory,
+        );
+
+        // Stops browsing once the third request shows the second response was processed
+        let serve = async {
+            for response in &responses {
+                assert_eq!(answer(&mut server, response).await.unwrap().0, vec![0x02]);
+            }
+        };
+
+        futures::pin_mut!(browse, serve);
+        match futures::future::select(browse, serve).await {
+            futures::future::Either::Left((result, _)) => panic!("browsing ended: {:?}", result),
+            futures::future::Either::Right(_) => {}
+        }
+    });
+
+    assert_eq!(instances, vec!["MSSQLSERVER", "SQLEXPRESS"]);
+}
+
+#[test]
+fn tcp_endpoints_skip_instances_without_tcp() {
+    use futures::StreamExt;
+
+    let network = MemoryNetwork::new();
+    let mut first = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let mut second = network.bind(SocketAddr::new(server_ip(2), 1434)).unwrap();
+    let first_response = instance_response("SRV1", "MSSQLSERVER");
+    let second_response = svr_resp(
+        "ServerName;SRV2;InstanceName;PIPES;IsClustered;No;Version;15.0.2000.5;np;\\\\SRV2\\pipe\\sql\\query;;",
+    );
+
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_secs(5)))
+        .expected_responders(Some(2));
+    let mut factory = network.socket_factory();
+
+    let endpoints = futures::executor::block_on(async {
+        let browse = async {
+            browse_tcp_endpoints(IpAddr::V4(Ipv4Addr::BROADCAST), &options, &mut factory)
+                .await
+                .unwrap()
+                .collect::<Vec<_>>()
+                .await
+        };
+
+        let (endpoints, _, _) = futures::join!(
+            browse,
+            answer(&mut first, &first_response),
+            answer(&mut second, &second_response)
+        );
+        endpoints
+    });
+
+    let endpoints = endpoints
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(endpoints, vec![SocketAddr::new(server_ip(1), 1433)]);
+}
+
+#[test]
+fn candidate_browser_ports_are_tried_in_order() {
+    let network = MemoryNetwork::new();
+    let forwarded = SocketAddr::new(server_ip(1), 11434);
+    let mut server = network.bind(forwarded).unwrap();
+    let response = instance_response("SRV1", "SQLEXPRESS");
+
+    // Nothing answers on 1434, so it times out before the forwarded port is tried
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_millis(100)))
+        .browser_ports(vec![1434, forwarded.port()]);
+    let mut factory = network.socket_factory();
+
+    let (instances, request) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_with_options(server_ip(1), &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+
+    assert_eq!(request.unwrap().0, vec![0x03]);
+    let instance = instances.unwrap().next().unwrap().unwrap();
+    assert_eq!(instance.instance_name, "SQLEXPRESS");
+    assert_eq!(instance.source, Some(forwarded));
+}
+
+#[test]
+fn receive_timeouts_expire_in_deadline_order() {
+    let network = MemoryNetwork::new();
+    let mut long = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let mut short = network.bind(SocketAddr::new(server_ip(2), 1434)).unwrap();
+    let mut long_buf = [0u8; 16];
+    let mut short_buf = [0u8; 16];
+
+    // The shorter timeout is registered last, but must not wait for the longer one
+    let start = std::time::Instant::now();
+    let long_recv = async {
+        let received = long
+            .recv_timeout(&mut long_buf, Duration::from_millis(300))
+            .await;
+        (received.unwrap(), start.elapsed())
+    };
+    let short_recv = async {
+        let received = short
+            .recv_timeout(&mut short_buf, Duration::from_millis(20))
+            .await;
+        (received.unwrap(), start.elapsed())
+    };
+    let ((long_received, long_elapsed), (short_received, short_elapsed)) =
+        futures::executor::block_on(async { futures::join!(long_recv, short_recv) });
+
+    assert_eq!(long_received, None);
+    assert_eq!(short_received, None);
+    assert!(short_elapsed < Duration::from_millis(200));
+    assert!(long_elapsed >= Duration::from_millis(300));
+}
+
+#[test]
+fn browse_instances_sends_all_requests_before_collecting_responses() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_secs(2)))
+        .retry(Some(
+            RetryPolicy::new(2).initial_delay(Duration::from_millis(50)),
+        ));
+    let mut factory = network.socket_factory();
+
+    let serve = async {
+        let mut buf = [0u8; 64];
+        let mut requests = Vec::new();
+        let mut client = None;
+        for _ in 0..3 {
+            let (len, source) = server.recv_from(&mut buf).await.unwrap();
+            requests.push(buf[..len].to_vec());
+            client = Some(source);
+        }
+        let client = client.unwrap();
+
+        // Answered in reverse order, with a malformed response for SQLEXPRESS
+        // and none for MSSQLSERVER until its request is retransmitted
+        let responses = [
+            instance_response("SRV1", "REPORTING"),
+            svr_resp("ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;No;Version;15.0.2000.5;tcp;none;;"),
+        ];
+        for response in &responses {
+            server.send_to(response, &client).await.unwrap();
+        }
+
+        let (len, source) = server.recv_from(&mut buf).await.unwrap();
+        requests.push(buf[..len].to_vec());
+        let response = instance_response("SRV1", "MSSQLSERVER");
+        server.send_to(&response, &source).await.unwrap();
+        requests
+    };
+
+    let (results, requests) = futures::executor::block_on(async {
+        futures::join!(
+            browse_instances_with_options(
+                server_ip(1),
+                &["MSSQLSERVER", "SQLEXPRESS", "REPORTING"],
+                &options,
+                &mut factory
+            ),
+            serve
+        )
+    });
+
+    assert_eq!(
+        requests,
+        vec![
+            b"\x04MSSQLSERVER\0".to_vec(),
+            b"\x04SQLEXPRESS\0".to_vec(),
+            b"\x04REPORTING\0".to_vec(),
+            b"\x04MSSQLSERVER\0".to_vec(),
+        ]
+    );
+
+    let results = results.unwrap();
+    let names = results
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["MSSQLSERVER", "SQLEXPRESS", "REPORTING"]);
+
+    let first = results[0].1.as_ref().unwrap().as_ref().unwrap();
+    assert_eq!(first.instance_name, "MSSQLSERVER");
+    match &results[1].1 {
+        Err(e) => match e.reason() {
+            BrowserProtocolError::UnexpectedToken {
+                expected: BrowserProtocolToken::TcpPort,
+                ..
+            } => {}
+            reason => panic!("expected an invalid tcp port, got {:?}", reason),
+        },
+        other => panic!("expected an invalid tcp port, got {:?}", other),
+    }
+    let third = results[2].1.as_ref().unwrap().as_ref().unwrap();
+    assert_eq!(third.instance_name, "REPORTING");
+}
+
+#[test]
+fn browse_host_by_name_probes_the_preferred_address_family() {
+    let network = MemoryNetwork::new();
+    let v4 = server_ip(1);
+    let v6 = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1));
+    network.add_host("sqlhost", vec![v4, v6]);
+    let mut v4_server = network.bind(SocketAddr::new(v4, 1434)).unwrap();
+    let mut v6_server = network.bind(SocketAddr::new(v6, 1434)).unwrap();
+    let mut factory = network.socket_factory();
+    let response = instance_response("SRV1", "SQLEXPRESS");
+
+    let options = BrowserOptions::new().address_preference(Some(AddressPreference::PreferV6));
+    let (instances, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_by_name_with_options("SQLHOST", &options, &mut factory),
+            answer(&mut v6_server, &response)
+        )
+    });
+    assert_eq!(instances.unwrap().next().unwrap().unwrap().addr, v6);
+
+    let options = BrowserOptions::new().address_preference(Some(AddressPreference::PreferV4));
+    let (instances, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_by_name_with_options("sqlhost", &options, &mut factory),
+            answer(&mut v4_server, &response)
+        )
+    });
+    assert_eq!(instances.unwrap().next().unwrap().unwrap().addr, v4);
+
+    // Neither request reached the server of the other family
+    let mut buf = [0u8; 16];
+    for server in [&mut v4_server, &mut v6_server].iter_mut() {
+        let received = futures::executor::block_on(
+            server.recv_from_timeout(&mut buf, Duration::from_millis(10)),
+        );
+        assert_eq!(received.unwrap(), None);
+    }
+
+    let options = BrowserOptions::new().address_preference(Some(AddressPreference::V6Only));
+    network.add_host("v4host", vec![v4]);
+    let result = futures::executor::block_on(browse_host_by_name_with_options(
+        "v4host",
+        &options,
+        &mut factory,
+    ));
+    assert!(matches!(result, Err(BrowserError::ResolveFailed(ref host, _)) if host == "v4host"));
+}
+
+#[test]
+fn browse_host_by_name_probes_all_addresses_concurrently() {
+    let network = MemoryNetwork::new();
+    let silent = server_ip(1);
+    let responsive = server_ip(2);
+    network.add_host("sqlhost", vec![silent, responsive]);
+    let _silent_server = network.bind(SocketAddr::new(silent, 1434)).unwrap();
+    let mut server = network.bind(SocketAddr::new(responsive, 1434)).unwrap();
+    let mut factory = network.socket_factory();
+    let response = instance_response("SRV1", "SQLEXPRESS");
+
+    // The first address never answers, which must not delay the response of the second
+    let options = BrowserOptions::new().timeout(Some(Duration::from_secs(5)));
+    let started = std::time::Instant::now();
+    let (instances, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_by_name_with_options("sqlhost", &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+    assert_eq!(instances.unwrap().next().unwrap().unwrap().addr, responsive);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn browse_host_accepts_responses_up_to_the_protocol_maximum_by_default() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let mut factory = network.socket_factory();
+
+    // A host with many instances sends a response well above 32 KiB
+    let body: String = (0..600)
+        .map(|i| {
+            format!(
+                "ServerName;SRV1;InstanceName;INSTANCE{};IsClustered;No;Version;15.0.2000.5;tcp;{};;",
+                i,
+                10000 + i
+            )
+        })
+        .collect();
+    let response = svr_resp(&body);
+    assert!(response.len() > 40 * 1024);
+
+    let options = BrowserOptions::new();
+    let (instances, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_with_options(server_ip(1), &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+    let mut instances = instances.unwrap();
+    let mut count = 0;
+    while instances.next().unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 600);
+
+    let options = BrowserOptions::new().max_response_size(32 * 1024);
+    let (result, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_with_options(server_ip(1), &options, &mut factory),
+            answer(&mut server, &response)
+        )
+    });
+    match result {
+        Err(BrowserError::ProtocolError(e)) => assert!(matches!(
+            e.reason(),
+            BrowserProtocolError::ResponseTooLarge { limit: 32768, .. }
+        )),
+        Err(e) => panic!("expected ResponseTooLarge, got {:?}", e),
+        Ok(_) => panic!("expected ResponseTooLarge"),
+    }
+}
+
+#[test]
+fn browse_host_with_dac_sends_the_dac_requests_concurrently() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(1), 1434)).unwrap();
+    let mut factory = network.socket_factory();
+    let response = svr_resp(
+        "ServerName;SRV1;InstanceName;INST1;IsClustered;No;Version;15.0.2000.5;tcp;1433;;\
+         ServerName;SRV1;InstanceName;INST2;IsClustered;No;Version;15.0.2000.5;tcp;1434;;",
+    );
+
+    // Both DAC requests arrive before either is answered, and only INST1 answers
+    let serve = async {
+        answer(&mut server, &response).await.unwrap();
+        let mut buf = [0u8; 64];
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let (len, client) = server.recv_from(&mut buf).await.unwrap();
+            requests.push((buf[..len].to_vec(), client));
+        }
+        for (request, client) in requests {
+            if request == b"\x0f\x01INST1\0" {
+                let dac = [0x05, 0x06, 0x00, 0x01, 0x2D, 0x05];
+                server.send_to(&dac, &client).await.unwrap();
+            }
+        }
+    };
+
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(300)));
+    let (instances, _) = futures::executor::block_on(async {
+        futures::join!(
+            browse_host_with_dac_with_options(server_ip(1), &options, &mut factory),
+            serve
+        )
+    });
+
+    let instances = instances.unwrap();
+    assert_eq!(instances.len(), 2);
+    assert_eq!(instances[0].instance_name, "INST1");
+    assert_eq!(instances[0].dac_info.as_ref().unwrap().port, 1325);
+    assert_eq!(instances[1].instance_name, "INST2");
+    assert!(instances[1].dac_info.is_none());
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "test-util")]
+
+use async_trait::async_trait;
+use mssql_browser::custom_socket::{self, SocketConfig, UdpSocket, UdpSocketFactory};
+use mssql_browser::test_util::{MemoryNetwork, MemorySocket, MemorySocketFactory, MulticastEvent};
+use mssql_browser::{BrowserError, BrowserOptions};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+fn events(network: &MemoryNetwork) -> Vec<MulticastEvent> {
+    network
+        .multicast_events()
+        .into_iter()
+        .map(|(_, event)| event)
+        .collect()
+}
+
+#[test]
+fn group_is_joined_while_browsing_and_left_on_drop() {
+    let network = MemoryNetwork::new();
+    let mut factory = network.socket_factory();
+    let group = Ipv4Addr::new(239, 255, 255, 250);
+    let options = BrowserOptions::new()
+        .join_multicast_group(true)
+        .timeout(Some(Duration::from_millis(10)));
+
+    futures::executor::block_on(async {
+        let mut iterator =
+            custom_socket::browse_with_options(IpAddr::V4(group), &options, &mut factory)
+                .await
+                .unwrap();
+
+        assert!(iterator.next().await.unwrap().is_none());
+        assert_eq!(
+            events(&network),
+            vec![MulticastEvent::JoinV4(group, Ipv4Addr::UNSPECIFIED)]
+        );
+    });
+
+    assert_eq!(
+        events(&network),
+        vec![
+            MulticastEvent::JoinV4(group, Ipv4Addr::UNSPECIFIED),
+            MulticastEvent::LeaveV4(group, Ipv4Addr::UNSPECIFIED)
+        ]
+    );
+}
+
+#[test]
+fn ipv6_group_is_joined_on_the_configured_interface() {
+    let network = MemoryNetwork::new();
+    let mut factory = network.socket_factory();
+    let group = Ipv6Addr::new(0xff05, 0, 0, 0, 0, 0, 0, 0x1434);
+    let options = BrowserOptions::new()
+        .join_multicast_group(true)
+        .multicast_interface_v6(Some(3));
+
+    futures::executor::block_on(async {
+        custom_socket::browse_with_options(IpAddr::V6(group), &options, &mut factory)
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(
+        events(&network),
+        vec![
+            MulticastEvent::JoinV6(group, 3),
+            MulticastEvent::LeaveV6(group, 3)
+        ]
+    );
+}
+
+#[test]
+fn group_is_not_joined_by_default() {
+    let network = MemoryNetwork::new();
+    let mut factory = network.socket_factory();
+    let group = IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250));
+
+    futures::executor::block_on(async {
+        custom_socket::browse(group, &mut factory).await.unwrap();
+    });
+
+    assert!(network.multicast_events().is_empty());
+}
+
+/// Binds sockets on a `MemoryNetwork` that fail to join any multicast group
+struct NoMulticastFactory(MemorySocketFactory);
+
+#[async_trait]
+impl UdpSocketFactory for NoMulticastFactory {
+    type Socket = NoMulticastSocket;
+    type Error = io::Error;
+
+    async fn bind(&mut self, addr: &SocketAddr) -> Result<NoMulticastSocket, io::Error> {
+        self.0.bind(addr).await.map(NoMulticastSocket)
+    }
+
+    async fn bind_with(
+        &mut self,
+        addr: &SocketAddr,
+        config: &SocketConfig,
+    ) -> Result<NoMulticastSocket, io::Error> {
+        self.0.bind_with(addr, config).await.map(NoMulticastSocket)
+    }
+}
+
+struct NoMulticastSocket(MemorySocket);
+
+#[async_trait]
+impl UdpSocket for NoMulticastSocket {
+    type Error = io::Error;
+
+    async fn enable_broadcast(&mut self) -> Result<(), io::Error> {
+        self.0.enable_broadcast().await
+    }
+
+    async fn connect(&mut self, addr: &SocketAddr) -> Result<(), io::Error> {
+        self.0.connect(addr).await
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        self.0.send(buf).await
+    }
+
+    async fn send_to(&mut self, buf: &[u8], addr: &SocketAddr) -> Result<usize, io::Error> {
+        self.0.send_to(buf, addr).await
+    }
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        self.0.recv(buf).await
+    }
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr), io::Error> {
+        self.0.recv_from(buf).await
+    }
+
+    async fn recv_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<usize>, io::Error> {
+        self.0.recv_timeout(buf, timeout).await
+    }
+
+    async fn recv_from_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, io::Error> {
+        self.0.recv_from_timeout(buf, timeout).await
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        UdpSocket::local_addr(&self.0)
+    }
+
+    fn join_multicast_v4(&self, _group: &Ipv4Addr, _interface: &Ipv4Addr) -> Result<(), io::Error> {
+        Err(io::ErrorKind::AddrNotAvailable.into())
+    }
+}
+
+#[test]
+fn failing_to_join_is_reported() {
+    let mut factory = NoMulticastFactory(MemoryNetwork::new().socket_factory());
+    let group = IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250));
+    let options = BrowserOptions::new().join_multicast_group(true);
+
+    let result = futures::executor::block_on(custom_socket::browse_with_options(
+        group,
+        &options,
+        &mut factory,
+    ));
+
+    match result {
+        Err(BrowserError::JoinMulticastFailed(addr, _)) => assert_eq!(addr, group),
+        Err(e) => panic!("expected JoinMulticastFailed, got {:?}", e),
+        Ok(_) => panic!("expected JoinMulticastFailed, got an iterator"),
+    }
+}
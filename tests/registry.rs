@@ -0,0 +1,96 @@
+use mssql_browser::{parse_responses, InstanceInfo, InstanceRegistry};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, SystemTime};
+
+fn instance(addr: Ipv4Addr, server_name: &str, instance_name: &str, port: u16) -> InstanceInfo {
+    let body = format!(
+        "ServerName;{};InstanceName;{};IsClustered;No;Version;15.0.2000.5;tcp;{};;",
+        server_name, instance_name, port
+    );
+    let mut datagram = vec![0x05];
+    datagram.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    datagram.extend_from_slice(body.as_bytes());
+
+    let instance = parse_responses(vec![(IpAddr::V4(addr), &datagram[..])])
+        .next()
+        .unwrap()
+        .unwrap();
+    instance
+}
+
+#[test]
+fn instances_seen_again_are_updated() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let later = start + Duration::from_secs(60);
+    let addr = Ipv4Addr::new(10, 0, 0, 1);
+    let mut registry = InstanceRegistry::new();
+
+    assert!(registry.insert_at(instance(addr, "SRV1", "SQLEXPRESS", 1433), start));
+    assert!(!registry.insert_at(instance(addr, "srv1", "sqlexpress", 50123), later));
+
+    assert_eq!(registry.len(), 1);
+    let known = registry.iter().next().unwrap();
+    assert_eq!(known.info.tcp_info.as_ref().unwrap().port, 50123);
+    assert_eq!(known.first_seen, start);
+    assert_eq!(known.last_seen, later);
+}
+
+#[test]
+fn older_information_does_not_replace_newer() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let earlier = start - Duration::from_secs(60);
+    let addr = Ipv4Addr::new(10, 0, 0, 1);
+    let mut registry = InstanceRegistry::new();
+
+    registry.insert_at(instance(addr, "SRV1", "SQLEXPRESS", 1433), start);
+    registry.insert_at(instance(addr, "SRV1", "SQLEXPRESS", 50123), earlier);
+
+    let known = registry.iter().next().unwrap();
+    assert_eq!(known.info.tcp_info.as_ref().unwrap().port, 1433);
+    assert_eq!(known.first_seen, earlier);
+    assert_eq!(known.last_seen, start);
+}
+
+#[test]
+fn instances_on_different_hosts_are_kept_apart() {
+    let mut registry = InstanceRegistry::new();
+    registry.insert(instance(
+        Ipv4Addr::new(10, 0, 0, 1),
+        "SRV1",
+        "SQLEXPRESS",
+        1433,
+    ));
+    registry.insert(instance(
+        Ipv4Addr::new(10, 0, 0, 2),
+        "SRV2",
+        "SQLEXPRESS",
+        1433,
+    ));
+    registry.insert(instance(Ipv4Addr::new(10, 0, 0, 3), "", "SQLEXPRESS", 1433));
+
+    let hosts = registry
+        .iter()
+        .map(|x| x.info.server_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(hosts, vec!["", "SRV1", "SRV2"]);
+}
+
+#[test]
+fn unseen_instances_are_removed() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let later = start + Duration::from_secs(60);
+    let mut registry = InstanceRegistry::new();
+    registry.insert_at(
+        instance(Ipv4Addr::new(10, 0, 0, 1), "SRV1", "A", 1433),
+        start,
+    );
+    registry.insert_at(
+        instance(Ipv4Addr::new(10, 0, 0, 1), "SRV1", "B", 1434),
+        later,
+    );
+
+    let removed = registry.remove_unseen_since(later);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].info.instance_name, "A");
+    assert_eq!(registry.len(), 1);
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "test-util")]
+
+use mssql_browser::custom_socket::{self, UdpSocket};
+use mssql_browser::test_util::{answer, MemoryNetwork, MemorySocket};
+use mssql_browser::{BrowserError, BrowserOptions, RetryPolicy};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+const DAC_RESPONSE: [u8; 6] = [0x05, 0x06, 0x00, 0x01, 0x9D, 0x05];
+
+fn server_ip() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))
+}
+
+fn retry_options() -> BrowserOptions {
+    BrowserOptions::new()
+        .timeout(Some(Duration::from_millis(500)))
+        .retry(Some(
+            RetryPolicy::new(3).initial_delay(Duration::from_millis(20)),
+        ))
+}
+
+/// Counts the requests the server received without answering them
+fn unanswered_requests(server: &mut MemorySocket) -> usize {
+    let mut buf = [0u8; 64];
+    let mut count = 0;
+    while futures::executor::block_on(server.recv_from_timeout(&mut buf, Duration::from_millis(10)))
+        .unwrap()
+        .is_some()
+    {
+        count += 1;
+    }
+    count
+}
+
+#[test]
+fn request_is_retransmitted_until_answered() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(), 1434)).unwrap();
+    let mut factory = network.socket_factory();
+    let options = retry_options();
+
+    // The first two requests are lost, the third one is answered
+    let serve = async {
+        let mut buf = [0u8; 64];
+        for _ in 0..2 {
+            server.recv_from(&mut buf).await.unwrap();
+        }
+        answer(&mut server, &DAC_RESPONSE).await
+    };
+    let (info, request) = futures::executor::block_on(async {
+        futures::join!(
+            custom_socket::browse_instance_dac_with_options(
+                server_ip(),
+                "MSSQLSERVER",
+                &options,
+                &mut factory,
+            ),
+            serve
+        )
+    });
+
+    assert_eq!(request.unwrap().0, b"\x0f\x01MSSQLSERVER\0".to_vec());
+    assert_eq!(info.unwrap().port, 1437);
+}
+
+#[test]
+fn retransmission_stops_after_all_attempts() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(), 1434)).unwrap();
+    let mut factory = network.socket_factory();
+    let options = retry_options();
+
+    let result = futures::executor::block_on(custom_socket::browse_instance_dac_with_options(
+        server_ip(),
+        "MSSQLSERVER",
+        &options,
+        &mut factory,
+    ));
+    match result {
+        Err(BrowserError::Timeout(_)) => {}
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    assert_eq!(unanswered_requests(&mut server), 3);
+}
+
+#[test]
+fn request_is_sent_once_without_retry_policy() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(), 1434)).unwrap();
+    let mut factory = network.socket_factory();
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(200)));
+
+    let result = futures::executor::block_on(custom_socket::browse_instance_dac_with_options(
+        server_ip(),
+        "MSSQLSERVER",
+        &options,
+        &mut factory,
+    ));
+    match result {
+        Err(BrowserError::Timeout(_)) => {}
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    assert_eq!(unanswered_requests(&mut server), 1);
+}
@@ -0,0 +1,54 @@
+use mssql_browser::parse_responses;
+use std::net::{IpAddr, Ipv4Addr};
+
+fn svr_resp(body: &[u8]) -> Vec<u8> {
+    let mut datagram = vec![0x05];
+    datagram.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    datagram.extend_from_slice(body);
+    datagram
+}
+
+#[test]
+fn every_endpoint_survives_a_round_trip() {
+    let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let datagram = svr_resp(
+        b"ServerName;SRV1;InstanceName;SQLEXPRESS;IsClustered;Yes;Version;15.0.2000.5;\
+          np;\\\\SRV1\\pipe\\sql\\query;tcp;1433;via;SRV1,0:1433,1:1434;rpc;SRV1;\
+          spx;SQLEXPRESS;adsp;SQLEXPRESS;bv;item;group;org;Edition;Express;;",
+    );
+
+    let instance = parse_responses(vec![(addr, &datagram[..])])
+        .next()
+        .unwrap()
+        .unwrap();
+    let serialized = instance.to_ssrp_bytes();
+    assert_eq!(serialized, datagram);
+
+    let reparsed = parse_responses(vec![(addr, &serialized[..])])
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(reparsed.instance_name, "SQLEXPRESS");
+    assert!(reparsed.is_clustered);
+    assert_eq!(reparsed.via_info.unwrap().addresses.len(), 2);
+    assert_eq!(reparsed.bv_info.unwrap().org_name, "org");
+}
+
+#[test]
+fn absent_endpoints_are_omitted() {
+    let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let datagram =
+        svr_resp(b"ServerName;SRV1;InstanceName;MSSQLSERVER;IsClustered;No;Version;16.0.1000.6;;");
+
+    let instance = parse_responses(vec![(addr, &datagram[..])])
+        .next()
+        .unwrap()
+        .unwrap();
+
+    let serialized = instance.to_ssrp_bytes();
+    assert_eq!(serialized, datagram);
+    assert_eq!(
+        u16::from_le_bytes([serialized[1], serialized[2]]) as usize,
+        serialized.len() - 3
+    );
+}
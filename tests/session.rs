@@ -0,0 +1,117 @@
+#![cfg(feature = "test-util")]
+
+use mssql_browser::custom_socket::{browser_session, UdpSocket};
+use mssql_browser::test_util::{answer, svr_resp, MemoryNetwork};
+use mssql_browser::{BrowserError, BrowserOptions, RetryPolicy};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+fn server_ip() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))
+}
+
+fn instance_response(instance_name: &str) -> Vec<u8> {
+    svr_resp(&format!(
+        "ServerName;SRV1;InstanceName;{};IsClustered;No;Version;15.0.2000.5;tcp;1433;;",
+        instance_name
+    ))
+}
+
+#[test]
+fn session_binds_to_an_ipv6_local_ip() {
+    let network = MemoryNetwork::new();
+    let server_ip = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1));
+    let local_ip = IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2));
+    let mut server = network.bind(SocketAddr::new(server_ip, 1434)).unwrap();
+    let options = BrowserOptions::new().local_ip(local_ip);
+    let mut factory = network.socket_factory();
+
+    let mut session = futures::executor::block_on(browser_session(&options, &mut factory)).unwrap();
+    assert_eq!(session.local_addr().unwrap().ip(), local_ip);
+
+    let response = instance_response("SQLEXPRESS");
+    let (info, request) = futures::executor::block_on(async {
+        futures::join!(
+            session.instance(server_ip, "SQLEXPRESS"),
+            answer(&mut server, &response)
+        )
+    });
+
+    assert_eq!(request.unwrap().1.ip(), local_ip);
+    assert_eq!(info.unwrap().addr, server_ip);
+}
+
+#[test]
+fn session_skips_responses_that_dont_match_the_request() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(), 1434)).unwrap();
+    let options = BrowserOptions::new().timeout(Some(Duration::from_millis(200)));
+    let mut factory = network.socket_factory();
+    let mut session = futures::executor::block_on(browser_session(&options, &mut factory)).unwrap();
+
+    // The request for SQLEXPRESS times out, its response arrives with the next request
+    let timed_out = futures::executor::block_on(session.instance(server_ip(), "SQLEXPRESS"));
+    assert!(matches!(timed_out, Err(BrowserError::Timeout(_))));
+
+    let serve = async {
+        let mut buf = [0u8; 64];
+        let (_, client) = server.recv_from(&mut buf).await.unwrap();
+        let responses = [
+            instance_response("SQLEXPRESS"),
+            svr_resp("ServerName;SRV1"),
+            instance_response("MSSQLSERVER"),
+        ];
+        for response in &responses {
+            server.send_to(response, &client).await.unwrap();
+        }
+    };
+    let (info, _) = futures::executor::block_on(async {
+        futures::join!(session.instance(server_ip(), "MSSQLSERVER"), serve)
+    });
+    assert_eq!(info.unwrap().instance_name, "MSSQLSERVER");
+
+    // Late instance responses, whether queued before the DAC request is sent or arriving
+    // afterwards, are skipped as they aren't DAC responses
+    let late = instance_response("SQLEXPRESS");
+    let client = session.local_addr().unwrap();
+    futures::executor::block_on(server.send_to(&late, &client)).unwrap();
+
+    let serve = async {
+        let mut buf = [0u8; 64];
+        let (_, client) = server.recv_from(&mut buf).await.unwrap();
+        server.send_to(&late, &client).await.unwrap();
+        let response = [0x05, 0x06, 0x00, 0x01, 0x2D, 0x05];
+        server.send_to(&response, &client).await.unwrap();
+    };
+    let (info, _) = futures::executor::block_on(async {
+        futures::join!(session.dac(server_ip(), "MSSQLSERVER"), serve)
+    });
+    assert_eq!(info.unwrap().port, 1325);
+}
+
+#[test]
+fn session_retransmits_and_tries_each_browser_port() {
+    let network = MemoryNetwork::new();
+    let mut server = network.bind(SocketAddr::new(server_ip(), 2434)).unwrap();
+    let options = BrowserOptions::new()
+        .timeout(Some(Duration::from_millis(300)))
+        .retry(Some(
+            RetryPolicy::new(2).initial_delay(Duration::from_millis(50)),
+        ))
+        .browser_ports(vec![1434, 2434]);
+    let mut factory = network.socket_factory();
+    let mut session = futures::executor::block_on(browser_session(&options, &mut factory)).unwrap();
+
+    // Nothing listens on port 1434, and the first request on port 2434 goes unanswered
+    let serve = async {
+        let mut buf = [0u8; 64];
+        server.recv_from(&mut buf).await.unwrap();
+        answer(&mut server, &instance_response("SQLEXPRESS")).await
+    };
+    let (info, request) = futures::executor::block_on(async {
+        futures::join!(session.instance(server_ip(), "SQLEXPRESS"), serve)
+    });
+
+    assert_eq!(request.unwrap().0, b"\x04SQLEXPRESS\0".to_vec());
+    assert_eq!(info.unwrap().source.unwrap().port(), 2434);
+}
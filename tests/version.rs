@@ -0,0 +1,27 @@
+use mssql_browser::SqlServerVersion;
+
+#[test]
+fn earlier_build_is_older() {
+    let rtm = SqlServerVersion::parse("15.0.2000.5").unwrap();
+    let baseline = SqlServerVersion::parse("15.0.4000.0").unwrap();
+
+    assert!(rtm < baseline);
+    assert!(!rtm.at_least(&baseline));
+    assert!(baseline.at_least(&rtm));
+    assert!(baseline.at_least(&baseline));
+}
+
+#[test]
+fn versions_are_ordered_by_each_component() {
+    let mut versions: Vec<_> = ["15.0.2000.5", "14.0.3456.2", "15.0.2000", "10.50.6000.34"]
+        .iter()
+        .map(|v| SqlServerVersion::parse(v).unwrap())
+        .collect();
+    versions.sort();
+
+    let sorted: Vec<_> = versions.iter().map(|v| v.to_string()).collect();
+    assert_eq!(
+        sorted,
+        vec!["10.50.6000.34", "14.0.3456.2", "15.0.2000.0", "15.0.2000.5"]
+    );
+}
@@ -0,0 +1,52 @@
+use mssql_browser::{
+    parse_responses, BrowserProtocolError, BrowserProtocolField, BrowserProtocolToken,
+};
+use std::net::{IpAddr, Ipv4Addr};
+
+fn parse_via(parameters: &str) -> Result<mssql_browser::ViaInfo, BrowserProtocolError> {
+    let body = format!(
+        "ServerName;SRV1;InstanceName;MSSQLSERVER;IsClustered;No;Version;15.0.2000.5;via;{};;",
+        parameters
+    );
+    let mut datagram = vec![0x05];
+    datagram.extend_from_slice(&(body.len() as u16).to_le_bytes());
+    datagram.extend_from_slice(body.as_bytes());
+
+    let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let instance = parse_responses(vec![(addr, &datagram[..])])
+        .next()
+        .unwrap()?;
+    Ok(instance.via_info.unwrap())
+}
+
+fn assert_missing(parameters: &str, field: BrowserProtocolField, offending: &str) {
+    match parse_via(parameters) {
+        Err(BrowserProtocolError::UnexpectedToken {
+            expected: BrowserProtocolToken::ValueOf(expected),
+            found: BrowserProtocolToken::Literal(found),
+        }) => {
+            assert_eq!(expected, field, "{}", parameters);
+            assert_eq!(found, offending, "{}", parameters);
+        }
+        other => panic!("unexpected result for {}: {:?}", parameters, other),
+    }
+}
+
+#[test]
+fn valid_via_parameters_are_parsed() {
+    let via = parse_via("SRV1,0:1433,1:1434").unwrap();
+    assert_eq!(via.machine_name, "SRV1");
+    assert_eq!(
+        via.endpoints().collect::<Vec<_>>(),
+        vec![("0", 1433), ("1", 1434)]
+    );
+}
+
+#[test]
+fn malformed_via_parameters_name_the_missing_part() {
+    assert_missing(",0:1433", BrowserProtocolField::ViaMachineName, ",0:1433");
+    assert_missing("SRV1", BrowserProtocolField::ViaNic, "SRV1");
+    assert_missing("SRV1,:1433", BrowserProtocolField::ViaNic, ":1433");
+    assert_missing("SRV1,0", BrowserProtocolField::ViaPort, "0");
+    assert_missing("SRV1,0:1433,1:", BrowserProtocolField::ViaPort, "1:");
+}